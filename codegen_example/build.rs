@@ -0,0 +1,10 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+fn main() {
+    println!("cargo:rerun-if-changed=src/grammar.peg");
+    let grammar = npeg_rs::Grammar::from_peg_string(include_str!("src/grammar.peg")).unwrap_or_else(|error| panic!("{}", error));
+    let source = npeg_rs_codegen::generate(&grammar).unwrap_or_else(|error| panic!("{}", error));
+    fs::write(Path::new(&env::var("OUT_DIR").unwrap()).join("arithmetic.rs"), source).unwrap();
+}
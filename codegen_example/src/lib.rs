@@ -0,0 +1,69 @@
+//! An example of `npeg_rs_codegen`; `build.rs` compiles `grammar.peg` to Rust, which is included here.
+
+pub mod arithmetic {
+    include!(concat!(env!("OUT_DIR"), "/arithmetic.rs"));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use npeg_rs::*;
+
+    /// The generated parser must produce exactly the same tree as the interpreter
+    #[test]
+    fn generated_parser_matches_interpreter() {
+        let grammar = Rc::new(Grammar::from_peg_string(include_str!("grammar.peg")).unwrap());
+        for text in [
+            "1",
+            "1+2*3",
+            "(1+2)*3^2^1",
+            "max(1,2.5,PI)-e",
+            "max(1,  2, 3)",
+            "pie",
+            "12.",
+            "2*(3",
+            "sqrt 4",
+            "1234567890",
+            "1.",
+            "ax",
+            "x^2",
+            "",
+        ] {
+            let mut context = Box::new(ParserContext::new(text));
+            let interpreted = grammar.clone().parse(&mut context, 0);
+            let generated = super::arithmetic::parse(text);
+            assert_eq!(format!("{:?}", generated), format!("{:?}", interpreted), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn generated_parser_has_the_interpreter_limits() {
+        let grammar = Rc::new(Grammar::from_peg_string(include_str!("grammar.peg")).unwrap());
+        let text = format!("{}1{}", "(".repeat(100_000), ")".repeat(100_000));
+        let mut context = Box::new(ParserContext::new(&text));
+        let interpreted = grammar.clone().try_parse(&mut context, 0).map(|result| result.is_some());
+        assert_eq!(super::arithmetic::try_parse(&text).map(|result| result.is_some()), interpreted);
+        assert!(matches!(interpreted, Err(ParseError::TooDeep { .. })));
+        assert!(super::arithmetic::parse(&text).is_none());
+
+        let limits = super::arithmetic::Limits { max_steps: Some(50), ..Default::default() };
+        assert!(matches!(super::arithmetic::try_parse_with_limits("1+2*3+4", &limits), Err(ParseError::TooManySteps { .. })));
+        let limits = super::arithmetic::Limits { max_memo_entries: Some(3), ..Default::default() };
+        assert!(matches!(super::arithmetic::try_parse_with_limits("1+2*3+4", &limits), Err(ParseError::TooManyMemoEntries { .. })));
+        let limits = super::arithmetic::Limits { deadline: Some(std::time::Instant::now()), ..Default::default() };
+        assert_eq!(super::arithmetic::try_parse_with_limits("1", &limits).unwrap_err(), ParseError::DeadlineExceeded { position: 0 });
+        assert!(super::arithmetic::try_parse("1+2*3+4").unwrap().is_some());
+    }
+
+    #[test]
+    fn generated_parser_labels_rules() {
+        let result = super::arithmetic::parse("max(1,2)").unwrap();
+        let result = result.get_root();
+        assert_eq!(result.get_label(), Some("Expression"));
+        assert_eq!(result.len(), 8);
+        // The nested grammar of Constant, which falls back to Letter in the enclosing grammar
+        assert_eq!(super::arithmetic::parse("max(1,2.5,PI)-e").unwrap().get_root().len(), 15);
+        assert!(super::arithmetic::parse("pie").is_none());
+    }
+}
@@ -0,0 +1,62 @@
+//! Compares the interpreter with the parsing machine on a generated arithmetic expression.
+//!
+//! `cargo run --release --example vm_benchmark`
+
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use npeg_rs::*;
+
+const GRAMMAR: &str = r#"
+    Expression <- Sum !r"."s
+    Sum        <- Product (operator:("+" / "-") Product)*
+    Product    <- Atom (operator:("*" / "/") Atom)*
+    Atom       <- Number / "(" Sum ")"
+    Number     <- r"[0-9]+"
+"#;
+
+/// An expression of about `size` terms with a little nesting
+fn expression(size: usize) -> String {
+    let mut text = String::new();
+    for index in 0..size {
+        if index > 0 {
+            text.push(['+', '-', '*', '/'][index % 4]);
+        }
+        if index % 7 == 0 {
+            text.push_str(&format!("({}*{})", index, index + 1));
+        } else {
+            text.push_str(&index.to_string());
+        }
+    }
+    text
+}
+
+fn time<T>(iterations: u32, mut run: impl FnMut() -> T) -> Duration {
+    let start = Instant::now();
+    for _ in 0..iterations {
+        std::hint::black_box(run());
+    }
+    start.elapsed() / iterations
+}
+
+fn main() {
+    let grammar = Rc::new(Grammar::from_peg_string(GRAMMAR).unwrap());
+    let program = Program::compile(&grammar).unwrap();
+    for size in [100, 1_000, 10_000] {
+        let text = expression(size);
+        let mut context = Box::new(ParserContext::new(&text));
+        let interpreted = grammar.clone().parse(&mut context, 0);
+        assert_eq!(format!("{:?}", program.parse(&text)), format!("{:?}", interpreted));
+        let iterations = (100_000 / size) as u32;
+        let interpreter = time(iterations, || grammar.clone().parse(&mut Box::new(ParserContext::new(&text)), 0));
+        let machine = time(iterations, || program.parse(&text));
+        println!(
+            "{:>6} terms, {:>7} bytes: interpreter {:>12?}, machine {:>12?} ({:.1}x)",
+            size,
+            text.len(),
+            interpreter,
+            machine,
+            interpreter.as_secs_f64() / machine.as_secs_f64()
+        );
+    }
+}
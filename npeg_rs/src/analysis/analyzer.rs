@@ -0,0 +1,394 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use super::regex_info::regex_info;
+use super::{Analysis, AnalysisWarning, FirstSet};
+use crate::core::{OpKind, Parser};
+use crate::ops::{Grammar, ResolvedRule};
+
+/// What is known about the matches of an op
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct OpInfo {
+    /// Can match without consuming input
+    pub(crate) nullable: bool,
+    /// Matches at every position
+    pub(crate) infallible: bool,
+    pub(crate) first: FirstSet,
+    /// Matches exactly this text, wherever the input starts with it, and fails everywhere else
+    pub(crate) fixed: Option<String>,
+    /// Every match starts with this text
+    pub(crate) prefix: String,
+}
+
+impl OpInfo {
+    /// For ops the analysis can not see into; assumed to consume input
+    pub(crate) fn unknown() -> OpInfo {
+        OpInfo { first: FirstSet::any(), ..OpInfo::default() }
+    }
+    pub(crate) fn literal(literal_text: &str) -> OpInfo {
+        let mut first = FirstSet::default();
+        if let Some(byte) = literal_text.bytes().next() {
+            first.insert(byte);
+        }
+        OpInfo {
+            nullable: literal_text.is_empty(),
+            infallible: literal_text.is_empty(),
+            first,
+            fixed: Some(literal_text.to_owned()),
+            prefix: literal_text.to_owned(),
+        }
+    }
+    pub(crate) fn sequence(children: impl Iterator<Item = OpInfo>) -> OpInfo {
+        let mut info = OpInfo::literal("");
+        // Whether every child so far has a fixed text, so that the prefix may still grow
+        let mut prefix_open = true;
+        for child in children {
+            if info.nullable {
+                info.first.union(&child.first);
+            }
+            if prefix_open {
+                info.prefix.push_str(&child.prefix);
+                prefix_open = child.fixed.is_some();
+            }
+            info.fixed = info.fixed.zip(child.fixed).map(|(fixed, child_fixed)| fixed + &child_fixed);
+            info.nullable &= child.nullable;
+            info.infallible &= child.infallible;
+        }
+        info
+    }
+    pub(crate) fn alternation(children: impl Iterator<Item = OpInfo>) -> OpInfo {
+        let mut info: Option<OpInfo> = None;
+        for child in children {
+            info = Some(match info {
+                None => child,
+                Some(mut info) => {
+                    info.nullable |= child.nullable;
+                    info.infallible |= child.infallible;
+                    info.first.union(&child.first);
+                    if info.fixed != child.fixed {
+                        info.fixed = None;
+                    }
+                    let common = info.prefix.char_indices().zip(child.prefix.chars()).find(|((_, a), b)| a != b).map_or(
+                        info.prefix.len().min(child.prefix.len()),
+                        |((index, _), _)| index,
+                    );
+                    info.prefix.truncate(common);
+                    info
+                },
+            });
+        }
+        info.unwrap_or_default()
+    }
+    /// `child` repeated at least `minimum_occurrences` times; `fixed` is only right if it is repeated exactly that often
+    pub(crate) fn repeat(child: OpInfo, minimum_occurrences: usize) -> OpInfo {
+        let prefix = match (&child.fixed, minimum_occurrences) {
+            (_, 0) => String::new(),
+            (Some(fixed), _) => fixed.repeat(minimum_occurrences),
+            (None, _) => child.prefix.clone(),
+        };
+        OpInfo {
+            nullable: minimum_occurrences == 0 || child.nullable,
+            infallible: minimum_occurrences == 0 || child.infallible,
+            fixed: child.fixed.as_ref().map(|fixed| fixed.repeat(minimum_occurrences)),
+            first: child.first,
+            prefix,
+        }
+    }
+    /// Combines what was known about a rule with a new result; what is known only ever grows, so iteration ends
+    fn merge(&self, other: &OpInfo) -> OpInfo {
+        let mut first = self.first.clone();
+        first.union(&other.first);
+        OpInfo {
+            nullable: self.nullable || other.nullable,
+            infallible: self.infallible || other.infallible,
+            first,
+            fixed: other.fixed.clone().or_else(|| self.fixed.clone()),
+            prefix: if other.prefix.len() > self.prefix.len() { other.prefix.clone() } else { self.prefix.clone() },
+        }
+    }
+}
+
+/// A grammar on the stack used to resolve rule references, like the stack of rule sets in `ParserContext`.
+/// Nested grammars are borrowed from the op tree, while imported grammars are shared.
+enum RuleSet<'a, S> {
+    Borrowed(&'a Grammar<S>),
+    Shared(Rc<Grammar<S>>),
+}
+
+// Not derived, since that would require `S: Clone`
+impl<S> Clone for RuleSet<'_, S> {
+    fn clone(&self) -> Self {
+        match self {
+            RuleSet::Borrowed(grammar) => RuleSet::Borrowed(grammar),
+            RuleSet::Shared(grammar) => RuleSet::Shared(grammar.clone()),
+        }
+    }
+}
+
+impl<S> RuleSet<'_, S> {
+    fn grammar(&self) -> &Grammar<S> {
+        match self {
+            RuleSet::Borrowed(grammar) => grammar,
+            RuleSet::Shared(grammar) => grammar,
+        }
+    }
+}
+
+/// Rules are analyzed once for each stack of rule sets they are used with
+type RuleKey = (Vec<usize>, usize);
+
+#[derive(Default)]
+pub(crate) struct Analyzer {
+    rules: HashMap<RuleKey, OpInfo>,
+    /// The rules analyzed in the current pass
+    visited: HashSet<RuleKey>,
+    /// Rules which reached a fixed point in an earlier call to `first_sets()`, and are not analyzed again
+    settled: HashSet<RuleKey>,
+    changed: bool,
+    /// Set for the last pass, once the rules have reached a fixed point
+    report: bool,
+    analysis: Analysis,
+    /// The rule being analyzed, and its parameters
+    rule_name: String,
+    parameters: Vec<Rc<String>>,
+}
+
+impl Analyzer {
+    pub(crate) fn analyze<S>(mut self, grammar: &Grammar<S>) -> Analysis {
+        let mut rule_names = vec![];
+        collect_rule_names(grammar, "", &mut rule_names);
+        let stack = [RuleSet::Borrowed(grammar)];
+        loop {
+            self.changed = false;
+            self.visited.clear();
+            let infos: Vec<(String, OpInfo)> = rule_names
+                .iter()
+                .filter_map(|rule_name| {
+                    let (resolved, rule_sets) = resolve(&stack, rule_name)?;
+                    Some((rule_name.clone(), self.analyze_rule(&rule_sets, &resolved)))
+                })
+                .collect();
+            if self.report {
+                for (rule_name, info) in infos {
+                    self.analysis.rules.insert(rule_name, (info.nullable, info.first));
+                }
+                return self.analysis;
+            }
+            self.report = !self.changed;
+        }
+    }
+
+    /// Whether each of `ops` is nullable, and its FIRST set, where `rule_sets` are in effect within a rule with `parameters`.
+    /// Rules analyzed by earlier calls on the same `Analyzer` are not analyzed again.
+    pub(crate) fn first_sets<S>(&mut self, rule_sets: &[Rc<Grammar<S>>], parameters: &[Rc<String>], ops: &[Rc<dyn Parser<S>>]) -> Vec<(bool, FirstSet)> {
+        let stack: Vec<RuleSet<'_, S>> = rule_sets.iter().cloned().map(RuleSet::Shared).collect();
+        self.parameters = parameters.to_vec();
+        loop {
+            self.changed = false;
+            self.visited.clear();
+            let infos: Vec<OpInfo> = ops.iter().map(|op| self.analyze_op(&stack, op.as_ref())).collect();
+            if !self.changed {
+                // Nothing changed in this pass, so every rule it analyzed has reached its fixed point
+                self.settled.extend(self.visited.drain());
+                return infos.into_iter().map(|info| (info.nullable, info.first)).collect();
+            }
+        }
+    }
+
+    fn analyze_rule<S>(&mut self, stack: &[RuleSet<'_, S>], resolved: &ResolvedRule<S>) -> OpInfo {
+        let key = (stack.iter().map(|rule_set| rule_set.grammar().get_id()).collect(), resolved.parser_operator.get_id());
+        if self.settled.contains(&key) {
+            return self.rules[&key].clone();
+        }
+        if !self.visited.insert(key.clone()) {
+            // Either analyzed already in this pass, or being analyzed further up; use what is known so far
+            return self.rules.get(&key).cloned().unwrap_or_default();
+        }
+        let previous_rule_name = std::mem::replace(&mut self.rule_name, resolved.rule_name.to_string());
+        let previous_parameters = std::mem::replace(&mut self.parameters, resolved.parameters.clone());
+        let info = self.analyze_op(stack, resolved.parser_operator.as_ref());
+        self.rule_name = previous_rule_name;
+        self.parameters = previous_parameters;
+        let known = self.rules.entry(key).or_default();
+        let merged = known.merge(&info);
+        if merged != *known {
+            self.changed = true;
+            *known = merged.clone();
+        }
+        merged
+    }
+
+    fn analyze_op<S>(&mut self, stack: &[RuleSet<'_, S>], op: &dyn Parser<S>) -> OpInfo {
+        let info = match op.kind() {
+            OpKind::Literal(literal_text) => OpInfo::literal(literal_text),
+            OpKind::ByteLiteral(literal_bytes) => {
+                let mut first = FirstSet::default();
+                first.insert(literal_bytes[0]);
+                OpInfo { first, ..OpInfo::default() }
+            },
+            OpKind::ByteClass(ranges) => {
+                let mut first = FirstSet::default();
+                for byte in ranges.iter().flat_map(|range| range.clone()) {
+                    first.insert(byte);
+                }
+                OpInfo { first, ..OpInfo::default() }
+            },
+            // The captured length may be zero
+            OpKind::Take { .. } => OpInfo { nullable: true, ..OpInfo::unknown() },
+            OpKind::Regex { pattern, multi_line, case_insensitive, dot_matches_new_line } => regex_info(pattern, multi_line, case_insensitive, dot_matches_new_line),
+            OpKind::Sequence(children) => {
+                let infos: Vec<OpInfo> = children.iter().map(|child| self.analyze_op(stack, child.as_ref())).collect();
+                OpInfo::sequence(infos.into_iter())
+            },
+            OpKind::Alternation(children) => {
+                let infos: Vec<OpInfo> = children.iter().map(|child| self.analyze_op(stack, child.as_ref())).collect();
+                if self.report {
+                    self.check_alternatives(op, &infos);
+                }
+                OpInfo::alternation(infos.into_iter())
+            },
+            OpKind::Quantity { child, minimum_occurrences, .. } => {
+                let child = self.analyze_op(stack, child.as_ref());
+                if self.report && child.nullable {
+                    self.warn(AnalysisWarning::NullableRepetition { rule_name: self.rule_name.clone(), quantity: op.to_peg_string() });
+                }
+                let mut info = OpInfo::repeat(child, minimum_occurrences);
+                // A `Quantity` always allows more than its minimum
+                info.fixed = None;
+                info
+            },
+            OpKind::SepBy { element, separator, minimum_occurrences, .. } => {
+                let element = self.analyze_op(stack, element.as_ref());
+                let separator = self.analyze_op(stack, separator.as_ref());
+                let mut first = element.first.clone();
+                if element.nullable {
+                    first.union(&separator.first);
+                }
+                // Each element after the first must consume input along with its separator
+                OpInfo {
+                    nullable: minimum_occurrences == 0 || (minimum_occurrences == 1 && element.nullable),
+                    infallible: minimum_occurrences == 0 || (minimum_occurrences == 1 && element.infallible),
+                    first,
+                    fixed: None,
+                    prefix: if minimum_occurrences == 0 { String::new() } else { element.prefix },
+                }
+            },
+            OpKind::Until { child, terminator } => {
+                let child = self.analyze_op(stack, child.as_ref());
+                let terminator = self.analyze_op(stack, terminator.as_ref());
+                let mut first = terminator.first.clone();
+                first.union(&child.first);
+                OpInfo { nullable: terminator.nullable, infallible: terminator.infallible, first, ..OpInfo::default() }
+            },
+            // The count may be zero
+            OpKind::Counted { child, .. } => OpInfo { nullable: true, first: self.analyze_op(stack, child.as_ref()).first, ..OpInfo::default() },
+            OpKind::Lookahead { child, scout, .. } => {
+                self.analyze_op(stack, scout.as_ref());
+                // The scout may fail, so the child's text alone no longer decides the match
+                OpInfo { infallible: false, fixed: None, ..self.analyze_op(stack, child.as_ref()) }
+            },
+            OpKind::Action { child, .. } | OpKind::Label { child, .. } | OpKind::Capture { child, .. } => self.analyze_op(stack, child.as_ref()),
+            // The check may reject any match of the child
+            OpKind::Predicate { child, .. } => OpInfo { infallible: false, fixed: None, ..self.analyze_op(stack, child.as_ref()) },
+            OpKind::OperatorTable(table) => {
+                let atom = self.analyze_op(stack, table.get_atom().as_ref());
+                let prefixes: Vec<OpInfo> = table
+                    .get_prefix_operators()
+                    .iter()
+                    .map(|operator| self.analyze_op(stack, operator.get_parser().as_ref()))
+                    .collect();
+                for operator in table.get_infix_operators().iter().chain(table.get_postfix_operators()) {
+                    self.analyze_op(stack, operator.get_parser().as_ref());
+                }
+                // An expression starts with a prefix operator or the atom; operators only match if they consume input
+                let (nullable, infallible) = (atom.nullable, atom.infallible);
+                OpInfo { nullable, infallible, fixed: None, ..OpInfo::alternation(std::iter::once(atom).chain(prefixes)) }
+            },
+            OpKind::RuleReference(rule_name) if self.parameters.iter().any(|parameter| parameter.as_str() == rule_name) => OpInfo::unknown(),
+            OpKind::RuleReference(rule_name) => match resolve(stack, rule_name) {
+                Some((resolved, rule_sets)) if resolved.parameters.is_empty() => self.analyze_rule(&rule_sets, &resolved),
+                _ => OpInfo::unknown(),
+            },
+            OpKind::RuleCall { rule_name, arguments } => {
+                for argument in arguments {
+                    self.analyze_op(stack, argument.as_ref());
+                }
+                match resolve(stack, rule_name) {
+                    // The parameters are unknown within the body, whatever the arguments are
+                    Some((resolved, rule_sets)) => self.analyze_rule(&rule_sets, &resolved),
+                    None => OpInfo::unknown(),
+                }
+            },
+            OpKind::Grammar(grammar) => {
+                let mut rule_sets = stack.to_vec();
+                rule_sets.push(RuleSet::Borrowed(grammar));
+                match grammar.get_starting_rule() {
+                    Some((rule_name, parser_operator)) => {
+                        let resolved = ResolvedRule { parameters: vec![], rule_name, parser_operator, rule_sets: None };
+                        self.analyze_rule(&rule_sets, &resolved)
+                    },
+                    None => OpInfo::unknown(),
+                }
+            },
+            OpKind::AnyGrapheme
+            | OpKind::BackReference(_)
+            | OpKind::Dedent
+            | OpKind::Indent
+            | OpKind::Integer { .. }
+            | OpKind::Samedent
+            | OpKind::Other => OpInfo::unknown(),
+        };
+        if self.report {
+            self.analysis.ops.entry(op.get_id()).or_insert_with(|| (info.nullable, info.first.clone()));
+        }
+        info
+    }
+
+    /// An alternative is unreachable if an earlier one matches at every position,
+    /// or matches a fixed text that every match of the later one starts with
+    fn check_alternatives<S>(&mut self, alternation: &dyn Parser<S>, infos: &[OpInfo]) {
+        for (index, info) in infos.iter().enumerate() {
+            let shadowed_by = infos[..index].iter().position(|earlier| {
+                earlier.infallible || earlier.fixed.as_ref().is_some_and(|fixed| info.prefix.starts_with(fixed.as_str()))
+            });
+            if let Some(shadowed_by) = shadowed_by {
+                self.warn(AnalysisWarning::UnreachableAlternative {
+                    rule_name: self.rule_name.clone(),
+                    alternation: alternation.to_peg_string(),
+                    index,
+                    shadowed_by,
+                });
+            }
+        }
+    }
+
+    /// Rules used with more than one stack of rule sets are analyzed more than once, but warned about once
+    fn warn(&mut self, warning: AnalysisWarning) {
+        if !self.analysis.warnings.contains(&warning) {
+            self.analysis.warnings.push(warning);
+        }
+    }
+}
+
+/// The names of the rules of `grammar` and of its imports, with their namespaces
+fn collect_rule_names<S>(grammar: &Grammar<S>, namespace: &str, rule_names: &mut Vec<String>) {
+    for (rule_name, _) in grammar.get_all_rules() {
+        rule_names.push(format!("{}{}", namespace, rule_name));
+    }
+    for (import_namespace, import) in grammar.get_all_imports() {
+        collect_rule_names(&import, &format!("{}{}::", namespace, import_namespace), rule_names);
+    }
+}
+
+/// Looks up a rule the way `ParserContext::resolve_rule` does, returning it with the stack of rule sets it is used with
+fn resolve<'a, S>(stack: &[RuleSet<'a, S>], rule_name: &str) -> Option<(ResolvedRule<S>, Vec<RuleSet<'a, S>>)> {
+    stack.iter().enumerate().rev().find_map(|(index, rule_set)| {
+        rule_set.grammar().resolve_rule(rule_name).map(|mut resolved| {
+            let rule_sets = match resolved.rule_sets.take() {
+                Some(rule_sets) => rule_sets.into_iter().map(RuleSet::Shared).collect(),
+                None => stack[..=index].to_vec(),
+            };
+            (resolved, rule_sets)
+        })
+    })
+}
@@ -0,0 +1,38 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::FirstSet;
+
+/// `DispatchTable`
+///
+/// The children of an `Alternation` which can match, in order, for each possible next byte and for the end of the input.
+/// A child is a candidate for a byte if its FIRST set contains the byte, if it is nullable, or if the analysis could not
+/// see into it; ops like `Dedent` and `BackReference` are assumed to consume input, but may in fact match nothing.
+#[derive(Debug)]
+pub(crate) struct DispatchTable {
+    /// 257 entries; the last is for the end of the input. Equal lists are shared.
+    candidates: Vec<Rc<[usize]>>,
+}
+
+impl DispatchTable {
+    /// `first_sets` are the nullability and FIRST set of each child, as returned by `Analyzer::first_sets()`
+    pub(crate) fn new(first_sets: &[(bool, FirstSet)]) -> DispatchTable {
+        let mut interned: HashMap<Vec<usize>, Rc<[usize]>> = HashMap::new();
+        let candidates = (0..=256)
+            .map(|next| {
+                let indices: Vec<usize> = first_sets
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (nullable, first))| *nullable || first.is_any() || (next < 256 && first.contains(next as u8)))
+                    .map(|(index, _)| index)
+                    .collect();
+                interned.entry(indices.clone()).or_insert_with(|| indices.into()).clone()
+            })
+            .collect();
+        DispatchTable { candidates }
+    }
+    /// The indices of the children worth trying, given the byte at the start position, or `None` at the end of the input
+    pub(crate) fn get_candidates(&self, next_byte: Option<u8>) -> &[usize] {
+        &self.candidates[next_byte.map_or(256, usize::from)]
+    }
+}
@@ -0,0 +1,287 @@
+//! Static analysis of grammars; see `Grammar::analyze()`
+//!
+//! Computes, for every op, whether it can match without consuming input (nullable) and which bytes a match can start
+//! with (its FIRST set), and warns about alternatives which can never be chosen and repetitions of nullable ops.
+//!
+//! Rules which refer to each other are solved by iterating until nothing changes. Ops whose match depends on the
+//! parser state (`BackReference`, `Indent`, `Samedent`, `Dedent`), parameters of parameterized rules and ops
+//! defined outside this crate are assumed to consume input, and to start with any byte. `Take` and `Counted` may match nothing.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::core::Parser;
+use crate::ops::Grammar;
+use crate::vm::ByteSet;
+
+mod analyzer;
+mod dispatch;
+mod regex_info;
+
+pub(crate) use self::analyzer::Analyzer;
+pub(crate) use self::dispatch::DispatchTable;
+
+/// The bytes a match can start with
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FirstSet {
+    bytes: ByteSet,
+    any: bool,
+}
+
+impl FirstSet {
+    pub(crate) fn any() -> FirstSet {
+        FirstSet { bytes: ByteSet::default(), any: true }
+    }
+    pub(crate) fn insert(&mut self, byte: u8) {
+        self.bytes.insert(byte);
+    }
+    pub(crate) fn union(&mut self, other: &FirstSet) {
+        self.bytes.union(&other.bytes);
+        self.any |= other.any;
+    }
+    pub fn contains(&self, byte: u8) -> bool {
+        self.any || self.bytes.contains(byte)
+    }
+    /// Whether a match may start with any byte, because the analysis could not see into the op
+    pub fn is_any(&self) -> bool {
+        self.any
+    }
+    /// True for ops which only match without consuming input
+    pub fn is_empty(&self) -> bool {
+        !self.any && self.bytes.is_empty()
+    }
+    /// The bytes, not including those implied by `is_any()`
+    pub fn get_bytes(&self) -> &ByteSet {
+        &self.bytes
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AnalysisWarning {
+    /// An `Alternation` branch can never be chosen, because an earlier branch always matches a prefix of whatever
+    /// it would match; `alternation` is the alternation written as PEG text, and the indices count from zero
+    UnreachableAlternative { rule_name: String, alternation: String, index: usize, shadowed_by: usize },
+    /// The child of a `Quantity` can match without consuming input; `quantity` is the quantity written as PEG text
+    NullableRepetition { rule_name: String, quantity: String },
+}
+
+impl fmt::Display for AnalysisWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnalysisWarning::UnreachableAlternative { rule_name, alternation, index, shadowed_by } => write!(
+                f,
+                "Rule {}: alternative {} of {} is unreachable, because alternative {} always matches first",
+                rule_name, index, alternation, shadowed_by,
+            ),
+            AnalysisWarning::NullableRepetition { rule_name, quantity } => write!(
+                f,
+                "Rule {}: the repeated expression in {} can match without consuming input",
+                rule_name, quantity,
+            ),
+        }
+    }
+}
+
+/// `Analysis`
+///
+/// The result of `Grammar::analyze()`. Ops are looked up by identity, so only the ops of the analyzed grammar are found.
+#[derive(Debug, Default)]
+pub struct Analysis {
+    /// By op id
+    ops: HashMap<usize, (bool, FirstSet)>,
+    /// By the name the rule is referenced by from the analyzed grammar, including those of imported grammars (`json::Value`)
+    rules: HashMap<String, (bool, FirstSet)>,
+    warnings: Vec<AnalysisWarning>,
+}
+
+impl Analysis {
+    pub fn is_nullable<S>(&self, op: &dyn Parser<S>) -> Option<bool> {
+        self.ops.get(&op.get_id()).map(|(nullable, _)| *nullable)
+    }
+    pub fn get_first_set<S>(&self, op: &dyn Parser<S>) -> Option<&FirstSet> {
+        self.ops.get(&op.get_id()).map(|(_, first_set)| first_set)
+    }
+    pub fn is_rule_nullable(&self, rule_name: &str) -> Option<bool> {
+        self.rules.get(rule_name).map(|(nullable, _)| *nullable)
+    }
+    pub fn get_rule_first_set(&self, rule_name: &str) -> Option<&FirstSet> {
+        self.rules.get(rule_name).map(|(_, first_set)| first_set)
+    }
+    pub fn get_warnings(&self) -> &[AnalysisWarning] {
+        &self.warnings
+    }
+}
+
+impl<S> Grammar<S> {
+    /// Analyzes every rule of this grammar, its base grammars and its imports; see `Analysis`
+    ///
+    /// ```
+    /// # use npeg_rs::*;
+    /// let gram: Grammar = Grammar::new(None, vec![
+    ///     ("Keyword", alt!(lit!("in"), lit!("int"))),
+    /// ]);
+    /// let analysis = gram.analyze();
+    /// assert!(matches!(analysis.get_warnings()[0], AnalysisWarning::UnreachableAlternative { index: 1, shadowed_by: 0, .. }));
+    /// assert!(analysis.get_rule_first_set("Keyword").unwrap().contains(b'i'));
+    /// ```
+    pub fn analyze(&self) -> Analysis {
+        analyzer::Analyzer::default().analyze(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::*;
+
+    #[test]
+    fn nullable_and_first_sets() {
+        let optional = qtt!(lit!("x"), 0, Some(1));
+        let gram: Grammar = Grammar::new(
+            None,
+            vec![
+                ("List",   seq!(rul!("Spaces"), lit!("["), qtt!(rul!("Item"), 0, None), lit!("]"))),
+                ("Item",   alt!(rul!("Number"), reg!("(?i)true|false"), optional.clone())),
+                ("Number", reg!("-?[0-9]+")),
+                ("Spaces", reg!(" *")),
+            ]
+        );
+        let analysis = gram.analyze();
+        assert_eq!(analysis.is_rule_nullable("Spaces"), Some(true));
+        assert_eq!(analysis.is_rule_nullable("List"), Some(false));
+        assert_eq!(analysis.is_rule_nullable("Item"), Some(true));
+        assert_eq!(analysis.is_nullable(optional.as_ref()), Some(true));
+
+        let list = analysis.get_rule_first_set("List").unwrap();
+        assert!(list.contains(b' ') && list.contains(b'[') && !list.contains(b'1'));
+        let item = analysis.get_rule_first_set("Item").unwrap();
+        assert!(!item.is_any());
+        assert!("-0123456789tTfFx".bytes().all(|byte| item.contains(byte)));
+        assert!(!item.contains(b'a'));
+        assert_eq!(analysis.get_rule_first_set("Missing"), None);
+    }
+
+    #[test]
+    fn byte_ops() {
+        let gram: Grammar = Grammar::new(
+            None,
+            vec![
+                ("Field",  seq!(rul!("Length"), take!("length"), alt!(byt!(b"\xff\xfe"), bcl!(b'0'..=b'9', b'_'..=b'_')))),
+                ("Length", cap!(int!(2, Big), "length")),
+            ]
+        );
+        let analysis = gram.analyze();
+        assert_eq!(analysis.is_rule_nullable("Length"), Some(false));
+        assert!(analysis.get_rule_first_set("Length").unwrap().is_any());
+        let (_, field) = gram.get_rule_by_name("Field").unwrap();
+        let OpKind::Sequence(children) = field.kind() else {
+            panic!("Expected a Sequence")
+        };
+        assert_eq!(analysis.is_nullable(children[1].as_ref()), Some(true));
+        let bytes = analysis.get_first_set(children[2].as_ref()).unwrap();
+        assert!(!bytes.is_any());
+        assert!(b"\xff0123456789_".iter().all(|byte| bytes.contains(*byte)));
+        assert!(!bytes.contains(b'a') && !bytes.contains(0xfe));
+    }
+
+    #[test]
+    fn list_ops() {
+        let gram = Grammar::new(
+            None,
+            vec![
+                ("Args",    sep!(reg!("[a-z]+"), lit!(","))),
+                ("Pairs",   Rc::new(SepBy::<()>::new(lit!("k"), lit!(";")).with_occurrences(2, None))),
+                ("Comment", unt!(reg!("(?s)."), lit!("*/"))),
+                ("Counts",  cnt!(lit!("x"), "n")),
+            ]
+        );
+        let analysis = gram.analyze();
+        assert_eq!(analysis.is_rule_nullable("Args"), Some(true));
+        assert_eq!(analysis.is_rule_nullable("Pairs"), Some(false));
+        assert_eq!(analysis.is_rule_nullable("Comment"), Some(false));
+        assert_eq!(analysis.is_rule_nullable("Counts"), Some(true));
+        let args = analysis.get_rule_first_set("Args").unwrap();
+        assert!(!args.is_any() && args.contains(b'a') && !args.contains(b','));
+        let pairs = analysis.get_rule_first_set("Pairs").unwrap();
+        assert!(pairs.contains(b'k') && !pairs.contains(b';'));
+        let comment = analysis.get_rule_first_set("Comment").unwrap();
+        assert!(comment.contains(b'*') && comment.contains(b'a'));
+        let counts = analysis.get_rule_first_set("Counts").unwrap();
+        assert!(!counts.is_any() && counts.contains(b'x'));
+    }
+
+    #[test]
+    fn recursive_rules_reach_a_fixed_point() {
+        let gram: Grammar = Grammar::new(
+            None,
+            vec![
+                ("Nested", alt!(seq!(lit!("("), rul!("Nested"), lit!(")")), rul!("Empty"))),
+                ("Empty",  qtt!(lit!(" "), 0, Some(1))),
+                ("Left",   seq!(rul!("Right"), lit!("l"))),
+                ("Right",  alt!(seq!(rul!("Left"), lit!("r")), lit!("r"))),
+            ]
+        );
+        let analysis = gram.analyze();
+        assert_eq!(analysis.is_rule_nullable("Nested"), Some(true));
+        assert!(analysis.get_rule_first_set("Nested").unwrap().contains(b'('));
+        assert_eq!(analysis.is_rule_nullable("Left"), Some(false));
+        assert!(analysis.get_rule_first_set("Left").unwrap().contains(b'r'));
+        assert!(!analysis.get_rule_first_set("Left").unwrap().contains(b'l'));
+    }
+
+    #[test]
+    fn unreachable_alternatives() {
+        let gram = Grammar::new(
+            None,
+            vec![
+                ("Op",      alt!(lit!("="), lit!("=="), lit!("!="))),
+                ("Keyword", alt!(rul!("In"), seq!(lit!("in"), reg!("t|to")), lit!("if"))),
+                ("In",      lbl!(lit!("in"), "in")),
+                ("Any",     alt!(reg!("[a-z]*"), lit!("x"))),
+                ("Fine",    alt!(lit!("=="), lit!("="), Rc::new(Lookahead::<()>::new(lit!("a"), lit!("b"), false)), lit!("ab"))),
+            ]
+        );
+        let warnings = gram.analyze().get_warnings().to_vec();
+        assert_eq!(warnings.len(), 3, "{:?}", warnings);
+        assert_eq!(warnings[0], AnalysisWarning::UnreachableAlternative { rule_name: "Op".into(), alternation: "\"=\" / \"==\" / \"!=\"".into(), index: 1, shadowed_by: 0 });
+        assert!(matches!(&warnings[1], AnalysisWarning::UnreachableAlternative { rule_name, index: 1, shadowed_by: 0, .. } if rule_name == "Keyword"));
+        assert!(matches!(&warnings[2], AnalysisWarning::UnreachableAlternative { rule_name, index: 1, shadowed_by: 0, .. } if rule_name == "Any"));
+        assert_eq!(
+            warnings[0].to_string(),
+            "Rule Op: alternative 1 of \"=\" / \"==\" / \"!=\" is unreachable, because alternative 0 always matches first"
+        );
+    }
+
+    #[test]
+    fn nullable_repetitions() {
+        let json: Rc<Grammar> = Rc::new(Grammar::new(None, vec![("Spaces", reg!("[ \\t]*"))]));
+        let gram = Grammar::new(
+            None,
+            vec![
+                ("Lines", qtt!(seq!(rul!("json::Spaces"), qtt!(lit!(";"), 0, Some(1))), 1, None)),
+                ("Words", qtt!(reg!("[a-z]+"), 0, None)),
+            ]
+        ).with_import("json", json);
+        let analysis = gram.analyze();
+        assert_eq!(
+            analysis.get_warnings(),
+            &[AnalysisWarning::NullableRepetition { rule_name: "Lines".into(), quantity: "(json::Spaces \";\"?)+".into() }]
+        );
+        assert_eq!(analysis.is_rule_nullable("json::Spaces"), Some(true));
+    }
+
+    #[test]
+    fn stateful_ops_and_parameters_are_assumed_to_consume_input() {
+        let gram: Grammar = Grammar::new(
+            None,
+            vec![
+                ("Block", seq!(cap!(reg!("[a-z]*"), "name"), qtt!(brf!("name"), 0, None), call!("List", lit!(",")))),
+            ]
+        ).with_parameterized_rule("List", vec!["Separator"], qtt!(rul!("Separator"), 0, None));
+        let analysis = gram.analyze();
+        assert!(analysis.get_warnings().is_empty(), "{:?}", analysis.get_warnings());
+        assert!(analysis.get_rule_first_set("List").unwrap().is_any());
+        assert_eq!(analysis.is_rule_nullable("List"), Some(true));
+    }
+}
@@ -0,0 +1,67 @@
+use regex_syntax::hir::{Class, Hir, HirKind, Literal, RepetitionKind, RepetitionRange};
+use regex_syntax::ParserBuilder;
+
+use super::analyzer::OpInfo;
+use super::FirstSet;
+
+/// What a `Regex` op matches, from its syntax tree; invalid patterns are unknown
+pub(crate) fn regex_info(pattern: &str, multi_line: bool, case_insensitive: bool, dot_matches_new_line: bool) -> OpInfo {
+    ParserBuilder::new()
+        .multi_line(multi_line)
+        .case_insensitive(case_insensitive)
+        .dot_matches_new_line(dot_matches_new_line)
+        .build()
+        .parse(pattern)
+        .map_or_else(|_| OpInfo::unknown(), |hir| hir_info(&hir))
+}
+
+fn hir_info(hir: &Hir) -> OpInfo {
+    match hir.kind() {
+        HirKind::Empty => OpInfo::literal(""),
+        HirKind::Literal(Literal::Unicode(each)) => OpInfo::literal(each.encode_utf8(&mut [0; 4])),
+        HirKind::Literal(Literal::Byte(byte)) if byte.is_ascii() => OpInfo::literal((*byte as char).encode_utf8(&mut [0; 4])),
+        HirKind::Literal(Literal::Byte(byte)) => {
+            let mut first = FirstSet::default();
+            first.insert(*byte);
+            OpInfo { first, ..OpInfo::default() }
+        },
+        HirKind::Class(Class::Unicode(class)) => {
+            let mut first = FirstSet::default();
+            for range in class.ranges() {
+                // The first byte of the UTF-8 encoding rises with the code point
+                for byte in range.start().encode_utf8(&mut [0; 4]).as_bytes()[0]..=range.end().encode_utf8(&mut [0; 4]).as_bytes()[0] {
+                    first.insert(byte);
+                }
+            }
+            OpInfo { first, ..OpInfo::default() }
+        },
+        HirKind::Class(Class::Bytes(class)) => {
+            let mut first = FirstSet::default();
+            for range in class.ranges() {
+                for byte in range.start()..=range.end() {
+                    first.insert(byte);
+                }
+            }
+            OpInfo { first, ..OpInfo::default() }
+        },
+        // Matches nothing, but only in some places
+        HirKind::Anchor(_) | HirKind::WordBoundary(_) => OpInfo { nullable: true, ..OpInfo::default() },
+        HirKind::Repetition(repetition) => {
+            let (minimum, exact) = match repetition.kind {
+                RepetitionKind::ZeroOrOne | RepetitionKind::ZeroOrMore => (0, false),
+                RepetitionKind::OneOrMore => (1, false),
+                RepetitionKind::Range(RepetitionRange::Exactly(count)) => (count as usize, true),
+                RepetitionKind::Range(RepetitionRange::AtLeast(minimum) | RepetitionRange::Bounded(minimum, _)) => (minimum as usize, false),
+            };
+            let child = hir_info(&repetition.hir);
+            let mut info = OpInfo::repeat(child, minimum);
+            if !exact {
+                info.fixed = None;
+            }
+            info
+        },
+        HirKind::Group(group) => hir_info(&group.hir),
+        HirKind::Concat(children) => OpInfo::sequence(children.iter().map(hir_info)),
+        HirKind::Alternation(children) => OpInfo::alternation(children.iter().map(hir_info)),
+    }
+}
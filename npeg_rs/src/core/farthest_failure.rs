@@ -0,0 +1,79 @@
+use std::rc::Rc;
+
+/// `FarthestFailure`
+///
+/// Where the parse got furthest before failing, and what was expected there; see `ParserContext::get_farthest_failure()`.
+/// When the parse fails, or stops before the end of the input, this is usually where the input is wrong.
+///
+/// Only ops which describe what they expect are recorded (see `Parser::describe_failure()`),
+/// so the failure of a rule is recorded as the failures of the terminals within it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FarthestFailure {
+    position: usize,
+    expected: Vec<Rc<str>>,
+}
+
+impl FarthestFailure {
+    pub fn get_position(&self) -> usize {
+        self.position
+    }
+    /// Descriptions of the ops which failed at the position, without repeats, in the order they failed
+    pub fn get_expected(&self) -> &[Rc<str>] {
+        &self.expected
+    }
+    /// Whether a failure at `position` would be kept
+    pub(crate) fn is_reached(farthest_failure: &Option<FarthestFailure>, position: usize) -> bool {
+        farthest_failure.as_ref().is_none_or(|farthest_failure| position >= farthest_failure.position)
+    }
+    /// Keeps the failures at the greatest position
+    pub(crate) fn record(farthest_failure: &mut Option<FarthestFailure>, position: usize, expected: Rc<str>) {
+        match farthest_failure {
+            Some(farthest_failure) if farthest_failure.position == position => {
+                if !farthest_failure.expected.contains(&expected) {
+                    farthest_failure.expected.push(expected);
+                }
+            },
+            Some(farthest_failure) if farthest_failure.position < position => {
+                farthest_failure.position = position;
+                farthest_failure.expected.clear();
+                farthest_failure.expected.push(expected);
+            },
+            Some(_) => {},
+            None => {
+                *farthest_failure = Some(FarthestFailure {
+                    position,
+                    expected: vec![expected],
+                });
+            },
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::*;
+
+    #[test]
+    fn farthest_failure_lists_the_terminals_expected() {
+        let gram = Rc::new(Grammar::new(
+            None,
+            vec![
+                ("List", seq!(lit!("["), rul!("Item"), qtt!(seq!(lit!(","), rul!("Item")), 0, None), lit!("]"))),
+                ("Item", alt!(reg!("[0-9]+"), lit!("true"))),
+            ]
+        ));
+        let mut context = Box::new(ParserContext::new("[1,2,x]"));
+        assert!(gram.clone().parse(&mut context, 0).is_none());
+        let farthest_failure = context.get_farthest_failure().unwrap();
+        assert_eq!(farthest_failure.get_position(), 5);
+        let expected = |farthest_failure: &FarthestFailure| farthest_failure.get_expected().iter().map(|expected| expected.to_string()).collect::<Vec<_>>();
+        assert_eq!(expected(farthest_failure), [r#"r"[0-9]+""#, r#""true""#]);
+
+        let mut context = Box::new(ParserContext::new("[1,2"));
+        assert!(gram.parse(&mut context, 0).is_none());
+        assert_eq!(expected(context.get_farthest_failure().unwrap()), [r#"",""#, r#""]""#]);
+    }
+}
@@ -0,0 +1,196 @@
+use std::fmt;
+use std::rc::Rc;
+
+use super::{MatchArena, MatchId, ParseError, Parser, ParserContext, ParserMatch, Symbol};
+
+/// `Match`
+///
+/// Like `ParserMatch`, but shares ownership of the `MatchArena` and borrows the text that was parsed,
+/// so `text()` needs no arguments and the match outlives the `ParserContext`.
+/// Text taken from a `Match` borrows from the input, not from the match, so it can be kept in an AST
+/// after the matches are dropped.
+///
+/// Made by `parse(...)`, `parse_bytes(...)`, `ParserContext::into_match(...)` or `ParseTree::into_match(...)`
+#[derive(Clone)]
+pub struct Match<'input> {
+    arena: Rc<MatchArena>,
+    id: MatchId,
+    input: &'input [u8],
+    /// `None` for byte input
+    full_text: Option<&'input str>,
+}
+
+impl<'input> Match<'input> {
+    /// Panics if the match is not in the arena
+    pub fn new(arena: Rc<MatchArena>, id: MatchId, full_text: &'input str) -> Self {
+        arena.get(id);
+        Match {
+            arena,
+            id,
+            input: full_text.as_bytes(),
+            full_text: Some(full_text),
+        }
+    }
+    /// Like `new`, for matches made by a `ParserContext::from_bytes()`
+    pub fn from_bytes(arena: Rc<MatchArena>, id: MatchId, input: &'input [u8]) -> Self {
+        arena.get(id);
+        Match {
+            arena,
+            id,
+            input,
+            full_text: None,
+        }
+    }
+    pub fn get_id(&self) -> MatchId {
+        self.id
+    }
+    pub fn get_start_position(&self) -> usize {
+        self.as_parser_match().get_start_position()
+    }
+    pub fn get_end_position(&self) -> usize {
+        self.as_parser_match().get_end_position()
+    }
+    pub fn get_label(&self) -> Option<&'static str> {
+        self.get_label_symbol().map(|label| label.as_str())
+    }
+    pub fn get_label_symbol(&self) -> Option<Symbol> {
+        self.as_parser_match().get_label_symbol()
+    }
+    pub fn get_children(&self) -> impl ExactSizeIterator<Item = Match<'input>> + DoubleEndedIterator + '_ {
+        self.as_parser_match()
+            .get_children()
+            .map(|child| self.with_id(child.get_id()))
+    }
+    pub fn get_child(&self, index: usize) -> Option<Match<'input>> {
+        self.get_children().nth(index)
+    }
+    pub fn len(&self) -> usize {
+        self.as_parser_match().len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.as_parser_match().is_empty()
+    }
+    /// The matched text, borrowed from the input.
+    /// Panics if the input is bytes and the matched bytes are not valid UTF-8.
+    pub fn text(&self) -> &'input str {
+        match self.full_text {
+            Some(full_text) => &full_text[self.get_start_position()..self.get_end_position()],
+            None => std::str::from_utf8(self.bytes()).expect("The matched bytes are not UTF-8"),
+        }
+    }
+    /// The matched bytes, borrowed from the input
+    pub fn bytes(&self) -> &'input [u8] {
+        &self.input[self.get_start_position()..self.get_end_position()]
+    }
+    /// The whole input; panics if the input is bytes
+    pub fn get_full_text(&self) -> &'input str {
+        self.full_text.expect("The input is bytes, not text; see Match::get_input()")
+    }
+    /// The whole input, as bytes
+    pub fn get_input(&self) -> &'input [u8] {
+        self.input
+    }
+    pub fn get_arena(&self) -> &Rc<MatchArena> {
+        &self.arena
+    }
+    pub fn as_parser_match(&self) -> ParserMatch<'_> {
+        self.arena.get(self.id)
+    }
+    fn with_id(&self, id: MatchId) -> Match<'input> {
+        Match {
+            arena: self.arena.clone(),
+            id,
+            input: self.input,
+            full_text: self.full_text,
+        }
+    }
+}
+
+impl fmt::Debug for Match<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug_struct = f.debug_struct("Match");
+        debug_struct
+            .field("start_position", &self.get_start_position())
+            .field("end_position", &self.get_end_position())
+            .field("label", &self.get_label());
+        match self.full_text {
+            Some(_) => debug_struct.field("text", &self.text()),
+            None => debug_struct.field("bytes", &self.bytes()),
+        };
+        debug_struct
+            .field("children", &self.get_children().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// Parses `full_text` from the start with a new `ParserContext`, which is dropped before returning.
+///
+/// Returns the `ParseError` if the parse was abandoned; see `Parser::try_parse`.
+/// Use a `ParserContext` directly to set limits or parse from another position.
+pub fn parse<'input, P: Parser + ?Sized>(parser: Rc<P>, full_text: &'input str) -> Result<Option<Match<'input>>, ParseError> {
+    let mut context = Box::new(ParserContext::new(full_text));
+    let result = parser.try_parse_match(&mut context, 0)?;
+    Ok(result.map(|result| context.into_match(result)))
+}
+
+/// Like `parse(...)`, for byte input; see `ParserContext::from_bytes()`
+pub fn parse_bytes<'input, P: Parser + ?Sized>(parser: Rc<P>, input: &'input [u8]) -> Result<Option<Match<'input>>, ParseError> {
+    let mut context = Box::new(ParserContext::from_bytes(input));
+    let result = parser.try_parse_match(&mut context, 0)?;
+    Ok(result.map(|result| context.into_match(result)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::*;
+
+    #[test]
+    fn text_borrows_from_input_after_matches_are_dropped() {
+        let gram = Rc::new(Grammar::new(
+            Some("Pair"),
+            vec![
+                ("Pair", seq!(rul!("Word"), lit!("="), rul!("Word"))),
+                ("Word", reg!("[a-z]+")),
+            ],
+        ));
+        let full_text = String::from("key=value");
+        let words: Vec<&str> = {
+            let result = parse(gram, &full_text).unwrap().unwrap();
+            assert_eq!(result.text(), "key=value");
+            assert_eq!(result.get_label(), Some("Pair"));
+            result
+                .get_children()
+                .filter(|child| child.get_label() == Some("Word"))
+                .map(|child| child.text())
+                .collect()
+        };
+        assert_eq!(words, vec!["key", "value"]);
+    }
+
+    #[test]
+    fn parse_returns_none_or_error() {
+        assert!(parse(lit!("a"), "b").unwrap().is_none());
+        assert_eq!(
+            parse(seq!(lit!("a"), lit!("b")), "ab").unwrap().unwrap().get_child(1).unwrap().text(),
+            "b"
+        );
+        let mut nested: Rc<dyn Parser> = lit!("a");
+        for _ in 0..DEFAULT_MAX_DEPTH + 1 {
+            nested = seq!(nested);
+        }
+        assert!(matches!(parse(nested, "a"), Err(ParseError::TooDeep { .. })));
+    }
+
+    #[test]
+    fn text_ops_match_valid_utf8_within_byte_input() {
+        let record = seq!(byt!(b"\xff"), lbl!(reg!("[a-zé]+"), "name"), byt!(b"\x00"), lbl!(reg!(".*"), "rest"));
+        let result = parse_bytes(record, b"\xffcaf\xc3\xa9\x00ok\xfe").unwrap().unwrap();
+        assert_eq!(result.get_child(1).unwrap().text(), "café");
+        // The regex stops at the invalid byte
+        assert_eq!(result.get_child(3).unwrap().text(), "ok");
+        assert_eq!(result.get_end_position(), 9);
+    }
+}
@@ -0,0 +1,72 @@
+use std::fmt;
+use std::rc::Rc;
+
+use super::Parser;
+use crate::ops::Grammar;
+
+/// `Instantiation`
+///
+/// The binding of a parameterized rule's parameter names to the argument expressions passed by a `RuleCall`.
+///
+/// Arguments are parsed in the `Instantiation` that was active where the `RuleCall` appeared (`enclosing`),
+/// which allows a parameter to be forwarded as the argument of another parameterized rule.
+/// Likewise, rule references in arguments are resolved in the grammars that were active at the `RuleCall` (`rule_sets`).
+///
+/// The `id` is unique within a `ParserContext` and is part of the memoization key;
+/// the same sub-tree parsed under two different instantiations must not share results.
+pub struct Instantiation<S = ()> {
+    id: usize,
+    bindings: Vec<(Rc<String>, Rc<dyn Parser<S>>)>,
+    enclosing: Option<Rc<Instantiation<S>>>,
+    rule_sets: Vec<Rc<Grammar<S>>>,
+}
+
+impl<S> Instantiation<S> {
+    pub fn new(
+        id: usize,
+        bindings: Vec<(Rc<String>, Rc<dyn Parser<S>>)>,
+        enclosing: Option<Rc<Instantiation<S>>>,
+        rule_sets: Vec<Rc<Grammar<S>>>,
+    ) -> Self {
+        Self {
+            id,
+            bindings,
+            enclosing,
+            rule_sets,
+        }
+    }
+    pub fn id(&self) -> usize {
+        self.id
+    }
+    pub fn get_enclosing(&self) -> Option<Rc<Instantiation<S>>> {
+        self.enclosing.clone()
+    }
+    pub fn get_rule_sets(&self) -> Vec<Rc<Grammar<S>>> {
+        self.rule_sets.clone()
+    }
+    /// The names of the parameters, in order.
+    pub fn get_parameters(&self) -> Vec<Rc<String>> {
+        self.bindings
+            .iter()
+            .map(|(parameter_name, _argument)| parameter_name.clone())
+            .collect()
+    }
+    /// Returns the argument bound to `parameter_name`, if any.
+    pub fn get_argument(&self, parameter_name: &str) -> Option<Rc<dyn Parser<S>>> {
+        self.bindings
+            .iter()
+            .find(|(each_parameter_name, _argument)| parameter_name == &each_parameter_name[..])
+            .map(|(_parameter_name, argument)| argument.clone())
+    }
+}
+
+impl<S> fmt::Debug for Instantiation<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Instantiation")
+            .field("id", &self.id)
+            .field("bindings", &self.bindings)
+            .field("enclosing", &self.enclosing)
+            .field("rule_sets", &self.rule_sets)
+            .finish()
+    }
+}
@@ -0,0 +1,177 @@
+use super::{ParseTree, ParserMatch, Symbol};
+
+/// Identifies a match within a `MatchArena`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MatchId(usize);
+
+/// The number of matches and children in a `MatchArena` at some point; see `MatchArena::checkpoint`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Checkpoint {
+    nodes: usize,
+    children: usize,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct MatchNode {
+    pub(crate) start_position: usize,
+    pub(crate) end_position: usize,
+    pub(crate) label: Option<Symbol>,
+    /// The range of `MatchArena::children` holding the children of this match
+    pub(crate) children_start: usize,
+    pub(crate) children_end: usize,
+}
+
+/// `MatchArena`
+///
+/// Holds every match made during a parse in two flat lists, one of matches and one of the children of all matches,
+/// so that making a match does not allocate. Matches are never changed once made; `relabel` adds a new match which
+/// shares the children of the original, since the original may be memoized or be the child of another match.
+///
+/// Matches made after a `checkpoint` may be removed by `rewind`, once nothing refers to them.
+#[derive(Debug, Clone, Default)]
+pub struct MatchArena {
+    nodes: Vec<MatchNode>,
+    children: Vec<MatchId>,
+    /// Children collected by `push_pending`, for matches which are not finished yet; see `finish_match`
+    pending: Vec<MatchId>,
+}
+
+impl MatchArena {
+    pub fn new() -> MatchArena {
+        MatchArena::default()
+    }
+    pub fn push(&mut self, start_position: usize, end_position: usize, label: Option<Symbol>, children: &[MatchId]) -> MatchId {
+        let children_start = self.children.len();
+        self.children.extend_from_slice(children);
+        self.push_node(start_position, end_position, label, children_start)
+    }
+    /// A copy of the match with `label` in place of its label
+    pub fn relabel(&mut self, match_id: MatchId, label: Symbol) -> MatchId {
+        let node = MatchNode { label: Some(label), ..self.nodes[match_id.0].clone() };
+        self.nodes.push(node);
+        MatchId(self.nodes.len() - 1)
+    }
+    /// Panics if the match is not in this arena
+    pub fn get(&self, match_id: MatchId) -> ParserMatch<'_> {
+        assert!(match_id.0 < self.nodes.len(), "Match {:?} is not in this arena", match_id);
+        ParserMatch::new(self, match_id)
+    }
+    /// The number of matches
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+    pub(crate) fn get_node(&self, match_id: MatchId) -> &MatchNode {
+        &self.nodes[match_id.0]
+    }
+    pub(crate) fn get_children(&self, node: &MatchNode) -> &[MatchId] {
+        &self.children[node.children_start..node.children_end]
+    }
+    /// A copy of the match and its descendants, in an arena of their own
+    pub fn extract(&self, root: MatchId) -> ParseTree {
+        let mut arena = MatchArena::new();
+        let copy = |arena: &mut MatchArena, match_id: MatchId| {
+            let node = self.get_node(match_id);
+            arena.nodes.push(MatchNode { children_start: 0, children_end: 0, ..node.clone() });
+            MatchId(arena.nodes.len() - 1)
+        };
+        let new_root = copy(&mut arena, root);
+        // Not recursive, since trees may be deeper than the stack allows
+        let mut unfinished = vec![(root, new_root)];
+        while let Some((original, new)) = unfinished.pop() {
+            let children_start = arena.children.len();
+            for &child in self.get_children(self.get_node(original)) {
+                let new_child = copy(&mut arena, child);
+                arena.children.push(new_child);
+                unfinished.push((child, new_child));
+            }
+            let node = &mut arena.nodes[new.0];
+            node.children_start = children_start;
+            node.children_end = arena.children.len();
+        }
+        ParseTree::new(arena, new_root)
+    }
+    /// Marks the matches made so far; see `rewind`
+    pub(crate) fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            nodes: self.nodes.len(),
+            children: self.children.len(),
+        }
+    }
+    /// Removes the matches made after both `checkpoint` and `keep`, e.g. when the branch which made them failed.
+    /// `keep` marks matches which are still referred to, such as those memoized.
+    pub(crate) fn rewind(&mut self, checkpoint: Checkpoint, keep: Checkpoint) {
+        self.nodes.truncate(checkpoint.nodes.max(keep.nodes));
+        self.children.truncate(checkpoint.children.max(keep.children));
+    }
+
+    /// Marks where the children of a new match start; children are then added with `push_pending`,
+    /// and the match is made with `finish_match`, or abandoned with `discard_pending`.
+    /// Children of matches made meanwhile, by child ops, are collected and removed above the mark.
+    pub(crate) fn start_pending(&self) -> usize {
+        self.pending.len()
+    }
+    pub(crate) fn push_pending(&mut self, match_id: MatchId) {
+        self.pending.push(match_id);
+    }
+    /// The number of children collected since `mark`
+    pub(crate) fn count_pending(&self, mark: usize) -> usize {
+        self.pending.len() - mark
+    }
+    pub(crate) fn discard_pending(&mut self, mark: usize) {
+        self.pending.truncate(mark);
+    }
+    pub(crate) fn finish_match(&mut self, start_position: usize, end_position: usize, mark: usize) -> MatchId {
+        let children_start = self.children.len();
+        self.children.extend(self.pending.drain(mark..));
+        self.push_node(start_position, end_position, None, children_start)
+    }
+
+    fn push_node(&mut self, start_position: usize, end_position: usize, label: Option<Symbol>, children_start: usize) -> MatchId {
+        self.nodes.push(MatchNode {
+            start_position,
+            end_position,
+            label,
+            children_start,
+            children_end: self.children.len(),
+        });
+        MatchId(self.nodes.len() - 1)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::*;
+
+    #[test]
+    fn parse_tree_holds_only_its_matches() {
+        let gram = Rc::new(Grammar::new(None, vec![
+            ("List", alt!(seq!(rul!("Item"), lit!(";")), seq!(rul!("Item"), lit!(",")))),
+            ("Item", reg!("[a-z]+")),
+        ]));
+        let mut context = Box::new(ParserContext::new("ab,"));
+        let tree = gram.clone().parse(&mut context, 0).unwrap();
+        assert_eq!(tree.len(), 3);
+        assert_eq!(tree.get_child(0).unwrap().get_child(0).unwrap().get_label(), Some("Item"));
+        // List, the second sequence, Item and ","; not the matches of the first sequence
+        assert_eq!(tree.get_arena().len(), 4);
+        assert!(context.get_arena().len() > tree.get_arena().len());
+        // The context keeps what it memoized, so it may parse again
+        assert_eq!(format!("{:?}", gram.parse(&mut context, 0).unwrap()), format!("{:?}", tree));
+    }
+
+    #[test]
+    fn matches_of_failed_branches_are_removed() {
+        // `b"\xc3"` matches half of "é", so its match is dropped rather than memoized
+        let mut context = Box::new(ParserContext::new("é;"));
+        let result = seq!(alt!(byt!(b"\xc3"), lit!("é")), lit!(";")).parse_match(&mut context, 0).unwrap();
+        assert_eq!(context.get_match(result).len(), 3);
+        // The matches of "é", the alternation, ";" and the sequence
+        assert_eq!(context.get_arena().len(), 4);
+    }
+}
@@ -1,9 +1,30 @@
 mod parser_match;
+mod input_match;
+mod match_arena;
+mod symbol;
 mod parser;
 mod parser_context;
 mod opaque_identifier;
+mod instantiation;
+mod parser_state;
+mod op_kind;
+mod parse_error;
+mod profile;
+mod farthest_failure;
+#[cfg(feature = "unicode-normalization")]
+mod normalization;
 
-pub use parser_context    ::  ParserContext;
-pub use parser_match      ::  ParserMatch;
+pub use parser_context    ::  {MemoryKey, ParserContext, DEFAULT_MAX_DEPTH};
+pub use parser_match      ::  {Children, ParseTree, ParserMatch};
+pub use input_match       ::  {parse, parse_bytes, Match};
+pub use match_arena       ::  {MatchArena, MatchId};
+pub(crate) use match_arena::Checkpoint;
+pub use symbol            ::  Symbol;
 pub use parser            ::  Parser;
-pub use opaque_identifier ::  OpaqueIdentifier;
\ No newline at end of file
+pub use opaque_identifier ::  OpaqueIdentifier;
+pub use instantiation     ::  Instantiation;
+pub use op_kind           ::  OpKind;
+pub use parse_error       ::  ParseError;
+pub use profile           ::  {Profile, ProfileEntry};
+pub use farthest_failure  ::  FarthestFailure;
+pub use parser_state      ::  {CaptureScope, CapturedText, IndentationLevel, ParserState};
\ No newline at end of file
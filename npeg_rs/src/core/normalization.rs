@@ -0,0 +1,72 @@
+//! Canonical equivalence, for `ParserContext::with_nfc()`.
+//!
+//! Text is compared after NFC normalization, but positions still refer to the input as given,
+//! so a match of decomposed input covers all of the decomposed characters.
+use unicode_normalization::{char::canonical_combining_class, UnicodeNormalization};
+
+/// Decomposed text is rarely more than a few times as long as its composed form;
+/// longer starts of the input are not compared with the expected text
+const MAX_EXPANSION: usize = 4;
+
+fn starts_with_combining_mark(text: &str) -> bool {
+    text.chars().next().is_some_and(|character| canonical_combining_class(character) != 0)
+}
+
+/// The length in bytes of the shortest start of `text` which is canonically equivalent to `expected`.
+/// The start never ends between a character and the combining marks which follow it.
+pub(crate) fn canonical_prefix_len(text: &str, expected: &str) -> Option<usize> {
+    if text.starts_with(expected) && !starts_with_combining_mark(&text[expected.len()..]) {
+        return Some(expected.len());
+    }
+    let expected: String = expected.nfc().collect();
+    let max_characters = (expected.chars().count() + 1) * MAX_EXPANSION;
+    text.char_indices()
+        .map(|(offset, _character)| offset)
+        .skip(1)
+        .chain(std::iter::once(text.len()))
+        .take(max_characters)
+        .filter(|end| !starts_with_combining_mark(&text[*end..]))
+        .find(|end| text[..*end].nfc().eq(expected.chars()))
+}
+
+/// The first character of `text` composed with the combining marks which follow it,
+/// and the length in bytes of the characters it was composed from.
+/// `None` if they do not compose to a single character.
+pub(crate) fn composed_char(text: &str) -> Option<(char, usize)> {
+    let mut characters = text.char_indices();
+    characters.next()?;
+    let length = characters
+        .find(|(_offset, character)| canonical_combining_class(*character) == 0)
+        .map_or(text.len(), |(offset, _character)| offset);
+    let mut composed = text[..length].nfc();
+    match (composed.next(), composed.next()) {
+        (Some(character), None) => Some((character, length)),
+        _ => None,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decomposed_text_matches_composed_text() {
+        let decomposed = "cafe\u{301} au lait";
+        assert_eq!(canonical_prefix_len(decomposed, "café"), Some(6));
+        assert_eq!(canonical_prefix_len("café au lait", "cafe\u{301}"), Some(5));
+        assert_eq!(canonical_prefix_len(decomposed, "caf"), Some(3));
+        // "e" is only part of "é"
+        assert_eq!(canonical_prefix_len(decomposed, "cafe"), None);
+        assert_eq!(canonical_prefix_len("", "a"), None);
+    }
+
+    #[test]
+    fn combining_marks_are_composed() {
+        assert_eq!(composed_char("e\u{301}x"), Some(('é', 3)));
+        assert_eq!(composed_char("éx"), Some(('é', 2)));
+        // There is no composed "q" with an acute accent
+        assert_eq!(composed_char("q\u{301}"), None);
+        assert_eq!(composed_char(""), None);
+    }
+}
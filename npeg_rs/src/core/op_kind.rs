@@ -0,0 +1,185 @@
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+
+use super::Parser;
+use crate::ops::{Endianness, Grammar, OperatorTable};
+
+/// `OpKind`
+///
+/// A borrowed view of what an op is and what it contains, returned by `Parser::kind()`.
+/// This is what lets tools which walk a grammar (printers, exporters, analyses)
+/// look inside an `Rc<dyn Parser>` without downcasting.
+///
+/// Ops defined outside this crate are `OpKind::Other` unless they override `Parser::kind()`.
+#[derive(Debug)]
+pub enum OpKind<'a, S = ()> {
+    /// The update of an `Action` is a closure, which tools can not see into
+    Action {
+        child: &'a Rc<dyn Parser<S>>,
+        /// The name of the type of user state it updates
+        user_state_type: &'static str,
+    },
+    Alternation(&'a [Rc<dyn Parser<S>>]),
+    AnyGrapheme,
+    BackReference(&'a str),
+    /// A `ByteClass`, with its ranges as given
+    ByteClass(&'a [RangeInclusive<u8>]),
+    /// A `Literal` made from bytes which are not UTF-8
+    ByteLiteral(&'a [u8]),
+    Capture {
+        child: &'a Rc<dyn Parser<S>>,
+        name: &'a str,
+    },
+    Counted {
+        child: &'a Rc<dyn Parser<S>>,
+        /// The capture which gives the count
+        name: &'a str,
+        /// `None` if the count is decimal text
+        endianness: Option<Endianness>,
+    },
+    Dedent,
+    Grammar(&'a Grammar<S>),
+    Indent,
+    Integer {
+        width: usize,
+        endianness: Endianness,
+        minimum: u64,
+        maximum: u64,
+    },
+    Label {
+        child: &'a Rc<dyn Parser<S>>,
+        label: &'a str,
+    },
+    Literal(&'a str),
+    Lookahead {
+        child: &'a Rc<dyn Parser<S>>,
+        scout: &'a Rc<dyn Parser<S>>,
+        accept_match: bool,
+    },
+    OperatorTable(&'a OperatorTable<S>),
+    /// The check of a `Predicate` is a closure, which tools can not see into
+    Predicate {
+        child: &'a Rc<dyn Parser<S>>,
+        description: &'a str,
+    },
+    Quantity {
+        child: &'a Rc<dyn Parser<S>>,
+        minimum_occurrences: usize,
+        maximum_occurrences: Option<usize>,
+    },
+    /// `pattern` is as it was given to `Regex::new`, without the `^` anchor that is added to it
+    Regex {
+        pattern: &'a str,
+        multi_line: bool,
+        case_insensitive: bool,
+        dot_matches_new_line: bool,
+    },
+    RuleCall {
+        rule_name: &'a str,
+        arguments: &'a [Rc<dyn Parser<S>>],
+    },
+    RuleReference(&'a str),
+    Samedent,
+    SepBy {
+        element: &'a Rc<dyn Parser<S>>,
+        separator: &'a Rc<dyn Parser<S>>,
+        minimum_occurrences: usize,
+        maximum_occurrences: Option<usize>,
+        trailing_separator: bool,
+    },
+    Sequence(&'a [Rc<dyn Parser<S>>]),
+    Take {
+        name: &'a str,
+        endianness: Endianness,
+    },
+    Until {
+        child: &'a Rc<dyn Parser<S>>,
+        terminator: &'a Rc<dyn Parser<S>>,
+    },
+    Other,
+}
+
+// Not derived, since that would require `S: Clone`
+impl<S> Clone for OpKind<'_, S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<S> Copy for OpKind<'_, S> {}
+
+impl<'a, S> OpKind<'a, S> {
+    /// The name of the variant, e.g. `"Alternation"`
+    pub fn name(&self) -> &'static str {
+        match self {
+            OpKind::Action { .. } => "Action",
+            OpKind::Alternation(_) => "Alternation",
+            OpKind::AnyGrapheme => "AnyGrapheme",
+            OpKind::BackReference(_) => "BackReference",
+            OpKind::ByteClass(_) => "ByteClass",
+            OpKind::ByteLiteral(_) => "ByteLiteral",
+            OpKind::Capture { .. } => "Capture",
+            OpKind::Counted { .. } => "Counted",
+            OpKind::Dedent => "Dedent",
+            OpKind::Grammar(_) => "Grammar",
+            OpKind::Indent => "Indent",
+            OpKind::Integer { .. } => "Integer",
+            OpKind::Label { .. } => "Label",
+            OpKind::Literal(_) => "Literal",
+            OpKind::Lookahead { .. } => "Lookahead",
+            OpKind::OperatorTable(_) => "OperatorTable",
+            OpKind::Predicate { .. } => "Predicate",
+            OpKind::Quantity { .. } => "Quantity",
+            OpKind::Regex { .. } => "Regex",
+            OpKind::RuleCall { .. } => "RuleCall",
+            OpKind::RuleReference(_) => "RuleReference",
+            OpKind::Samedent => "Samedent",
+            OpKind::SepBy { .. } => "SepBy",
+            OpKind::Sequence(_) => "Sequence",
+            OpKind::Take { .. } => "Take",
+            OpKind::Until { .. } => "Until",
+            OpKind::Other => "Other",
+        }
+    }
+    /// The ops directly contained by this op, in order.
+    /// The rules of a nested `Grammar` are not included.
+    pub fn children(&self) -> Vec<&'a Rc<dyn Parser<S>>> {
+        match *self {
+            OpKind::Alternation(children) | OpKind::Sequence(children) => children.iter().collect(),
+            OpKind::RuleCall { arguments, .. } => arguments.iter().collect(),
+            OpKind::Action { child, .. }
+            | OpKind::Capture { child, .. }
+            | OpKind::Counted { child, .. }
+            | OpKind::Label { child, .. }
+            | OpKind::Predicate { child, .. }
+            | OpKind::Quantity { child, .. } => vec![child],
+            OpKind::Lookahead { child, scout, .. } => vec![child, scout],
+            OpKind::SepBy { element, separator, .. } => vec![element, separator],
+            OpKind::Until { child, terminator } => vec![child, terminator],
+            // The atom, then the prefix, infix and postfix operators
+            OpKind::OperatorTable(table) => std::iter::once(table.get_atom())
+                .chain(
+                    table.get_prefix_operators()
+                        .iter()
+                        .chain(table.get_infix_operators())
+                        .chain(table.get_postfix_operators())
+                        .map(|operator| operator.get_parser()),
+                )
+                .collect(),
+            OpKind::AnyGrapheme
+            | OpKind::BackReference(_)
+            | OpKind::ByteClass(_)
+            | OpKind::ByteLiteral(_)
+            | OpKind::Dedent
+            | OpKind::Grammar(_)
+            | OpKind::Indent
+            | OpKind::Integer { .. }
+            | OpKind::Literal(_)
+            | OpKind::Regex { .. }
+            | OpKind::RuleReference(_)
+            | OpKind::Samedent
+            | OpKind::Take { .. }
+            | OpKind::Other => vec![],
+        }
+    }
+}
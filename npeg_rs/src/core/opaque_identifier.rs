@@ -67,5 +67,12 @@ mod tests{
                 self.identifier.hash(state);
             }
         }
+        let key = KeyStruct{
+            identifier:OpaqueIdentifier::new(),
+            _some_unhashable_data:UnHashableData{}
+        };
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        key.hash(&mut hasher);
+        let _ = hasher.finish();
     }
 }
\ No newline at end of file
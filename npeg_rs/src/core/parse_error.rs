@@ -0,0 +1,35 @@
+use std::error::Error;
+use std::fmt;
+
+/// `ParseError`
+///
+/// Why a parse was abandoned, as opposed to the text simply not matching.
+/// The first error is stored in the `ParserContext`; every op then fails until the error is taken
+/// with `ParserContext::take_error()`, or is returned by `Parser::try_parse()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// Ops were nested deeper than the `ParserContext`'s maximum depth; see `ParserContext::with_max_depth()`
+    TooDeep { position: usize },
+    /// More ops were parsed than allowed; see `ParserContext::with_max_steps()`
+    TooManySteps { position: usize },
+    /// More results were memoized than allowed; see `ParserContext::with_max_memo_entries()`
+    TooManyMemoEntries { position: usize },
+    /// Memoized results took more memory than allowed; see `ParserContext::with_max_memo_bytes()`
+    TooManyMemoBytes { position: usize },
+    /// The parse ran past its deadline; see `ParserContext::with_deadline()`
+    DeadlineExceeded { position: usize },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::TooDeep { position } => write!(f, "Input is nested too deeply at position {}", position),
+            ParseError::TooManySteps { position } => write!(f, "Parse took too many steps at position {}", position),
+            ParseError::TooManyMemoEntries { position } => write!(f, "Parse memoized too many results at position {}", position),
+            ParseError::TooManyMemoBytes { position } => write!(f, "Parse used too much memory at position {}", position),
+            ParseError::DeadlineExceeded { position } => write!(f, "Parse ran past its deadline at position {}", position),
+        }
+    }
+}
+
+impl Error for ParseError {}
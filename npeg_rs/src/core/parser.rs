@@ -0,0 +1,128 @@
+use std::rc::Rc;
+use std::fmt::Debug;
+
+use crate::peg_text::write_expression;
+use super::{
+    op_kind::OpKind,
+    parse_error::ParseError,
+    match_arena::MatchId,
+    parser_context::ParserContext,
+    parser_match::ParseTree,
+};
+
+/// An op, which parses with a `ParserContext` whose user state is an `S`; see `ParserContext::set_user_state()`.
+/// Most ops can parse with any user state, while a `Predicate` or `Action` is made for one type.
+pub trait Parser<S = ()>: Debug {
+    /// Parses at `start_position`, returning the match as a `ParseTree` which owns it and its descendants.
+    /// The context keeps what it memoized, so it may be used to parse again.
+    fn parse(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<ParseTree>{
+        self.parse_match(context, start_position).map(|root| context.get_arena().extract(root))
+    }
+    /// Like `parse`, but returns the match as an id within the context's `MatchArena`; ops parse their children with this
+    fn parse_match(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<MatchId>{
+        // After an error everything fails, so that the parse unwinds quickly
+        if context.get_error().is_some() {
+            return None;
+        }
+        // Text is never split within a character
+        if !context.is_char_boundary(start_position) {
+            return None;
+        }
+        // Try to lookup previously computed value
+        let memory_key = context.get_memory_key(start_position, self.get_id());
+        match context.get_memory(&memory_key){
+            Some(result)=> {
+                // Counted, so that an op which keeps finding the same result in memory is still limited
+                if !context.step(start_position) {
+                    return None;
+                }
+                context.profile_memo_hit(self.get_id(), self.kind(), result.is_some());
+                result
+            },
+            None=>{
+                // if the cache failed, try to do the parse
+                if !context.enter(start_position) {
+                    return None;
+                }
+                let state = context.get_state();
+                let checkpoint = context.checkpoint();
+                let started = context.profile_enter();
+                let result = grow_stack(|| self.clone().parse_internal(context, start_position))
+                    .filter(|result| context.is_char_boundary(context.get_match_end_position(*result)));
+                context.leave();
+                context.profile_leave(started, self.get_id(), self.kind(), result.is_some());
+                if context.get_error().is_some() {
+                    // The result was cut short by the error, so it must not be memoized
+                    return None;
+                }
+                if result.is_none() {
+                    // undo any changes made by the parts that did match, e.g. the first items of a `Sequence`
+                    context.set_state(state);
+                    context.rewind(checkpoint);
+                    context.record_failure(start_position, &*self);
+                }
+                // cache the result
+                context.set_memory(memory_key, result);
+                if context.get_error().is_some() {
+                    // The memory limit was reached
+                    return None;
+                }
+                // finally, return the result
+                result
+            }
+        }
+    }
+    /// Like `parse`, but returns the `ParseError` if the parse was abandoned rather than failing to match.
+    /// The error is taken from the context, so the context may be used again afterwards.
+    fn try_parse(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Result<Option<ParseTree>, ParseError>{
+        let result = self.try_parse_match(context, start_position)?;
+        Ok(result.map(|root| context.get_arena().extract(root)))
+    }
+    /// Like `try_parse`, but returns the match as an id within the context's `MatchArena`
+    fn try_parse_match(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Result<Option<MatchId>, ParseError>{
+        let result = self.parse_match(context, start_position);
+        match context.take_error() {
+            Some(error) => Err(error),
+            None => Ok(result),
+        }
+    }
+    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<MatchId>;
+    fn get_id(&self)->usize;
+    /// Describes this op to tools which walk a grammar
+    fn kind(&self)->OpKind<'_, S>{
+        OpKind::Other
+    }
+    /// What this op expected, for `ParserContext::get_farthest_failure()`, or `None` if its failure is only
+    /// the failure of its children. By default, terminals such as `Literal` and `Regex` are written as PEG expressions.
+    fn describe_failure(&self)->Option<String>{
+        match self.kind() {
+            OpKind::BackReference(_)
+            | OpKind::ByteLiteral(_)
+            | OpKind::Dedent
+            | OpKind::Indent
+            | OpKind::Literal(_)
+            | OpKind::Regex { .. }
+            | OpKind::Samedent => Some(self.to_peg_string()),
+            _ => None,
+        }
+    }
+    /// Writes this op as a PEG expression, which `Grammar::from_peg_string` can read back.
+    /// Ops that do not override `kind()` are written as `@unknown`.
+    fn to_peg_string(&self)->String{
+        let mut text = String::new();
+        write_expression(&mut text, self.kind()).expect("Writing to a String does not fail");
+        text
+    }
+}
+
+/// With the `stacker` feature, runs `parse_internal` on a new stack segment when the current one is nearly used up,
+/// so that trusted input can be nested more deeply than the thread's stack would allow.
+#[cfg(feature = "stacker")]
+fn grow_stack<R>(parse_internal: impl FnOnce() -> R) -> R {
+    stacker::maybe_grow(64 * 1024, 1024 * 1024, parse_internal)
+}
+
+#[cfg(not(feature = "stacker"))]
+fn grow_stack<R>(parse_internal: impl FnOnce() -> R) -> R {
+    parse_internal()
+}
@@ -1,60 +1,608 @@
 use regex::{Regex, RegexBuilder};
 
-use std::{collections::BTreeMap, rc::Rc};
+use std::{collections::{BTreeMap, HashMap}, mem::{size_of, size_of_val}, rc::Rc, time::Instant};
 
 use super::{
+    CaptureScope,
+    CapturedText,
+    Checkpoint,
+    FarthestFailure,
+    Instantiation,
+    Match,
+    MatchArena,
+    MatchId,
+    OpKind,
+    ParseError,
     ParserMatch,
+    ParserState,
     Parser,
+    Profile,
+    Symbol,
 };
-use crate::ops::Grammar;
+use super::match_arena::MatchNode;
+use crate::analysis::{Analyzer, DispatchTable};
+use crate::ops::{Grammar, ResolvedRule};
 
+/// An argument, with the instantiation it is bound in
+type BoundArgument<S> = (Rc<dyn Parser<S>>, Rc<Instantiation<S>>);
 
-pub struct ParserContext<'ft> {
-    full_text: &'ft str,
-    memory: BTreeMap<(usize, usize), Option<Rc<ParserMatch>>>,
-    current_grammar: Vec<Rc<Grammar>>,
+/// Identifies a memoized result. Besides the position and op, the result depends on
+/// the grammars and instantiation in effect (`scope_id`) and on the `ParserState` (`captures_id`, `indentation_id`, `user_state_id`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct MemoryKey {
+    start_position: usize,
+    parser_operator_id: usize,
+    scope_id: usize,
+    captures_id: usize,
+    indentation_id: usize,
+    user_state_id: usize,
 }
 
+pub struct ParserContext<'ft, S = ()> {
+    /// The input as bytes; positions are byte offsets into it
+    input: &'ft [u8],
+    /// The input as text, unless the context was made by `from_bytes()`
+    full_text: Option<&'ft str>,
+    /// Every match made so far; ops return `MatchId`s into it
+    arena: MatchArena,
+    /// The result of each op, along with the `ParserState` it left behind
+    memory: BTreeMap<MemoryKey, (Option<MatchId>, ParserState<S>)>,
+    current_grammar: Vec<Rc<Grammar<S>>>,
+    current_instantiation: Option<Rc<Instantiation<S>>>,
+    instantiations: BTreeMap<(usize, usize), Rc<Instantiation<S>>>,
+    /// Interned `(grammar ids, instantiation id)` pairs; see `get_scope_id()`
+    scopes: BTreeMap<(Vec<usize>, usize), usize>,
+    current_scope: usize,
+    state: ParserState<S>,
+    next_capture_id: usize,
+    next_indentation_id: usize,
+    next_user_state_id: usize,
+    /// The number of ops currently being parsed, one inside the other
+    depth: usize,
+    max_depth: Option<usize>,
+    /// The number of ops parsed or found in memory so far
+    steps: usize,
+    max_steps: Option<usize>,
+    /// An estimate of the size of `memory`; see `get_memory_bytes()`
+    memory_bytes: usize,
+    /// Covers every match that `memory` refers to, so that `rewind()` keeps them
+    memory_checkpoint: Checkpoint,
+    max_memory_entries: Option<usize>,
+    max_memory_bytes: Option<usize>,
+    deadline: Option<Instant>,
+    error: Option<ParseError>,
+    profile: Option<Profile>,
+    /// See `with_nfc()`
+    nfc: bool,
+    farthest_failure: Option<FarthestFailure>,
+    /// By op id; describing an op is slow, and the same ops fail over and over
+    failure_descriptions: HashMap<usize, Option<Rc<str>>>,
+    /// By `(alternation id, scope id)`; see `get_dispatch_table()`
+    dispatch_tables: BTreeMap<(usize, usize), Rc<DispatchTable>>,
+    /// Shared by all dispatch tables, so that each rule is analyzed once
+    analyzer: Analyzer,
+}
+
+/// The default for `ParserContext::with_max_depth()`; comfortably within the 2 MiB stack of a spawned thread
+pub const DEFAULT_MAX_DEPTH: usize = 1_000;
+
+/// Reading the clock for every op would be slow, so the deadline is only checked once per this many steps
+const DEADLINE_CHECK_INTERVAL: usize = 256;
+
 impl<'ft> ParserContext<'ft> {
-    pub fn new(full_text: &'ft str) -> ParserContext {
+    /// Parses text, with no user state; see `with_user_state()`
+    pub fn new(full_text: &'ft str) -> ParserContext<'ft> {
+        ParserContext::with_input(full_text.as_bytes(), Some(full_text), ())
+    }
+    /// Parses binary input, such as a file header or network packet.
+    ///
+    /// Ops which match bytes (`Literal`, `ByteClass`, `Integer`, `Take`, ...) work as they do on text.
+    /// Ops which match text (`Regex`, `CharClass`, ...) only see the valid UTF-8 which follows their position;
+    /// see `get_text_at()`.
+    pub fn from_bytes(input: &'ft [u8]) -> ParserContext<'ft> {
+        ParserContext::with_input(input, None, ())
+    }
+}
+
+impl<'ft, S> ParserContext<'ft, S> {
+    fn with_input(input: &'ft [u8], full_text: Option<&'ft str>, user_state: S) -> ParserContext<'ft, S> {
         ParserContext {
+            input,
             full_text,
+            arena: MatchArena::new(),
             memory: BTreeMap::new(),
             current_grammar: vec![],
+            current_instantiation: None,
+            instantiations: BTreeMap::new(),
+            scopes: BTreeMap::from([((vec![], 0), 0)]),
+            current_scope: 0,
+            state: ParserState::new(user_state),
+            next_capture_id: 1,
+            next_indentation_id: 1,
+            next_user_state_id: 1,
+            depth: 0,
+            max_depth: Some(DEFAULT_MAX_DEPTH),
+            steps: 0,
+            max_steps: None,
+            memory_bytes: 0,
+            memory_checkpoint: Checkpoint::default(),
+            max_memory_entries: None,
+            max_memory_bytes: None,
+            deadline: None,
+            error: None,
+            profile: None,
+            nfc: false,
+            farthest_failure: None,
+            failure_descriptions: HashMap::new(),
+            dispatch_tables: BTreeMap::new(),
+            analyzer: Analyzer::default(),
+        }
+    }
+    /// Limits how deeply ops may be nested while parsing; deeper input fails with `ParseError::TooDeep`.
+    /// Every op counts, so a rule which refers to itself uses several levels for each level of nesting in the input.
+    ///
+    /// `None` removes the limit, which is only safe for trusted input;
+    /// consider enabling the `stacker` feature so that the stack grows as needed.
+    pub fn with_max_depth(mut self, max_depth: Option<usize>) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+    /// Limits the number of ops parsed; more fails with `ParseError::TooManySteps`.
+    /// Ops whose result is found in memory count too, so that an op which keeps looking up the same result is still limited.
+    pub fn with_max_steps(mut self, max_steps: Option<usize>) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+    /// Limits the number of memoized results; more fails with `ParseError::TooManyMemoEntries`
+    pub fn with_max_memo_entries(mut self, max_memory_entries: Option<usize>) -> Self {
+        self.max_memory_entries = max_memory_entries;
+        self
+    }
+    /// Limits the estimated size of the memoized results; more fails with `ParseError::TooManyMemoBytes`
+    pub fn with_max_memo_bytes(mut self, max_memory_bytes: Option<usize>) -> Self {
+        self.max_memory_bytes = max_memory_bytes;
+        self
+    }
+    /// Parsing past the deadline fails with `ParseError::DeadlineExceeded`.
+    /// The clock is read periodically rather than for every op, so the deadline may be overrun slightly.
+    pub fn with_deadline(mut self, deadline: Option<Instant>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+    /// Counts invocations, memo hits and misses, backtracks and time for each op and rule; see `get_profile()`.
+    /// Profiling reads the clock twice for each op parsed, so it slows the parse down.
+    pub fn with_profiling(mut self, enabled: bool) -> Self {
+        self.profile = enabled.then(Profile::default);
+        self
+    }
+    /// Text is matched up to canonical equivalence, so that decomposed input (NFD), such as text typed by users,
+    /// matches a grammar written in composed form (NFC), and the reverse.
+    /// `Literal`, `LiteralSet`, `BackReference` and `CharClass` compare the NFC forms, and never match part of
+    /// a character which is followed by combining marks. Matches still span the input as given.
+    ///
+    /// `Regex` matches the input as given. Comparing normalized text is slower, and `Alternation`s try every child.
+    #[cfg(feature = "unicode-normalization")]
+    pub fn with_nfc(mut self, enabled: bool) -> Self {
+        self.nfc = enabled && self.full_text.is_some();
+        self
+    }
+    pub fn is_nfc(&self) -> bool {
+        self.nfc
+    }
+    /// The statistics collected so far, if profiling is enabled
+    pub fn get_profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+    /// Called by `Parser::parse` when a result is found in memory
+    pub(crate) fn profile_memo_hit(&mut self, parser_operator_id: usize, kind: OpKind<S>, matched: bool) {
+        if let Some(profile) = self.profile.as_mut() {
+            profile.record_memo_hit(parser_operator_id, kind, matched);
+        }
+    }
+    /// Called by `Parser::parse` before an op is parsed; returns the time at which it started, if profiling
+    pub(crate) fn profile_enter(&mut self) -> Option<Instant> {
+        let profile = self.profile.as_mut()?;
+        profile.enter();
+        Some(Instant::now())
+    }
+    /// Called by `Parser::parse` after an op is parsed, with the time returned by `profile_enter()`
+    pub(crate) fn profile_leave(&mut self, started: Option<Instant>, parser_operator_id: usize, kind: OpKind<S>, matched: bool) {
+        if let (Some(profile), Some(started)) = (self.profile.as_mut(), started) {
+            profile.leave(parser_operator_id, kind, matched, started.elapsed());
+        }
+    }
+    pub fn get_depth(&self) -> usize {
+        self.depth
+    }
+    pub fn get_steps(&self) -> usize {
+        self.steps
+    }
+    pub fn get_memory_entries(&self) -> usize {
+        self.memory.len()
+    }
+    /// An estimate of the memory used by memoized results, counting each match as if it were not shared
+    pub fn get_memory_bytes(&self) -> usize {
+        self.memory_bytes
+    }
+    /// Called by `Parser::parse` before an op is parsed.
+    /// Returns `false`, and records the error, if the op is too deep or the step limit or deadline has been reached.
+    pub fn enter(&mut self, start_position: usize) -> bool {
+        if self.max_depth.is_some_and(|max_depth| self.depth >= max_depth) {
+            self.set_error(ParseError::TooDeep { position: start_position });
+            return false;
+        }
+        if !self.step(start_position) {
+            return false;
+        }
+        self.depth += 1;
+        true
+    }
+    /// Counts a step; called by `enter()`, and by `Parser::parse` when a result is found in memory.
+    /// Returns `false`, and records the error, if the step limit or deadline has been reached.
+    pub fn step(&mut self, start_position: usize) -> bool {
+        let error = if self.max_steps.is_some_and(|max_steps| self.steps >= max_steps) {
+            Some(ParseError::TooManySteps { position: start_position })
+        } else if self.steps.is_multiple_of(DEADLINE_CHECK_INTERVAL) && self.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            Some(ParseError::DeadlineExceeded { position: start_position })
+        } else {
+            None
+        };
+        if let Some(error) = error {
+            self.set_error(error);
+            return false;
+        }
+        self.steps += 1;
+        true
+    }
+    /// Called by `Parser::parse` after an op for which `enter` returned `true` has been parsed
+    pub fn leave(&mut self) {
+        self.depth -= 1;
+    }
+    /// The error which stopped the parse, if any
+    pub fn get_error(&self) -> Option<&ParseError> {
+        self.error.as_ref()
+    }
+    /// Keeps the first error only; the rest are consequences of it
+    pub fn set_error(&mut self, error: ParseError) {
+        self.error.get_or_insert(error);
+    }
+    /// Where the parse got furthest before failing, if anything failed; kept across parses with this context.
+    /// A successful parse which did not reach the end of the input stopped at or before this position.
+    pub fn get_farthest_failure(&self) -> Option<&FarthestFailure> {
+        self.farthest_failure.as_ref()
+    }
+    /// Called by `Parser::parse` when an op fails at `start_position`; the op is only described if needed
+    pub fn record_failure(&mut self, start_position: usize, parser_operator: &(impl Parser<S> + ?Sized)) {
+        if !FarthestFailure::is_reached(&self.farthest_failure, start_position) {
+            return;
+        }
+        let description = self.failure_descriptions
+            .entry(parser_operator.get_id())
+            .or_insert_with(|| parser_operator.describe_failure().map(Rc::from));
+        if let Some(description) = description {
+            FarthestFailure::record(&mut self.farthest_failure, start_position, description.clone());
+        }
+    }
+    /// Removes the error so that the context can be used to parse again
+    pub fn take_error(&mut self) -> Option<ParseError> {
+        self.error.take()
+    }
+    /// Panics if the context was made by `from_bytes()`; see `get_input()` and `get_text_at()`
+    pub fn get_full_text(&self) -> &'ft str {
+        self.full_text.expect("The input is bytes, not text; see ParserContext::from_bytes()")
+    }
+    pub fn get_input(&self) -> &'ft [u8] {
+        self.input
+    }
+    /// Whether `position` is at the start or end of a character, which `Parser::parse` requires of every match.
+    /// Always true for byte input.
+    pub fn is_char_boundary(&self, position: usize) -> bool {
+        self.full_text.is_none_or(|full_text| full_text.is_char_boundary(position))
+    }
+    /// The text from `start_position` to the end of the input.
+    /// For byte input, only the valid UTF-8 from `start_position` to the first invalid byte.
+    pub fn get_text_at(&self, start_position: usize) -> &'ft str {
+        match self.full_text {
+            Some(full_text) => &full_text[start_position..],
+            None => {
+                let bytes = &self.input[start_position..];
+                match std::str::from_utf8(bytes) {
+                    Ok(text) => text,
+                    Err(error) => std::str::from_utf8(&bytes[..error.valid_up_to()]).expect("Checked by from_utf8"),
+                }
+            },
+        }
+    }
+    /// The end of `expected` if the text at `start_position` is `expected`, or is canonically equivalent to it;
+    /// see `with_nfc()`
+    pub fn match_text(&self, start_position: usize, expected: &str) -> Option<usize> {
+        #[cfg(feature = "unicode-normalization")]
+        if self.nfc {
+            return super::normalization::canonical_prefix_len(self.get_text_at(start_position), expected)
+                .map(|length| start_position + length);
         }
+        self.input[start_position..]
+            .starts_with(expected.as_bytes())
+            .then(|| start_position + expected.len())
+    }
+    /// The character at `start_position` and its length in bytes.
+    /// With `with_nfc()`, the character composed with the combining marks which follow it, and their length.
+    pub fn get_char_at(&self, start_position: usize) -> Option<(char, usize)> {
+        #[cfg(feature = "unicode-normalization")]
+        if self.nfc {
+            return super::normalization::composed_char(self.get_text_at(start_position));
+        }
+        let character = self.get_text_at(start_position).chars().next()?;
+        Some((character, character.len_utf8()))
+    }
+    /// Keeps the matches made by this context, so that the match outlives it; see `Match`
+    pub fn into_match(self, match_id: MatchId) -> Match<'ft> {
+        match self.full_text {
+            Some(full_text) => Match::new(Rc::new(self.arena), match_id, full_text),
+            None => Match::from_bytes(Rc::new(self.arena), match_id, self.input),
+        }
+    }
+    /// Panics if the match was not made by this context
+    pub fn get_match(&self, match_id: MatchId) -> ParserMatch<'_> {
+        self.arena.get(match_id)
+    }
+    pub fn get_arena(&self) -> &MatchArena {
+        &self.arena
+    }
+    pub fn new_match(&mut self, start_position: usize, end_position: usize, label: Option<Symbol>, children: &[MatchId]) -> MatchId {
+        self.arena.push(start_position, end_position, label, children)
+    }
+    /// See `MatchArena::relabel`
+    pub fn relabel_match(&mut self, match_id: MatchId, label: Symbol) -> MatchId {
+        self.arena.relabel(match_id, label)
+    }
+    pub fn get_match_end_position(&self, match_id: MatchId) -> usize {
+        self.arena.get_node(match_id).end_position
     }
-    pub fn get_full_text(&self) -> &str {
-        self.full_text
+    /// Marks the matches made so far, before trying a branch which may fail; see `rewind()`
+    pub(crate) fn checkpoint(&self) -> Checkpoint {
+        self.arena.checkpoint()
     }
-    pub fn get_memory(&self, start_position: usize, parser_operator_id: usize) -> Option<Option<Rc<ParserMatch>>> {
-        self.memory.get(&(start_position, parser_operator_id)).map(|item|item.clone())
+    /// Removes the matches made since `checkpoint` by a branch which failed, except those still in memory
+    pub(crate) fn rewind(&mut self, checkpoint: Checkpoint) {
+        self.arena.rewind(checkpoint, self.memory_checkpoint);
     }
-    pub fn set_memory(& mut self, start_position: usize, parser_operator_id: usize, parser_match: Option<Rc<ParserMatch>>) -> Option<Rc<ParserMatch>> {
+    /// Collects the children of a match without allocating; see `MatchArena::start_pending`
+    pub(crate) fn get_arena_mut(&mut self) -> &mut MatchArena {
+        &mut self.arena
+    }
+    /// Must be called before the op is parsed, since parsing changes the `ParserState`
+    pub fn get_memory_key(&self, start_position: usize, parser_operator_id: usize) -> MemoryKey {
+        MemoryKey {
+            start_position,
+            parser_operator_id,
+            scope_id: self.current_scope,
+            captures_id: self.state.get_captures_id(),
+            indentation_id: self.state.get_indentation_id(),
+            user_state_id: self.state.get_user_state_id(),
+        }
+    }
+    /// Returns the memoized result, if any, and restores the `ParserState` that the op left behind
+    pub fn get_memory(&mut self, memory_key: &MemoryKey) -> Option<Option<MatchId>> {
+        self.memory.get(memory_key).cloned().map(|(parser_match, state)| {
+            self.state = state;
+            parser_match
+        })
+    }
+    /// Memoizes the result, unless that would exceed the limits on memoized results, in which case the error is recorded instead
+    pub fn set_memory(& mut self, memory_key: MemoryKey, parser_match: Option<MatchId>) -> Option<MatchId> {
         // TODO: every time the parser steps forward, we can abandon parts of this map where start_position < new_position
-        if let Some(_old_value) = self.memory.insert((start_position, parser_operator_id), parser_match.clone()) {
+        let entry_bytes = estimate_memory_bytes::<S>(&self.arena, parser_match);
+        if self.max_memory_entries.is_some_and(|max_memory_entries| self.memory.len() >= max_memory_entries) {
+            self.set_error(ParseError::TooManyMemoEntries { position: memory_key.start_position });
+            return parser_match;
+        }
+        if self.max_memory_bytes.is_some_and(|max_memory_bytes| self.memory_bytes + entry_bytes > max_memory_bytes) {
+            self.set_error(ParseError::TooManyMemoBytes { position: memory_key.start_position });
+            return parser_match;
+        }
+        self.memory_bytes += entry_bytes;
+        if parser_match.is_some() {
+            self.memory_checkpoint = self.arena.checkpoint();
+        }
+        if let Some(_old_value) = self.memory.insert(memory_key, (parser_match, self.state.clone())) {
             // TODO: If we try re-insert over the same key, this is not the user's fault. Try to add test case or something?
-            panic!("Reinserted over same key at position {}", start_position)
+            panic!("Reinserted over same key at position {}", memory_key.start_position)
         };
         parser_match
     }
+    pub fn get_state(&self) -> ParserState<S> {
+        self.state.clone()
+    }
+    /// Like `get_state()`, without taking a snapshot
+    pub fn get_state_ref(&self) -> &ParserState<S> {
+        &self.state
+    }
+    pub fn set_state(&mut self, state: ParserState<S>) {
+        self.state = state;
+    }
+    /// Stores the text between the positions under `name`, until the parser backtracks over it or the capture scope is closed
+    pub fn push_capture(&mut self, name: Rc<String>, start_position: usize, end_position: usize) {
+        self.state.push_capture(self.next_capture_id, name, start_position, end_position);
+        self.next_capture_id += 1;
+    }
+    pub fn get_capture(&self, name: &str) -> Option<&CapturedText> {
+        self.state.get_capture(name)
+    }
+    pub fn get_indentation(&self) -> usize {
+        self.state.get_indentation()
+    }
+    pub fn push_indentation(&mut self, width: usize) {
+        self.state.push_indentation(self.next_indentation_id, width);
+        self.next_indentation_id += 1;
+    }
+    pub fn pop_indentation(&mut self) -> Option<usize> {
+        self.state.pop_indentation()
+    }
+    /// Parses with `user_state` as the user state to start with; see `set_user_state()`.
+    /// Only ops made for user state of type `T` can parse with the context this returns.
+    /// The limits and options set so far are kept, but nothing from parses made with this context.
+    pub fn with_user_state<T>(self, user_state: T) -> ParserContext<'ft, T> {
+        ParserContext {
+            max_depth: self.max_depth,
+            max_steps: self.max_steps,
+            max_memory_entries: self.max_memory_entries,
+            max_memory_bytes: self.max_memory_bytes,
+            deadline: self.deadline,
+            profile: self.profile.is_some().then(Profile::default),
+            nfc: self.nfc,
+            ..ParserContext::with_input(self.input, self.full_text, user_state)
+        }
+    }
+    pub fn get_user_state(&self) -> &S {
+        self.state.get_user_state()
+    }
+    /// State kept for the grammar, such as a symbol table, which `Predicate`s can read and `Action`s change.
+    /// It is part of the `ParserState`, so a change made by an op which the parser backtracks over is undone.
+    ///
+    /// The state is replaced rather than changed, since earlier states are kept for backtracking;
+    /// a large state should share its parts, e.g. with `Rc`, so that it is cheap to copy.
+    pub fn set_user_state(&mut self, user_state: S) {
+        self.state.set_user_state(self.next_user_state_id, user_state);
+        self.next_user_state_id += 1;
+    }
+    /// Rules open a capture scope so that captures made in one rule invocation
+    /// are not seen after it returns, e.g. by the enclosing invocation of a recursive rule.
+    pub fn open_capture_scope(&self) -> CaptureScope {
+        CaptureScope(self.state.get_captures())
+    }
+    pub fn close_capture_scope(&mut self, capture_scope: CaptureScope) {
+        self.state.set_captures(capture_scope.0);
+    }
 
-    pub fn push_rule_set(&mut self, rule_set: Rc<Grammar>) {
-        self.current_grammar.push(rule_set)
+    /// Identifies the stack of grammars and the instantiation currently in effect.
+    /// The result of a `RuleReference` (and of everything containing one) depends on both,
+    /// so the scope is part of the memoization key.
+    pub fn get_scope_id(&self) -> usize {
+        self.current_scope
+    }
+    fn update_scope(&mut self) {
+        let key = (
+            self.current_grammar.iter().map(|rule_set| rule_set.get_id()).collect(),
+            self.get_instantiation_id(),
+        );
+        let next_scope = self.scopes.len();
+        self.current_scope = *self.scopes.entry(key).or_insert(next_scope);
+    }
+    pub fn push_rule_set(&mut self, rule_set: Rc<Grammar<S>>) {
+        self.current_grammar.push(rule_set);
+        self.update_scope();
     }
     pub fn pop_rule_set(&mut self) {
         self.current_grammar.pop();
+        self.update_scope();
+    }
+    /// Replaces the whole stack of grammars, returning the previous stack so that it can be restored later.
+    pub fn replace_rule_sets(&mut self, rule_sets: Vec<Rc<Grammar<S>>>) -> Vec<Rc<Grammar<S>>> {
+        let previous_rule_sets = std::mem::replace(&mut self.current_grammar, rule_sets);
+        self.update_scope();
+        previous_rule_sets
+    }
+    pub fn get_rule_sets(&self) -> &[Rc<Grammar<S>>] {
+        &self.current_grammar
     }
-    pub fn get_rule(&self, rule_name: &str) -> Option<(Rc<String>, Rc<dyn Parser>)> {
+    /// Looks up a rule in the innermost grammar first, falling back to the enclosing grammars.
+    /// Rules found in an enclosing grammar are parsed with that grammar on top of the stack,
+    /// so that they cannot see rules of the nested grammar.
+    pub fn resolve_rule(&self, rule_name: &str) -> Option<ResolvedRule<S>> {
         self.current_grammar
-        .last()
-        .map(|rule_set| rule_set.get_rule_by_name(rule_name))
-        .flatten()
+        .iter()
+        .enumerate()
+        .rev()
+        .find_map(|(index, rule_set)|
+            rule_set.resolve_rule(rule_name).map(|resolved| ResolvedRule {
+                rule_sets: resolved.rule_sets.or_else(||
+                    (index + 1 < self.current_grammar.len()).then(|| self.current_grammar[..=index].to_vec())
+                ),
+                ..resolved
+            })
+        )
+    }
+    pub fn get_rule(&self, rule_name: &str) -> Option<(Rc<String>, Rc<dyn Parser<S>>)> {
+        self.resolve_rule(rule_name)
+        .map(|resolved| (resolved.rule_name, resolved.parser_operator))
+    }
+    pub fn get_rule_parameters(&self, rule_name: &str) -> Vec<Rc<String>> {
+        self.resolve_rule(rule_name)
+        .map(|resolved| resolved.parameters)
+        .unwrap_or_default()
+    }
+    pub fn get_instantiation(&self) -> Option<Rc<Instantiation<S>>> {
+        self.current_instantiation.clone()
+    }
+    /// Replaces the current instantiation, returning the previous one so that it can be restored later.
+    pub fn set_instantiation(&mut self, instantiation: Option<Rc<Instantiation<S>>>) -> Option<Rc<Instantiation<S>>> {
+        let previous_instantiation = std::mem::replace(&mut self.current_instantiation, instantiation);
+        self.update_scope();
+        previous_instantiation
+    }
+    fn get_instantiation_id(&self) -> usize {
+        self.current_instantiation
+        .as_ref()
+        .map_or(0, |instantiation| instantiation.id())
+    }
+    /// Binds `parameters` to `arguments` for the `RuleCall` identified by `rule_call_id`.
+    /// The result is reused each time the same `RuleCall` is reached from the same enclosing instantiation and grammars,
+    /// so that the memoized results of the rule body can be shared between those calls.
+    pub fn instantiate(&mut self, rule_call_id: usize, parameters: &[Rc<String>], arguments: &[Rc<dyn Parser<S>>]) -> Rc<Instantiation<S>> {
+        let key = (rule_call_id, self.current_scope);
+        if let Some(instantiation) = self.instantiations.get(&key) {
+            return instantiation.clone();
+        }
+        let instantiation = Rc::new(Instantiation::new(
+            self.instantiations.len() + 1,
+            parameters.iter().cloned().zip(arguments.iter().cloned()).collect(),
+            self.current_instantiation.clone(),
+            self.current_grammar.clone(),
+        ));
+        self.instantiations.insert(key, instantiation.clone());
+        instantiation
+    }
+    /// If `parameter_name` is bound in the current instantiation, returns the argument
+    /// together with that instantiation. The argument must be parsed in its `get_enclosing()` instantiation.
+    pub fn get_argument(&self, parameter_name: &str) -> Option<BoundArgument<S>> {
+        self.current_instantiation
+        .as_ref()
+        .and_then(|instantiation|
+            instantiation
+            .get_argument(parameter_name)
+            .map(|argument| (argument, instantiation.clone()))
+        )
+    }
+    /// Which children of the `Alternation` identified by `alternation_id` can match, by the next byte.
+    /// Built the first time the alternation is parsed in each scope, from the FIRST sets of its children in that scope.
+    pub(crate) fn get_dispatch_table(&mut self, alternation_id: usize, children: &[Rc<dyn Parser<S>>]) -> Rc<DispatchTable> {
+        let key = (alternation_id, self.current_scope);
+        if let Some(dispatch_table) = self.dispatch_tables.get(&key) {
+            return dispatch_table.clone();
+        }
+        let parameters = self.current_instantiation
+            .as_ref()
+            .map(|instantiation| instantiation.get_parameters())
+            .unwrap_or_default();
+        let first_sets = self.analyzer.first_sets(&self.current_grammar, &parameters, children);
+        let dispatch_table = Rc::new(DispatchTable::new(&first_sets));
+        self.dispatch_tables.insert(key, dispatch_table.clone());
+        dispatch_table
     }
-    pub fn get_starting_rule(&self) -> Option<(Rc<String>, Rc<dyn Parser>)> {
+    /// Whether `op` can match without consuming input, by the same analysis as `get_dispatch_table()`
+    pub(crate) fn can_match_nothing(&mut self, op: &Rc<dyn Parser<S>>) -> bool {
+        let parameters = self.current_instantiation
+            .as_ref()
+            .map(|instantiation| instantiation.get_parameters())
+            .unwrap_or_default();
+        let first_sets = self.analyzer.first_sets(&self.current_grammar, &parameters, std::slice::from_ref(op));
+        first_sets[0].0
+    }
+    pub fn get_starting_rule(&self) -> Option<(Rc<String>, Rc<dyn Parser<S>>)> {
         self.current_grammar
         .last()
-        .map(|rule_set| rule_set.get_starting_rule())
-        .flatten()
+        .and_then(|rule_set| rule_set.get_starting_rule())
     }
     pub fn get_compiled_regex(&self, pattern: &str, multi_line: bool, case_insensitive: bool, dot_matches_new_line: bool) -> Regex {
         // TODO:  memoize; this is super slow to do repeatedly
@@ -71,3 +619,240 @@ impl<'ft> ParserContext<'ft> {
         }
     }
 }
+
+
+/// The size of a memory entry and of the match it holds, not counting the match's children
+fn estimate_memory_bytes<S>(arena: &MatchArena, parser_match: Option<MatchId>) -> usize {
+    let entry_bytes = size_of::<MemoryKey>() + size_of::<(Option<MatchId>, ParserState<S>)>();
+    // The match is kept in the arena anyway, but memoizing it keeps it reachable
+    let match_bytes = parser_match.map_or(0, |parser_match| {
+        let node = arena.get_node(parser_match);
+        size_of::<MatchNode>() + size_of_val(arena.get_children(node))
+    });
+    entry_bytes + match_bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use crate::*;
+    use crate::core::OpaqueIdentifier;
+
+    /// Counts how often its child is actually parsed, as opposed to looked up in memory
+    #[derive(Debug)]
+    struct Counter {
+        id: OpaqueIdentifier,
+        child: Rc<dyn Parser>,
+        count: Cell<usize>,
+    }
+    impl Parser for Counter {
+        fn get_id(&self) -> usize {
+            self.id.id()
+        }
+        fn parse_internal(self: Rc<Self>, context: &mut Box<ParserContext>, start_position: usize) -> Option<MatchId> {
+            self.count.set(self.count.get() + 1);
+            self.child.clone().parse_match(context, start_position)
+        }
+    }
+    fn counter(child: Rc<dyn Parser>) -> Rc<Counter> {
+        Rc::new(Counter { id: OpaqueIdentifier::new(), child, count: Cell::new(0) })
+    }
+
+    #[test]
+    fn memory_is_hit_when_backtracking() {
+        let shared = counter(lit!("a"));
+        let alt = alt!(seq!(shared.clone(), lit!("x")), seq!(shared.clone(), lit!("y")));
+        let mut context = Box::new(ParserContext::new("ay"));
+        let result = alt.parse(&mut context, 0).unwrap();
+        assert_eq!(result.len(), 2);
+        assert_eq!(shared.count.get(), 1);
+    }
+
+    #[test]
+    fn memory_is_hit_for_rule_bodies() {
+        let body = counter(reg!("[0-9]+"));
+        let gram = Rc::new(Grammar::new(
+            None,
+            vec![
+                ("Prog", alt!(seq!(rul!("Num"), lit!("+")), seq!(rul!("Num"), lit!("-")))),
+                ("Num",  body.clone()),
+            ]
+        ));
+        let mut context = Box::new(ParserContext::new("12-"));
+        let result = gram.parse(&mut context, 0).unwrap();
+        assert_eq!(result.len(), 3);
+        assert_eq!(body.count.get(), 1);
+    }
+
+    #[test]
+    fn memory_is_isolated_between_nested_grammars() {
+        // The same RuleReference object resolves to a different rule in each grammar
+        let shared_reference = rul!("A");
+        let first = Rc::new(Grammar::new(None, vec![("Start", shared_reference.clone()), ("A", lit!("a"))]));
+        let second = Rc::new(Grammar::new(None, vec![("Start", shared_reference.clone()), ("A", lit!("b"))]));
+        let alt = alt!(first, second);
+        let mut context = Box::new(ParserContext::new("b"));
+        let result = alt.parse(&mut context, 0).unwrap();
+        assert_eq!(result.len(), 1);
+    }
+
+    #[test]
+    fn memory_is_isolated_between_instantiations() {
+        let gram = Rc::new(
+            Grammar::new(
+                None,
+                vec![
+                    ("Prog", alt!(call!("Pair", lit!("a")), call!("Pair", lit!("b")))),
+                ]
+            ).with_parameterized_rule("Pair", vec!["X"], seq!(rul!("X"), rul!("X")))
+        );
+        let mut context = Box::new(ParserContext::new("bb"));
+        let result = gram.parse(&mut context, 0).unwrap();
+        assert_eq!(result.len(), 2);
+    }
+
+    fn nested() -> Rc<Grammar> {
+        Rc::new(Grammar::new(None, vec![
+            ("Nested", alt!(seq!(lit!("("), rul!("Nested"), lit!(")")), lit!("x"))),
+        ]))
+    }
+
+    #[test]
+    fn too_deep_input_is_an_error() {
+        let text = "(".repeat(5) + "x" + &")".repeat(5);
+        let mut context = Box::new(ParserContext::new(&text).with_max_depth(Some(10)));
+        // Each level of parentheses is a RuleReference, an Alternation and a Sequence
+        assert_eq!(nested().try_parse(&mut context, 0).unwrap_err(), ParseError::TooDeep { position: 3 });
+        assert_eq!(context.get_depth(), 0);
+        assert!(context.get_error().is_none());
+
+        let mut context = Box::new(ParserContext::new(&text).with_max_depth(Some(20)));
+        let result = nested().try_parse(&mut context, 0).unwrap().unwrap();
+        assert_eq!(result.len(), 11);
+    }
+
+    #[test]
+    fn default_max_depth_prevents_stack_overflow() {
+        let text = "(".repeat(100_000) + "x" + &")".repeat(100_000);
+        let mut context = Box::new(ParserContext::new(&text));
+        assert!(matches!(nested().try_parse(&mut context, 0), Err(ParseError::TooDeep { .. })));
+    }
+
+    #[cfg(feature = "stacker")]
+    #[test]
+    fn stack_grows_without_max_depth() {
+        let text = "(".repeat(10_000) + "x" + &")".repeat(10_000);
+        let mut context = Box::new(ParserContext::new(&text).with_max_depth(None));
+        let result = nested().try_parse(&mut context, 0).unwrap().unwrap();
+        assert_eq!(result.len(), text.len());
+    }
+
+    fn words() -> Rc<Grammar> {
+        Rc::new(Grammar::new(None, vec![
+            ("Words", qtt!(seq!(rul!("Word"), lit!(" ")), 0, None)),
+            ("Word",  reg!("[a-z]+")),
+        ]))
+    }
+
+    #[test]
+    fn step_limit() {
+        let text = "a ".repeat(100);
+        let mut context = Box::new(ParserContext::new(&text).with_max_steps(Some(50)));
+        assert!(matches!(words().try_parse(&mut context, 0), Err(ParseError::TooManySteps { .. })));
+        assert_eq!(context.get_steps(), 50);
+
+        let mut context = Box::new(ParserContext::new(&text).with_max_steps(Some(1_000)));
+        let result = words().try_parse(&mut context, 0).unwrap().unwrap();
+        assert_eq!(result.len(), 200);
+    }
+
+    /// Parses its child at the same position until it fails, like a repetition which does not check for progress
+    #[derive(Debug)]
+    struct Forever {
+        id: OpaqueIdentifier,
+        child: Rc<dyn Parser>,
+    }
+    impl Parser for Forever {
+        fn get_id(&self) -> usize {
+            self.id.id()
+        }
+        fn parse_internal(self: Rc<Self>, context: &mut Box<ParserContext>, start_position: usize) -> Option<MatchId> {
+            while self.child.clone().parse_match(context, start_position).is_some() {}
+            None
+        }
+    }
+    fn forever() -> Rc<Forever> {
+        Rc::new(Forever { id: OpaqueIdentifier::new(), child: qtt!(lit!("a"), 0, None) })
+    }
+
+    #[test]
+    fn memoized_results_count_towards_the_step_limit() {
+        let mut context = Box::new(ParserContext::new("b").with_max_steps(Some(1_000)));
+        assert_eq!(forever().try_parse(&mut context, 0).unwrap_err(), ParseError::TooManySteps { position: 0 });
+        assert_eq!(context.get_steps(), 1_000);
+    }
+
+    #[test]
+    fn memoized_results_are_checked_against_the_deadline() {
+        let deadline = std::time::Instant::now() + std::time::Duration::from_millis(50);
+        let mut context = Box::new(ParserContext::new("b").with_deadline(Some(deadline)));
+        assert_eq!(forever().try_parse(&mut context, 0).unwrap_err(), ParseError::DeadlineExceeded { position: 0 });
+    }
+
+    #[test]
+    fn repetition_of_a_repetition_which_matches_nothing() {
+        let mut context = Box::new(ParserContext::new("b").with_max_steps(Some(1_000)));
+        let result = qtt!(qtt!(lit!("a"), 0, None), 0, None).try_parse(&mut context, 0).unwrap().unwrap();
+        assert_eq!(result.get_children().len(), 1);
+        let mut context = Box::new(ParserContext::new("b"));
+        let result = qtt!(qtt!(lit!("a"), 0, None), 3, None).try_parse(&mut context, 0).unwrap().unwrap();
+        assert_eq!(result.get_children().len(), 3);
+    }
+
+    #[test]
+    fn memo_limits() {
+        let text = "a ".repeat(100);
+        let mut context = Box::new(ParserContext::new(&text).with_max_memo_entries(Some(50)));
+        assert!(matches!(words().try_parse(&mut context, 0), Err(ParseError::TooManyMemoEntries { .. })));
+        assert_eq!(context.get_memory_entries(), 50);
+
+        let mut context = Box::new(ParserContext::new(&text).with_max_memo_bytes(Some(4_096)));
+        assert!(matches!(words().try_parse(&mut context, 0), Err(ParseError::TooManyMemoBytes { .. })));
+        assert!(context.get_memory_bytes() <= 4_096);
+
+        let mut context = Box::new(ParserContext::new(&text));
+        words().try_parse(&mut context, 0).unwrap();
+        assert!(context.get_memory_bytes() > 4_096);
+    }
+
+    #[test]
+    fn deadline() {
+        let text = "a ".repeat(100);
+        let mut context = Box::new(ParserContext::new(&text).with_deadline(Some(std::time::Instant::now())));
+        assert_eq!(words().try_parse(&mut context, 0).unwrap_err(), ParseError::DeadlineExceeded { position: 0 });
+    }
+
+    #[cfg(feature = "unicode-normalization")]
+    #[test]
+    fn nfc_matches_canonically_equivalent_text() {
+        let gram = Rc::new(Grammar::new(
+            None,
+            vec![
+                ("Start", seq!(alt!(lit!("tea"), lit!("café")), lit!(" "), cap!(reg!("[^ ]+"), "word"), lit!(" "), brf!("word"), rul!("Accent"))),
+                ("Accent", Rc::new(CharClass::new(&['é', 'ü']))),
+            ]
+        ));
+        let decomposed = "cafe\u{301} cafe\u{301} café\u{fc}";
+        let mut context = Box::new(ParserContext::new(decomposed));
+        assert!(gram.clone().parse(&mut context, 0).is_none());
+        let mut context = Box::new(ParserContext::new(decomposed).with_nfc(true));
+        let result = gram.clone().parse(&mut context, 0).unwrap();
+        assert_eq!(result.len(), decomposed.len());
+        // "e" alone is only part of "é"
+        let mut context = Box::new(ParserContext::new("cafe\u{301}").with_nfc(true));
+        assert!(lit!("cafe").parse(&mut context, 0).is_none());
+        let mut context = Box::new(ParserContext::new("u\u{308}").with_nfc(true));
+        assert_eq!(gram.get_rule_by_name("Accent").unwrap().1.parse(&mut context, 0).map(|result| result.len()), Some(3));
+    }
+}
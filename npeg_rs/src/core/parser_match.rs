@@ -1,76 +1,207 @@
-
+use std::fmt;
 use std::rc::Rc;
 
-
+use super::{Match, MatchArena, MatchId, Symbol};
 
 /// `ParserMatch`
-/// 
-/// The result of a successful `ParserOperator::parse(...)`
-/// 
+///
+/// The result of a successful `ParserOperator::parse(...)`, read from the `MatchArena` holding it;
+/// see `ParserContext::get_match(...)`
+///
 /// `ParserMatch` may or may not have a label which is assigned to the match as part of the `parse()` process
-/// 
+///
 /// The label
-/// 
-#[derive(Debug)]
-pub struct ParserMatch {
-    start_position: usize,
-    end_position: usize,
-    label: Option<Rc<String>>,
-    children: Rc<Vec<Rc<ParserMatch>>>,
+///
+#[derive(Clone, Copy)]
+pub struct ParserMatch<'a> {
+    arena: &'a MatchArena,
+    id: MatchId,
 }
-impl ParserMatch {
-    pub fn new(start_position: usize, end_position: usize, label: Option<Rc<String>>, children: Rc<Vec<Rc<Self>>>) -> Rc<Self>{
-        // Only allow obtain reference behind Rc
-        Rc::new(ParserMatch {
-            start_position,
-            end_position,
-            label,
-            children,
-        })
+impl<'a> ParserMatch<'a> {
+    pub(crate) fn new(arena: &'a MatchArena, id: MatchId) -> Self {
+        ParserMatch {
+            arena,
+            id,
+        }
+    }
+    pub fn get_id(&self) -> MatchId {
+        self.id
+    }
+    pub fn get_start_position(&self) -> usize {
+        self.arena.get_node(self.id).start_position
+    }
+    pub fn get_end_position(&self) -> usize {
+        self.arena.get_node(self.id).end_position
+    }
+    pub fn get_label(&self) -> Option<&'a str> {
+        self.get_label_symbol().map(|label| label.as_str())
+    }
+    pub fn get_label_symbol(&self) -> Option<Symbol> {
+        self.arena.get_node(self.id).label
+    }
+    pub fn get_children(&self) -> Children<'a> {
+        Children {
+            arena: self.arena,
+            ids: self.arena.get_children(self.arena.get_node(self.id)).iter(),
+        }
+    }
+    pub fn get_child(&self, index: usize) -> Option<ParserMatch<'a>> {
+        self.get_children().nth(index)
     }
     pub fn len(&self) -> usize {
-        self.end_position - self.start_position
+        self.get_end_position() - self.get_start_position()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
-    pub fn get_text<'a> (&self, full_text:&'a str) -> &'a str{
-        full_text[self.start_position..self.end_position].into()
+    pub fn get_text<'t> (&self, full_text:&'t str) -> &'t str{
+        &full_text[self.get_start_position()..self.get_end_position()]
     }
-    pub fn with_label(&self, new_label:Rc<String>)->Rc<Self>{
-        Self::new(
-            self.start_position,
-            self.end_position,
-            Some(new_label),
-            self.children.clone(),
-        )
+    pub fn get_bytes<'t> (&self, input:&'t [u8]) -> &'t [u8]{
+        &input[self.get_start_position()..self.get_end_position()]
+    }
+}
+
+/// Written like the `ParserMatch` struct of earlier versions, which held its children directly
+impl fmt::Debug for ParserMatch<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ParserMatch")
+            .field("start_position", &self.get_start_position())
+            .field("end_position", &self.get_end_position())
+            .field("label", &self.get_label())
+            .field("children", &self.get_children().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// The children of a `ParserMatch`; see `ParserMatch::get_children()`
+#[derive(Clone)]
+pub struct Children<'a> {
+    arena: &'a MatchArena,
+    ids: std::slice::Iter<'a, MatchId>,
+}
+
+impl<'a> Iterator for Children<'a> {
+    type Item = ParserMatch<'a>;
+    fn next(&mut self) -> Option<ParserMatch<'a>> {
+        self.ids.next().map(|id| ParserMatch::new(self.arena, *id))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.ids.size_hint()
+    }
+    fn nth(&mut self, index: usize) -> Option<ParserMatch<'a>> {
+        self.ids.nth(index).map(|id| ParserMatch::new(self.arena, *id))
+    }
+}
+
+impl DoubleEndedIterator for Children<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.ids.next_back().map(|id| ParserMatch::new(self.arena, *id))
+    }
+}
+
+impl ExactSizeIterator for Children<'_> {}
+
+/// `ParseTree`
+///
+/// A match together with the `MatchArena` holding it and its descendants.
+/// Returned by `Parser::parse`, and by parsers which do not parse with a `ParserContext`, such as `Program::parse`.
+/// Has the methods of `ParserMatch`, for the root match.
+#[derive(Clone)]
+pub struct ParseTree {
+    arena: MatchArena,
+    root: MatchId,
+}
+
+impl ParseTree {
+    pub fn new(arena: MatchArena, root: MatchId) -> ParseTree {
+        ParseTree {
+            arena,
+            root,
+        }
+    }
+    pub fn get_root(&self) -> ParserMatch<'_> {
+        self.arena.get(self.root)
+    }
+    pub fn get_arena(&self) -> &MatchArena {
+        &self.arena
+    }
+    pub fn get_start_position(&self) -> usize {
+        self.get_root().get_start_position()
+    }
+    pub fn get_end_position(&self) -> usize {
+        self.get_root().get_end_position()
+    }
+    pub fn get_label(&self) -> Option<&str> {
+        self.get_root().get_label()
+    }
+    pub fn get_label_symbol(&self) -> Option<Symbol> {
+        self.get_root().get_label_symbol()
+    }
+    pub fn get_children(&self) -> Children<'_> {
+        self.get_root().get_children()
+    }
+    pub fn get_child(&self, index: usize) -> Option<ParserMatch<'_>> {
+        self.get_root().get_child(index)
+    }
+    pub fn len(&self) -> usize {
+        self.get_root().len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.get_root().is_empty()
+    }
+    pub fn get_text<'t> (&self, full_text:&'t str) -> &'t str{
+        self.get_root().get_text(full_text)
+    }
+    pub fn get_bytes<'t> (&self, input:&'t [u8]) -> &'t [u8]{
+        self.get_root().get_bytes(input)
+    }
+    /// The root match, which borrows `full_text`; see `Match`
+    pub fn into_match(self, full_text: &str) -> Match<'_> {
+        Match::new(Rc::new(self.arena), self.root, full_text)
+    }
+}
+
+/// Written like the root match
+impl fmt::Debug for ParseTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.get_root().fmt(f)
     }
 }
 
 
 #[cfg(test)]
 mod tests {
-    use std::rc::Rc;
+    use super::super::{MatchArena, Symbol};
 
-    use super::ParserMatch;
     #[test]
     fn match_gets_correct_substring() {
-        let m = ParserMatch::new(
-            0,
-            1,
-            None,
-            Rc::new(vec![]),
-        );
+        let mut arena = MatchArena::new();
+        let id = arena.push(0, 1, None, &[]);
         let full_text = "0123456789";
-        assert_eq!(m.get_text(full_text), "0");
+        assert_eq!(arena.get(id).get_text(full_text), "0");
     }
 
     #[test]
     fn match_gets_correct_substring_unicode() {
-        let m = ParserMatch::new(
-            0,
-            "0✔️".len(),
-            None,
-            Rc::new(vec![]),
-        );
+        let mut arena = MatchArena::new();
+        let id = arena.push(0, "0✔️".len(), None, &[]);
         let full_text = "0✔️23456789";
-        assert_eq!(m.get_text(full_text), "0✔️");
+        assert_eq!(arena.get(id).get_text(full_text), "0✔️");
+    }
+
+    #[test]
+    fn relabeled_match_shares_children() {
+        let mut arena = MatchArena::new();
+        let first = arena.push(0, 1, None, &[]);
+        let second = arena.push(1, 3, Some(Symbol::intern("b")), &[]);
+        let parent = arena.push(0, 3, None, &[first, second]);
+        let relabeled = arena.relabel(parent, Symbol::intern("parent"));
+        assert_eq!(arena.get(parent).get_label(), None);
+        let relabeled = arena.get(relabeled);
+        assert_eq!(relabeled.get_label_symbol(), Some(Symbol::intern("parent")));
+        assert_eq!(relabeled.get_children().map(|child| child.get_id()).collect::<Vec<_>>(), vec![first, second]);
+        assert_eq!(relabeled.get_child(1).unwrap().get_label(), Some("b"));
+        assert_eq!(relabeled.get_children().len(), 2);
     }
 }
@@ -0,0 +1,147 @@
+use std::rc::Rc;
+
+/// `CapturedText`
+///
+/// One entry of the capture stack; the text matched by a `Capture` op, stored under a name.
+/// Entries are immutable and link to the entry below them, so a stack is shared rather than copied
+/// by every `ParserState` snapshot that contains it.
+#[derive(Debug)]
+pub struct CapturedText {
+    id: usize,
+    name: Rc<String>,
+    start_position: usize,
+    end_position: usize,
+    previous: Option<Rc<CapturedText>>,
+}
+
+impl CapturedText {
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+    pub fn get_start_position(&self) -> usize {
+        self.start_position
+    }
+    pub fn get_end_position(&self) -> usize {
+        self.end_position
+    }
+    pub fn get_text<'a>(&self, full_text: &'a str) -> &'a str {
+        &full_text[self.start_position..self.end_position]
+    }
+    pub fn get_bytes<'a>(&self, input: &'a [u8]) -> &'a [u8] {
+        &input[self.start_position..self.end_position]
+    }
+}
+
+/// One level of the indentation stack maintained by `Indent` and `Dedent`
+#[derive(Debug)]
+pub struct IndentationLevel {
+    id: usize,
+    width: usize,
+    previous: Option<Rc<IndentationLevel>>,
+}
+
+/// `ParserState`
+///
+/// State which ops change as they succeed, and which must be undone when the parser backtracks.
+/// `Parser::parse` takes a snapshot before each op and restores it if the op fails.
+///
+/// Taking a snapshot is a cheap `clone()`.
+#[derive(Debug)]
+pub struct ParserState<S = ()> {
+    captures: Option<Rc<CapturedText>>,
+    indentation: Option<Rc<IndentationLevel>>,
+    /// See `ParserContext::set_user_state`
+    user_state: Rc<S>,
+    /// Changes whenever the user state is set
+    user_state_id: usize,
+}
+
+// Not derived, since that would require `S: Clone`
+impl<S> Clone for ParserState<S> {
+    fn clone(&self) -> Self {
+        ParserState {
+            captures: self.captures.clone(),
+            indentation: self.indentation.clone(),
+            user_state: self.user_state.clone(),
+            user_state_id: self.user_state_id,
+        }
+    }
+}
+
+impl<S> ParserState<S> {
+    pub fn new(user_state: S) -> Self {
+        ParserState {
+            captures: None,
+            indentation: None,
+            user_state: Rc::new(user_state),
+            user_state_id: 0,
+        }
+    }
+    /// Identifies the capture stack; part of the memoization key, since `BackReference` depends on it.
+    pub fn get_captures_id(&self) -> usize {
+        self.captures.as_ref().map_or(0, |captured_text| captured_text.id)
+    }
+    pub fn push_capture(&mut self, id: usize, name: Rc<String>, start_position: usize, end_position: usize) {
+        self.captures = Some(Rc::new(CapturedText {
+            id,
+            name,
+            start_position,
+            end_position,
+            previous: self.captures.take(),
+        }));
+    }
+    /// Returns the most recent capture stored under `name`
+    pub fn get_capture(&self, name: &str) -> Option<&CapturedText> {
+        let mut captured_text = self.captures.as_deref();
+        while let Some(each_captured_text) = captured_text {
+            if each_captured_text.get_name() == name {
+                return Some(each_captured_text);
+            }
+            captured_text = each_captured_text.previous.as_deref();
+        }
+        None
+    }
+    /// Identifies the indentation stack; part of the memoization key, since the indentation ops depend on it.
+    pub fn get_indentation_id(&self) -> usize {
+        self.indentation.as_ref().map_or(0, |indentation_level| indentation_level.id)
+    }
+    /// The width of the innermost indented block, or zero at the top level
+    pub fn get_indentation(&self) -> usize {
+        self.indentation.as_ref().map_or(0, |indentation_level| indentation_level.width)
+    }
+    pub fn push_indentation(&mut self, id: usize, width: usize) {
+        self.indentation = Some(Rc::new(IndentationLevel {
+            id,
+            width,
+            previous: self.indentation.take(),
+        }));
+    }
+    /// Removes the innermost indentation level and returns its width, or `None` at the top level
+    pub fn pop_indentation(&mut self) -> Option<usize> {
+        self.indentation.take().map(|indentation_level| {
+            self.indentation = indentation_level.previous.clone();
+            indentation_level.width
+        })
+    }
+    /// Identifies the user state; part of the memoization key, since `Predicate`s and `Action`s may depend on it.
+    pub fn get_user_state_id(&self) -> usize {
+        self.user_state_id
+    }
+    pub fn get_user_state(&self) -> &S {
+        &self.user_state
+    }
+    pub fn set_user_state(&mut self, id: usize, user_state: S) {
+        self.user_state = Rc::new(user_state);
+        self.user_state_id = id;
+    }
+    pub(crate) fn get_captures(&self) -> Option<Rc<CapturedText>> {
+        self.captures.clone()
+    }
+    pub(crate) fn set_captures(&mut self, captures: Option<Rc<CapturedText>>) {
+        self.captures = captures;
+    }
+}
+
+/// Returned by `ParserContext::open_capture_scope`; captures made until the scope is closed are discarded when it is.
+#[derive(Debug)]
+pub struct CaptureScope(pub(crate) Option<Rc<CapturedText>>);
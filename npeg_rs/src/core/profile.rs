@@ -0,0 +1,227 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::Duration;
+
+use super::OpKind;
+use crate::peg_text::write_expression;
+
+/// Descriptions of ops in reports are cut to this many characters
+const DESCRIPTION_LENGTH: usize = 60;
+
+/// Counters for one op, or for one rule, collected while profiling
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProfileEntry {
+    /// Calls to `Parser::parse`, including those answered from memory
+    pub invocations: usize,
+    pub memo_hits: usize,
+    pub memo_misses: usize,
+    /// Parses which failed, so that the parser had to backtrack; results from memory are included
+    pub backtracks: usize,
+    /// Time spent parsing, including the time spent in child ops
+    pub total_time: Duration,
+    /// Time spent parsing, not including the time spent in child ops
+    pub self_time: Duration,
+}
+
+/// `Profile`
+///
+/// Statistics collected by a `ParserContext` created `with_profiling(true)`; see `ParserContext::get_profile()`.
+/// Rules are counted where they are invoked, by a `RuleReference`, a `RuleCall` or a `Grammar`.
+#[derive(Debug, Default)]
+pub struct Profile {
+    /// By op id, with a description of the op
+    ops: BTreeMap<usize, (String, ProfileEntry)>,
+    rules: BTreeMap<String, ProfileEntry>,
+    /// The time spent in the children of each op currently being parsed
+    child_times: Vec<Duration>,
+}
+
+impl Profile {
+    fn entries<S>(&mut self, parser_operator_id: usize, kind: OpKind<S>) -> (&mut ProfileEntry, Option<&mut ProfileEntry>) {
+        let rule_name = match kind {
+            OpKind::RuleReference(rule_name) | OpKind::RuleCall { rule_name, .. } => Some(rule_name.to_owned()),
+            OpKind::Grammar(grammar) => grammar.get_starting_rule_name().map(|rule_name| rule_name.to_string()),
+            _ => None,
+        };
+        let (_, op_entry) = self.ops.entry(parser_operator_id).or_insert_with(|| (describe(kind), ProfileEntry::default()));
+        let rule_entry = rule_name.map(|rule_name| self.rules.entry(rule_name).or_default());
+        (op_entry, rule_entry)
+    }
+    pub(crate) fn record_memo_hit<S>(&mut self, parser_operator_id: usize, kind: OpKind<S>, matched: bool) {
+        let (op_entry, rule_entry) = self.entries(parser_operator_id, kind);
+        for entry in std::iter::once(op_entry).chain(rule_entry) {
+            entry.invocations += 1;
+            entry.memo_hits += 1;
+            entry.backtracks += !matched as usize;
+        }
+    }
+    pub(crate) fn enter(&mut self) {
+        self.child_times.push(Duration::ZERO);
+    }
+    pub(crate) fn leave<S>(&mut self, parser_operator_id: usize, kind: OpKind<S>, matched: bool, total_time: Duration) {
+        let child_time = self.child_times.pop().unwrap_or_default();
+        if let Some(parent_child_time) = self.child_times.last_mut() {
+            *parent_child_time += total_time;
+        }
+        let (op_entry, rule_entry) = self.entries(parser_operator_id, kind);
+        for entry in std::iter::once(op_entry).chain(rule_entry) {
+            entry.invocations += 1;
+            entry.memo_misses += 1;
+            entry.backtracks += !matched as usize;
+            entry.total_time += total_time;
+            entry.self_time += total_time.saturating_sub(child_time);
+        }
+    }
+    /// Each op as PEG text, with its statistics, by descending self time
+    pub fn get_op_entries(&self) -> Vec<(&str, &ProfileEntry)> {
+        let mut op_entries: Vec<(&str, &ProfileEntry)> = self.ops.values().map(|(description, entry)| (description.as_str(), entry)).collect();
+        op_entries.sort_by(|(_, a), (_, b)| b.self_time.cmp(&a.self_time).then(b.invocations.cmp(&a.invocations)));
+        op_entries
+    }
+    /// Each rule name, with its statistics, by descending total time
+    pub fn get_rule_entries(&self) -> Vec<(&str, &ProfileEntry)> {
+        let mut rule_entries: Vec<(&str, &ProfileEntry)> = self.rules.iter().map(|(rule_name, entry)| (rule_name.as_str(), entry)).collect();
+        rule_entries.sort_by(|(_, a), (_, b)| b.total_time.cmp(&a.total_time).then(b.invocations.cmp(&a.invocations)));
+        rule_entries
+    }
+    /// A table of rules by total time, followed by a table of ops by self time
+    pub fn report_text(&self) -> String {
+        let mut report = String::new();
+        write_table(&mut report, "Rule", &self.get_rule_entries());
+        report.push('\n');
+        write_table(&mut report, "Op", &self.get_op_entries());
+        report
+    }
+    /// The same as `report_text()` as a JSON object with `rules` and `ops` arrays; times are in nanoseconds
+    pub fn report_json(&self) -> String {
+        let mut report = String::from("{\n");
+        write_json_array(&mut report, "rules", &self.get_rule_entries());
+        report.push_str(",\n");
+        write_json_array(&mut report, "ops", &self.get_op_entries());
+        report.push_str("\n}\n");
+        report
+    }
+}
+
+fn describe<S>(kind: OpKind<S>) -> String {
+    let mut description = format!("{}: ", kind.name());
+    write_expression(&mut description, kind).expect("Writing to a String does not fail");
+    if description.chars().count() > DESCRIPTION_LENGTH {
+        description = description.chars().take(DESCRIPTION_LENGTH - 3).collect::<String>() + "...";
+    }
+    description
+}
+
+fn write_table(report: &mut String, heading: &str, entries: &[(&str, &ProfileEntry)]) {
+    let width = entries.iter().map(|(name, _)| name.chars().count()).max().unwrap_or(0).max(heading.len());
+    let _ = writeln!(
+        report,
+        "{:<width$}  {:>11}  {:>9}  {:>11}  {:>10}  {:>12}  {:>12}",
+        heading, "invocations", "memo hits", "memo misses", "backtracks", "total time", "self time",
+    );
+    for (name, entry) in entries {
+        let _ = writeln!(
+            report,
+            "{:<width$}  {:>11}  {:>9}  {:>11}  {:>10}  {:>12?}  {:>12?}",
+            name, entry.invocations, entry.memo_hits, entry.memo_misses, entry.backtracks, entry.total_time, entry.self_time,
+        );
+    }
+}
+
+fn write_json_array(report: &mut String, key: &str, entries: &[(&str, &ProfileEntry)]) {
+    let _ = write!(report, "  \"{}\": [", key);
+    for (index, (name, entry)) in entries.iter().enumerate() {
+        let _ = write!(
+            report,
+            "{}\n    {{\"name\": {}, \"invocations\": {}, \"memo_hits\": {}, \"memo_misses\": {}, \"backtracks\": {}, \"total_time_ns\": {}, \"self_time_ns\": {}}}",
+            if index > 0 { "," } else { "" },
+            json_string(name),
+            entry.invocations,
+            entry.memo_hits,
+            entry.memo_misses,
+            entry.backtracks,
+            entry.total_time.as_nanos(),
+            entry.self_time.as_nanos(),
+        );
+    }
+    report.push_str(if entries.is_empty() { "]" } else { "\n  ]" });
+}
+
+fn json_string(text: &str) -> String {
+    let mut json = String::from("\"");
+    for each in text.chars() {
+        match each {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            each if (each as u32) < 0x20 => {
+                let _ = write!(json, "\\u{:04x}", each as u32);
+            },
+            each => json.push(each),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::*;
+
+    fn profile_of(full_text: &str) -> Box<ParserContext<'_>> {
+        let gram = Rc::new(Grammar::new(
+            None,
+            vec![
+                ("Prog", alt!(seq!(rul!("Num"), lit!("+")), seq!(rul!("Num"), lit!("-")))),
+                ("Num",  reg!("[0-9]+")),
+            ]
+        ));
+        let mut context = Box::new(ParserContext::new(full_text).with_profiling(true));
+        assert!(gram.parse(&mut context, 0).is_some());
+        context
+    }
+
+    #[test]
+    fn profiling_is_off_by_default() {
+        let mut context = Box::new(ParserContext::new("a"));
+        lit!("a").parse(&mut context, 0);
+        assert!(context.get_profile().is_none());
+    }
+
+    #[test]
+    fn ops_and_rules_are_counted() {
+        let context = profile_of("12-");
+        let profile = context.get_profile().unwrap();
+
+        let rules = profile.get_rule_entries();
+        let (_, prog) = rules.iter().find(|(name, _)| *name == "Prog").unwrap();
+        assert_eq!((prog.invocations, prog.memo_misses, prog.backtracks), (1, 1, 0));
+        let (_, num) = rules.iter().find(|(name, _)| *name == "Num").unwrap();
+        assert_eq!((num.invocations, num.backtracks), (2, 0));
+
+        let ops = profile.get_op_entries();
+        let (_, regex) = ops.iter().find(|(description, _)| *description == "Regex: r\"[0-9]+\"").unwrap();
+        assert_eq!((regex.invocations, regex.memo_hits, regex.memo_misses), (2, 1, 1));
+        let (_, plus) = ops.iter().find(|(description, _)| *description == "Literal: \"+\"").unwrap();
+        assert_eq!((plus.invocations, plus.backtracks), (1, 1));
+        let (_, alternation) = ops.iter().find(|(description, _)| description.starts_with("Alternation: ")).unwrap();
+        assert!(alternation.total_time >= alternation.self_time);
+        assert!(ops.windows(2).all(|pair| pair[0].1.self_time >= pair[1].1.self_time));
+    }
+
+    #[test]
+    fn reports_list_every_entry() {
+        let context = profile_of("12+");
+        let profile = context.get_profile().unwrap();
+        let text = profile.report_text();
+        assert!(text.starts_with("Rule"));
+        assert!(text.contains("Literal: \"+\""));
+        let json = profile.report_json();
+        assert!(json.starts_with("{\n  \"rules\": [\n"));
+        assert!(json.contains("{\"name\": \"Prog\", \"invocations\": 1, \"memo_hits\": 0, \"memo_misses\": 1, \"backtracks\": 0,"));
+        assert!(json.contains("{\"name\": \"Literal: \\\"+\\\"\", \"invocations\": 1, \"memo_hits\": 0, \"memo_misses\": 1, \"backtracks\": 0,"));
+        assert_eq!(json.matches("\"name\"").count(), profile.get_rule_entries().len() + profile.get_op_entries().len());
+    }
+}
@@ -0,0 +1,139 @@
+use std::rc::Rc;
+
+use crate::core::{OpKind, Parser};
+use crate::ops::Grammar;
+
+impl<S> Grammar<S> {
+    /// Renders the rule dependency graph in the Graphviz DOT language;
+    /// one node per rule and one edge for each rule referenced by another.
+    ///
+    /// Imported grammars are drawn as clusters, with their rules named by namespace (`json::Value`).
+    /// The starting rule is drawn in bold.
+    ///
+    /// ```
+    /// # use npeg_rs::*;
+    /// let gram: Grammar = Grammar::new(None, vec![
+    ///     ("Sum",    seq!(rul!("Number"), lit!("+"), rul!("Number"))),
+    ///     ("Number", reg!("[0-9]+")),
+    /// ]);
+    /// assert!(gram.to_dot().contains("\"Sum\" -> \"Number\";"));
+    /// ```
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph Grammar {\n    node [shape=box];\n");
+        write_grammar(self, "", 1, &mut dot);
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn write_grammar<S>(grammar: &Grammar<S>, namespace: &str, depth: usize, dot: &mut String) {
+    let indent = "    ".repeat(depth);
+    let starting_rule_name = grammar.get_starting_rule_name();
+    for (rule_name, parser_operator) in grammar.get_all_rules() {
+        let node = quote(&format!("{}{}", namespace, rule_name));
+        if starting_rule_name.as_ref() == Some(&rule_name) {
+            dot.push_str(&format!("{}{} [style=bold];\n", indent, node));
+        } else {
+            dot.push_str(&format!("{}{};\n", indent, node));
+        }
+        let mut references = vec![];
+        collect_references(&parser_operator, grammar.get_rule_parameters(&rule_name), &mut references);
+        for reference in references {
+            dot.push_str(&format!("{}{} -> {};\n", indent, node, quote(&format!("{}{}", namespace, reference))));
+        }
+    }
+    for (import_namespace, import) in grammar.get_all_imports() {
+        let import_namespace = format!("{}{}::", namespace, import_namespace);
+        dot.push_str(&format!("{}subgraph {} {{\n", indent, quote(&format!("cluster_{}", import_namespace))));
+        dot.push_str(&format!("{}    label={};\n", indent, quote(&import_namespace[..import_namespace.len() - 2])));
+        write_grammar(&import, &import_namespace, depth + 1, dot);
+        dot.push_str(&format!("{}}}\n", indent));
+    }
+}
+
+/// Collects the names of the rules referenced by `parser_operator`, without duplicates.
+/// References to `parameters` are not rules, and are skipped.
+fn collect_references<S>(parser_operator: &Rc<dyn Parser<S>>, parameters: &[Rc<String>], references: &mut Vec<String>) {
+    let mut add = |rule_name: &str| {
+        if !parameters.iter().any(|parameter| &parameter[..] == rule_name) && !references.iter().any(|reference| reference == rule_name) {
+            references.push(rule_name.into());
+        }
+    };
+    match parser_operator.kind() {
+        OpKind::RuleReference(rule_name) => add(rule_name),
+        OpKind::RuleCall { rule_name, .. } => add(rule_name),
+        OpKind::Grammar(nested_grammar) => {
+            // Only references which fall back to the enclosing grammar leave the nested grammar
+            let mut nested_references = vec![];
+            for (rule_name, nested_parser_operator) in nested_grammar.get_all_rules() {
+                collect_references(&nested_parser_operator, nested_grammar.get_rule_parameters(&rule_name), &mut nested_references);
+            }
+            for reference in nested_references {
+                if nested_grammar.resolve_rule(&reference).is_none() {
+                    add(&reference);
+                }
+            }
+        },
+        _ => {},
+    }
+    for child in parser_operator.kind().children() {
+        collect_references(child, parameters, references);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::*;
+
+    #[test]
+    fn dot_has_a_node_per_rule_and_an_edge_per_reference() {
+        let json: Rc<Grammar> = Rc::new(Grammar::new(None, vec![("Value", reg!("[0-9]+"))]));
+        let gram = Grammar::new(
+            Some("Prog"),
+            vec![
+                ("List",  seq!(rul!("Item"), qtt!(seq!(lit!(","), rul!("Item")), 0, None))),
+                ("Prog",  seq!(rul!("List"), rul!("json::Value"), rul!("List"))),
+                ("Item",  alt!(reg!("[a-z]+"), call!("Pair", rul!("Item")))),
+            ]
+        )
+        .with_parameterized_rule("Pair", vec!["X"], seq!(lit!("("), rul!("X"), rul!("X"), lit!(")")))
+        .with_import("json", json);
+        let dot = gram.to_dot();
+        assert_eq!(dot, [
+            "digraph Grammar {",
+            "    node [shape=box];",
+            "    \"List\";",
+            "    \"List\" -> \"Item\";",
+            "    \"Prog\" [style=bold];",
+            "    \"Prog\" -> \"List\";",
+            "    \"Prog\" -> \"json::Value\";",
+            "    \"Item\";",
+            "    \"Item\" -> \"Pair\";",
+            "    \"Item\" -> \"Item\";",
+            "    \"Pair\";",
+            "    subgraph \"cluster_json::\" {",
+            "        label=\"json\";",
+            "        \"json::Value\" [style=bold];",
+            "    }",
+            "}",
+            "",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn dot_includes_inherited_rules() {
+        let base: Rc<Grammar> = Rc::new(Grammar::new(None, vec![("A", rul!("B")), ("B", lit!("b"))]));
+        let derived = Grammar::new(None, vec![("B", rul!("C")), ("C", lit!("c"))]).with_base(base);
+        let dot = derived.to_dot();
+        assert!(dot.contains("\"A\" [style=bold];\n    \"A\" -> \"B\";"));
+        assert!(dot.contains("\"B\" -> \"C\";"));
+        // The base grammar's `B` is overridden, so there is only one node for it
+        assert_eq!(dot.matches("    \"B\";\n").count(), 1);
+    }
+}
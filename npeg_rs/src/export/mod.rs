@@ -0,0 +1,4 @@
+//! Renders grammars for people to review; see `Grammar::to_dot()` and `Grammar::to_railroad_svg()`
+
+mod dot;
+mod railroad;
@@ -0,0 +1,414 @@
+use std::rc::Rc;
+
+use crate::core::{OpKind, Parser};
+use crate::ops::{Grammar, Operator};
+
+// Layout constants, in SVG user units
+const CHARACTER_WIDTH: usize = 8;
+const BOX_PADDING: usize = 10;
+const BOX_HALF_HEIGHT: usize = 11;
+const ARC_RADIUS: usize = 10;
+const GAP: usize = 10;
+const GROUP_LABEL_HEIGHT: usize = 14;
+const MARGIN: usize = 20;
+const RULE_NAME_HEIGHT: usize = 24;
+
+const STYLE: &str = "\
+path { stroke-width: 2; stroke: black; fill: none; }
+rect { stroke-width: 2; stroke: black; fill: #ffffe0; }
+rect.terminal { fill: #e8f4ff; }
+rect.builtin { fill: #e8ffe8; }
+rect.group { stroke-width: 1; stroke-dasharray: 4 3; fill: none; }
+text { font: 13px monospace; text-anchor: middle; dominant-baseline: central; }
+text.rule-name { font-weight: bold; text-anchor: start; }
+text.group-label { font-size: 11px; text-anchor: start; fill: #555; }
+";
+
+impl<S> Grammar<S> {
+    /// Renders each rule as a railroad (syntax) diagram, one below the other, in a single SVG document.
+    ///
+    /// Literals and regular expressions are drawn as rounded boxes, rule references as square boxes,
+    /// and labels, captures, lookaheads and bounded repetitions as dashed boxes around what they apply to.
+    pub fn to_railroad_svg(&self) -> String {
+        let rules: Vec<(Rc<String>, Diagram)> = self
+            .get_all_rules()
+            .into_iter()
+            .map(|(rule_name, parser_operator)| (rule_name, Diagram::from_parser(&parser_operator)))
+            .collect();
+
+        let width = rules
+            .iter()
+            .map(|(rule_name, diagram)| (diagram.width() + 2 * MARGIN + 2 * GAP).max(text_width(rule_name) + 2 * MARGIN))
+            .max()
+            .unwrap_or(0);
+        let mut body = String::new();
+        let mut y = MARGIN;
+        for (rule_name, diagram) in &rules {
+            body.push_str(&format!("<text class=\"rule-name\" x=\"{}\" y=\"{}\">{}</text>\n", MARGIN, y + RULE_NAME_HEIGHT / 2, escape(rule_name)));
+            let baseline = y + RULE_NAME_HEIGHT + diagram.up();
+            // Entry and exit markers
+            body.push_str(&format!(
+                "<path d=\"M{x} {top} v{height} M{x} {baseline} h{gap}\"/>\n",
+                x = MARGIN,
+                top = baseline - BOX_HALF_HEIGHT / 2,
+                height = BOX_HALF_HEIGHT,
+                gap = GAP,
+                baseline = baseline,
+            ));
+            diagram.render(MARGIN + GAP, baseline, &mut body);
+            let end = MARGIN + GAP + diagram.width();
+            body.push_str(&format!(
+                "<path d=\"M{end} {baseline} h{gap} M{x} {top} v{height}\"/>\n",
+                end = end,
+                baseline = baseline,
+                gap = GAP,
+                x = end + GAP,
+                top = baseline - BOX_HALF_HEIGHT / 2,
+                height = BOX_HALF_HEIGHT,
+            ));
+            y = baseline + diagram.down() + MARGIN;
+        }
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\" class=\"railroad\">\n<style>\n{style}</style>\n{body}</svg>\n",
+            width = width,
+            height = y,
+            style = STYLE,
+            body = body,
+        )
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn text_width(text: &str) -> usize {
+    text.chars().count() * CHARACTER_WIDTH
+}
+
+/// The parts of a railroad diagram. Each part is laid out around a horizontal line (the baseline)
+/// which enters on the left and leaves on the right; `up()` and `down()` are its extent above and below that line.
+#[derive(Debug)]
+enum Diagram {
+    Skip,
+    /// Matches text directly; drawn with rounded corners
+    Terminal(String),
+    NonTerminal(String),
+    /// Matches something other than text, e.g. indentation
+    Builtin(String),
+    Sequence(Vec<Diagram>),
+    Choice(Vec<Diagram>),
+    /// One or more times
+    Repeat(Box<Diagram>),
+    Group(Box<Diagram>, String),
+}
+
+impl Diagram {
+    fn from_parser<S>(parser_operator: &Rc<dyn Parser<S>>) -> Self {
+        match parser_operator.kind() {
+            OpKind::Literal(literal_text) => Diagram::Terminal(format!("{:?}", literal_text)),
+            OpKind::Regex { pattern, multi_line, case_insensitive, dot_matches_new_line } => Diagram::Terminal(format!(
+                "/{}/{}{}{}",
+                pattern,
+                if multi_line { "m" } else { "" },
+                if case_insensitive { "i" } else { "" },
+                if dot_matches_new_line { "s" } else { "" },
+            )),
+            OpKind::ByteLiteral(_) | OpKind::ByteClass(_) => Diagram::Terminal(parser_operator.to_peg_string()),
+            OpKind::RuleReference(rule_name) => Diagram::NonTerminal(rule_name.into()),
+            OpKind::RuleCall { rule_name, arguments } => Diagram::NonTerminal(format!(
+                "{}({})",
+                rule_name,
+                arguments.iter().map(summary).collect::<Vec<String>>().join(", ")
+            )),
+            OpKind::Sequence(children) => Diagram::Sequence(children.iter().map(Diagram::from_parser).collect()),
+            OpKind::Alternation(children) => Diagram::Choice(children.iter().map(Diagram::from_parser).collect()),
+            OpKind::Quantity { child, minimum_occurrences, maximum_occurrences } => {
+                let child = Diagram::from_parser(child);
+                match (minimum_occurrences, maximum_occurrences) {
+                    (0, Some(1)) => Diagram::Choice(vec![Diagram::Skip, child]),
+                    (0, None)    => Diagram::Choice(vec![Diagram::Skip, Diagram::Repeat(Box::new(child))]),
+                    (1, None)    => Diagram::Repeat(Box::new(child)),
+                    (minimum_occurrences, maximum_occurrences) => Diagram::Group(
+                        Box::new(Diagram::Repeat(Box::new(child))),
+                        format!("{{{},{}}}", minimum_occurrences, maximum_occurrences.map(|maximum| maximum.to_string()).unwrap_or_default()),
+                    ),
+                }
+            },
+            OpKind::SepBy { element, separator, minimum_occurrences, maximum_occurrences, trailing_separator } => {
+                // An element, then any number of separators each followed by another element
+                let mut list = Diagram::Sequence(vec![
+                    Diagram::from_parser(element),
+                    Diagram::Choice(vec![
+                        Diagram::Skip,
+                        Diagram::Repeat(Box::new(Diagram::Sequence(vec![Diagram::from_parser(separator), Diagram::from_parser(element)]))),
+                    ]),
+                ]);
+                if minimum_occurrences > 1 || maximum_occurrences.is_some() || trailing_separator {
+                    let maximum = maximum_occurrences.map(|maximum| maximum.to_string()).unwrap_or_default();
+                    let trailing = if trailing_separator { ", trailing" } else { "" };
+                    list = Diagram::Group(Box::new(list), format!("{{{},{}}}{}", minimum_occurrences, maximum, trailing));
+                }
+                if minimum_occurrences == 0 {
+                    Diagram::Choice(vec![Diagram::Skip, list])
+                } else {
+                    list
+                }
+            },
+            OpKind::Until { child, terminator } => Diagram::Sequence(vec![
+                Diagram::Choice(vec![Diagram::Skip, Diagram::Repeat(Box::new(Diagram::from_parser(child)))]),
+                Diagram::Group(Box::new(Diagram::from_parser(terminator)), "until".into()),
+            ]),
+            OpKind::Counted { child, name, .. } => Diagram::Group(Box::new(Diagram::Repeat(Box::new(Diagram::from_parser(child)))), format!("{{{}}}", name)),
+            OpKind::Lookahead { child, scout, accept_match } => Diagram::Sequence(vec![
+                Diagram::from_parser(child),
+                Diagram::Group(
+                    Box::new(Diagram::from_parser(scout)),
+                    if accept_match { "followed by".into() } else { "not followed by".into() },
+                ),
+            ]),
+            OpKind::OperatorTable(table) => {
+                // Any of the operators, each drawn like a `Label`, any number of times
+                let zero_or_more = |operators: &[Operator<S>], then: Option<Diagram>| {
+                    if operators.is_empty() {
+                        return Diagram::Skip;
+                    }
+                    let choice = Diagram::Choice(
+                        operators
+                            .iter()
+                            .map(|operator| Diagram::Group(Box::new(Diagram::from_parser(operator.get_parser())), format!("{}:", operator.get_label())))
+                            .collect(),
+                    );
+                    let repeated = match then {
+                        Some(then) => Diagram::Sequence(vec![choice, then]),
+                        None => choice,
+                    };
+                    Diagram::Choice(vec![Diagram::Skip, Diagram::Repeat(Box::new(repeated))])
+                };
+                // Prefix operators, the atom and postfix operators, then any number of infix operators each followed by another operand
+                let operand = || Diagram::Sequence(vec![
+                    zero_or_more(table.get_prefix_operators(), None),
+                    Diagram::from_parser(table.get_atom()),
+                    zero_or_more(table.get_postfix_operators(), None),
+                ]);
+                Diagram::Group(
+                    Box::new(Diagram::Sequence(vec![operand(), zero_or_more(table.get_infix_operators(), Some(operand()))])),
+                    "operators".into(),
+                )
+            },
+            OpKind::Label { child, label } => Diagram::Group(Box::new(Diagram::from_parser(child)), format!("{}:", label)),
+            OpKind::Action { child, .. } => Diagram::Group(Box::new(Diagram::from_parser(child)), "action".into()),
+            OpKind::Predicate { child, description } => Diagram::Group(Box::new(Diagram::from_parser(child)), format!("if {}", description)),
+            OpKind::Capture { child, name } => Diagram::Group(Box::new(Diagram::from_parser(child)), format!("capture {}", name)),
+            OpKind::BackReference(name) => Diagram::Builtin(format!("={}", name)),
+            OpKind::Indent => Diagram::Builtin("INDENT".into()),
+            OpKind::Samedent => Diagram::Builtin("SAMEDENT".into()),
+            OpKind::Dedent => Diagram::Builtin("DEDENT".into()),
+            OpKind::AnyGrapheme => Diagram::Builtin("GRAPHEME".into()),
+            OpKind::Integer { .. } | OpKind::Take { .. } => Diagram::Builtin(parser_operator.to_peg_string()),
+            OpKind::Grammar(grammar) => Diagram::Group(
+                Box::new(Diagram::NonTerminal(grammar.get_starting_rule_name().map(|rule_name| rule_name.to_string()).unwrap_or_default())),
+                "grammar".into(),
+            ),
+            OpKind::Other => Diagram::NonTerminal("?".into()),
+        }
+    }
+
+    fn width(&self) -> usize {
+        match self {
+            Diagram::Skip => 0,
+            Diagram::Terminal(text) | Diagram::NonTerminal(text) | Diagram::Builtin(text) => text_width(text) + 2 * BOX_PADDING,
+            Diagram::Sequence(children) => children.iter().map(Diagram::width).sum::<usize>() + GAP * (children.len().max(1) - 1),
+            Diagram::Choice(children) => children.iter().map(Diagram::width).max().unwrap_or(0) + 4 * ARC_RADIUS,
+            Diagram::Repeat(child) => child.width() + 4 * ARC_RADIUS,
+            Diagram::Group(child, label) => child.width().max(text_width(label)) + 2 * GAP,
+        }
+    }
+
+    fn up(&self) -> usize {
+        match self {
+            Diagram::Skip => 0,
+            Diagram::Terminal(_) | Diagram::NonTerminal(_) | Diagram::Builtin(_) => BOX_HALF_HEIGHT,
+            Diagram::Sequence(children) => children.iter().map(Diagram::up).max().unwrap_or(0),
+            Diagram::Choice(children) => children.first().map_or(0, Diagram::up),
+            Diagram::Repeat(child) => child.up(),
+            Diagram::Group(child, _) => child.up() + GAP + GROUP_LABEL_HEIGHT,
+        }
+    }
+
+    fn down(&self) -> usize {
+        match self {
+            Diagram::Skip => 0,
+            Diagram::Terminal(_) | Diagram::NonTerminal(_) | Diagram::Builtin(_) => BOX_HALF_HEIGHT,
+            Diagram::Sequence(children) => children.iter().map(Diagram::down).max().unwrap_or(0),
+            Diagram::Choice(children) => {
+                let first_down = children.first().map_or(0, Diagram::down);
+                let branch_baselines = self.branch_offsets();
+                branch_baselines.last().map_or(first_down, |offset| offset + children.last().map_or(0, Diagram::down))
+            },
+            Diagram::Repeat(child) => self.loop_offset(child),
+            Diagram::Group(child, _) => child.down() + GAP,
+        }
+    }
+
+    /// For a `Choice`, how far below the baseline each branch's own baseline is
+    fn branch_offsets(&self) -> Vec<usize> {
+        let Diagram::Choice(children) = self else {
+            return vec![];
+        };
+        let mut offsets = vec![0];
+        for (index, child) in children.iter().enumerate().skip(1) {
+            let previous = offsets[index - 1] + children[index - 1].down();
+            offsets.push((previous + GAP + child.up()).max(offsets[index - 1] + 2 * ARC_RADIUS));
+        }
+        offsets
+    }
+
+    /// For a `Repeat`, how far below the baseline the line looping back is
+    fn loop_offset(&self, child: &Diagram) -> usize {
+        (child.down() + GAP).max(2 * ARC_RADIUS)
+    }
+
+    /// Draws the diagram with its baseline entering at `(x, y)`
+    fn render(&self, x: usize, y: usize, svg: &mut String) {
+        match self {
+            Diagram::Skip => {},
+            Diagram::Terminal(text) | Diagram::NonTerminal(text) | Diagram::Builtin(text) => {
+                let (class, radius) = match self {
+                    Diagram::Terminal(_) => ("terminal", BOX_HALF_HEIGHT),
+                    Diagram::Builtin(_) => ("builtin", BOX_HALF_HEIGHT / 2),
+                    _ => ("nonterminal", 0),
+                };
+                let width = self.width();
+                svg.push_str(&format!(
+                    "<rect class=\"{}\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"{}\"/>\n<text x=\"{}\" y=\"{}\">{}</text>\n",
+                    class,
+                    x,
+                    y - BOX_HALF_HEIGHT,
+                    width,
+                    2 * BOX_HALF_HEIGHT,
+                    radius,
+                    x + width / 2,
+                    y,
+                    escape(text),
+                ));
+            },
+            Diagram::Sequence(children) => {
+                let mut child_x = x;
+                for (index, child) in children.iter().enumerate() {
+                    if index > 0 {
+                        svg.push_str(&format!("<path d=\"M{} {} h{}\"/>\n", child_x, y, GAP));
+                        child_x += GAP;
+                    }
+                    child.render(child_x, y, svg);
+                    child_x += child.width();
+                }
+            },
+            Diagram::Choice(children) => {
+                let width = self.width();
+                let right = x + width;
+                let r = ARC_RADIUS;
+                for (child, offset) in children.iter().zip(self.branch_offsets()) {
+                    let branch_y = y + offset;
+                    let child_x = x + 2 * r;
+                    if offset == 0 {
+                        svg.push_str(&format!("<path d=\"M{} {} h{}\"/>\n", x, y, 2 * r));
+                    } else {
+                        svg.push_str(&format!(
+                            "<path d=\"M{x} {y} Q{x1} {y} {x1} {y1} V{y2} Q{x1} {by} {x2} {by}\"/>\n",
+                            x = x, y = y, x1 = x + r, y1 = y + r, y2 = branch_y - r, by = branch_y, x2 = child_x,
+                        ));
+                    }
+                    child.render(child_x, branch_y, svg);
+                    let child_end = child_x + child.width();
+                    if offset == 0 {
+                        svg.push_str(&format!("<path d=\"M{} {} H{}\"/>\n", child_end, y, right));
+                    } else {
+                        svg.push_str(&format!(
+                            "<path d=\"M{ce} {by} H{x2} Q{x1} {by} {x1} {y2} V{y1} Q{x1} {y} {right} {y}\"/>\n",
+                            ce = child_end, by = branch_y, x2 = right - 2 * r, x1 = right - r, y2 = branch_y - r, y1 = y + r, y = y, right = right,
+                        ));
+                    }
+                }
+            },
+            Diagram::Repeat(child) => {
+                let width = self.width();
+                let right = x + width;
+                let r = ARC_RADIUS;
+                let loop_y = y + self.loop_offset(child);
+                svg.push_str(&format!("<path d=\"M{} {} h{}\"/>\n", x, y, 2 * r));
+                child.render(x + 2 * r, y, svg);
+                svg.push_str(&format!("<path d=\"M{} {} H{}\"/>\n", x + 2 * r + child.width(), y, right));
+                svg.push_str(&format!(
+                    "<path d=\"M{a} {y} Q{b} {y} {b} {y1} V{y2} Q{b} {ly} {a} {ly} H{c} Q{d} {ly} {d} {y2} V{y1} Q{d} {y} {c} {y}\"/>\n",
+                    a = right - 2 * r, b = right - r, c = x + 2 * r, d = x + r, y = y, y1 = y + r, y2 = loop_y - r, ly = loop_y,
+                ));
+            },
+            Diagram::Group(child, label) => {
+                let width = self.width();
+                svg.push_str(&format!(
+                    "<rect class=\"group\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"{}\"/>\n<text class=\"group-label\" x=\"{}\" y=\"{}\">{}</text>\n",
+                    x,
+                    y - self.up(),
+                    width,
+                    self.up() + self.down(),
+                    ARC_RADIUS / 2,
+                    x + GAP / 2,
+                    y - self.up() + GROUP_LABEL_HEIGHT / 2 + 2,
+                    escape(label),
+                ));
+                svg.push_str(&format!("<path d=\"M{} {} h{}\"/>\n", x, y, GAP));
+                child.render(x + GAP, y, svg);
+                svg.push_str(&format!("<path d=\"M{} {} H{}\"/>\n", x + GAP + child.width(), y, x + width));
+            },
+        }
+    }
+}
+
+/// A short description of a `RuleCall` argument
+fn summary<S>(parser_operator: &Rc<dyn Parser<S>>) -> String {
+    match parser_operator.kind() {
+        OpKind::RuleReference(rule_name) => rule_name.into(),
+        OpKind::Literal(literal_text) => format!("{:?}", literal_text),
+        _ => "…".into(),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::*;
+
+    #[test]
+    fn railroad_svg_draws_every_rule() {
+        let gram: Grammar = Grammar::new(
+            None,
+            vec![
+                ("Tag",   seq!(lit!("<"), cap!(reg!("[a-z]+" i), "name"), qtt!(rul!("Attribute"), 0, None), lit!(">"))),
+                ("Attribute", alt!(lbl!(reg!("[a-z]+"), "key"), qtt!(lit!("&"), 2, Some(4)))),
+            ]
+        );
+        let svg = gram.to_railroad_svg();
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.ends_with("</svg>\n"));
+        assert!(svg.contains(">Tag</text>"));
+        assert!(svg.contains(">Attribute</text>"));
+        assert!(svg.contains(">&quot;&lt;&quot;</text>"));
+        assert!(svg.contains(">/[a-z]+/i</text>"));
+        assert!(svg.contains(">capture name</text>"));
+        assert!(svg.contains(">{2,4}</text>"));
+        assert!(svg.contains(">&quot;&amp;&quot;</text>"));
+        // Every element is closed
+        assert_eq!(svg.matches("<rect").count() + svg.matches("<path").count(), svg.matches("/>").count());
+        assert_eq!(svg.matches("<text").count(), svg.matches("</text>").count());
+    }
+
+    #[test]
+    fn railroad_svg_size_grows_with_rules() {
+        let one = Grammar::<()>::new(None, vec![("A", lit!("a"))]).to_railroad_svg();
+        let two = Rc::new(Grammar::<()>::new(None, vec![("A", lit!("a")), ("B", alt!(lit!("a"), lit!("b"), lit!("c")))])).to_railroad_svg();
+        let height = |svg: &str| svg.split("height=\"").nth(1).unwrap().split('"').next().unwrap().parse::<usize>().unwrap();
+        assert!(height(&two) > height(&one));
+    }
+}
@@ -0,0 +1,602 @@
+//! Random sentences from a grammar, for fuzzing; see `Generator`
+//!
+//! Sentences are generated by walking the op tree, choosing alternatives and repetition counts at random and sampling
+//! a string for each `Regex`. Lookaheads, regex anchors and back references are not taken into account while
+//! generating, so every sentence is parsed afterwards and discarded if it does not match.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+
+use regex_syntax::hir::Hir;
+
+use crate::core::{OpKind, Parser, ParserContext};
+use crate::ops::{Grammar, RuleReference};
+
+mod regex_sampler;
+mod rng;
+#[cfg(feature = "proptest")]
+mod strategy;
+
+use self::rng::Rng;
+#[cfg(feature = "proptest")]
+pub use self::strategy::{GrammarStrategy, GrammarValueTree};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GenerateError {
+    /// The op can not be generated; `op` is the op written as PEG text
+    Unsupported { rule_name: String, op: String },
+    /// A `RuleReference` names a rule which does not exist where it is used
+    RuleNotFound { rule_name: String, reference: String },
+    /// The starting rule, or a rule used by a `RuleReference`, takes parameters
+    ParameterizedRule { rule_name: String },
+    InvalidRegex { rule_name: String, pattern: String, message: String },
+    /// A grammar has no rules
+    EmptyGrammar,
+    /// The rule can not be finished without nesting rules deeper than the max depth
+    TooDeep { rule_name: String },
+    /// Every sentence generated failed to parse
+    NoValidSentence { attempts: usize },
+    /// Every mutation of the sentence still parsed
+    NoInvalidMutation { attempts: usize },
+}
+
+impl fmt::Display for GenerateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GenerateError::Unsupported { rule_name, op } => write!(f, "Rule {} uses {}, which can not be generated", rule_name, op),
+            GenerateError::RuleNotFound { rule_name, reference } => write!(f, "Rule {} references {}, which was not found", rule_name, reference),
+            GenerateError::ParameterizedRule { rule_name } => write!(f, "Rule {} takes parameters, which can not be generated", rule_name),
+            GenerateError::InvalidRegex { rule_name, pattern, message } => write!(f, "Rule {} has an invalid regular expression {}: {}", rule_name, pattern, message),
+            GenerateError::EmptyGrammar => write!(f, "Grammar has no rules"),
+            GenerateError::TooDeep { rule_name } => write!(f, "Rule {} can not be finished within the max depth", rule_name),
+            GenerateError::NoValidSentence { attempts } => write!(f, "None of {} generated sentences parsed", attempts),
+            GenerateError::NoInvalidMutation { attempts } => write!(f, "All of {} mutated sentences still parsed", attempts),
+        }
+    }
+}
+
+impl Error for GenerateError {}
+
+/// A grammar on the stack used to resolve rule references, like the stack of rule sets in `ParserContext`.
+/// Nested grammars are borrowed from the op tree, while imported grammars are shared.
+enum RuleSet<'a, S> {
+    Borrowed(&'a Grammar<S>),
+    Shared(Rc<Grammar<S>>),
+}
+
+// Not derived, since that would require `S: Clone`
+impl<S> Clone for RuleSet<'_, S> {
+    fn clone(&self) -> Self {
+        match self {
+            RuleSet::Borrowed(grammar) => RuleSet::Borrowed(grammar),
+            RuleSet::Shared(grammar) => RuleSet::Shared(grammar.clone()),
+        }
+    }
+}
+
+impl<S> RuleSet<'_, S> {
+    fn grammar(&self) -> &Grammar<S> {
+        match self {
+            RuleSet::Borrowed(grammar) => grammar,
+            RuleSet::Shared(grammar) => grammar,
+        }
+    }
+}
+
+/// Whether an op can be finished within a number of rule levels, for each stack of rule sets it is used with
+type FitsKey = (Vec<usize>, usize, usize);
+
+/// Characters inserted by mutations, besides those already in the sentence
+const MUTATION_CHARACTERS: &str = "()[]{}<>\"'\\,;:.+-*/=_ \t\n0a";
+
+/// `Generator`
+///
+/// Generates random sentences which parse with a `Grammar`, and near misses which do not.
+/// The same seed always gives the same sentences.
+///
+/// Rules may be nested at most `max_depth` deep; near the limit only the alternatives and repetition counts which
+/// can still be finished in time are chosen, so recursive grammars terminate. A `Quantity` repeats less the deeper it is.
+/// Ops which depend on the indentation, `RuleCall` and parameterized rules are not supported.
+/// For a grammar with user state, use `with_user_state()`; predicates and actions are only run when each sentence is parsed.
+///
+/// ```
+/// # use std::rc::Rc;
+/// # use npeg_rs::*;
+/// let gram = Rc::new(Grammar::new(None, vec![
+///     ("Sum",    seq!(rul!("Number"), qtt!(seq!(lit!("+"), rul!("Number")), 0, None))),
+///     ("Number", reg!("[0-9]+")),
+/// ]));
+/// let mut generator = Generator::new(gram.clone()).with_seed(7);
+/// let sentence = generator.generate().unwrap();
+/// let mut context = Box::new(ParserContext::new(&sentence));
+/// let result = gram.parse(&mut context, 0).unwrap();
+/// assert_eq!(result.len(), sentence.len());
+/// ```
+pub struct Generator<S = ()> {
+    grammar: Rc<Grammar<S>>,
+    /// The user state each sentence is parsed with
+    user_state: S,
+    rng: Rng,
+    max_depth: usize,
+    max_repetitions: usize,
+    max_attempts: usize,
+    regexes: HashMap<(String, bool, bool, bool), Rc<Hir>>,
+    fits: HashMap<FitsKey, bool>,
+    /// The text generated for each `Capture` in the current sentence, for `BackReference`
+    captures: HashMap<String, String>,
+    /// The rule being generated, for error messages
+    rule_name: String,
+}
+
+impl Generator {
+    pub fn new(grammar: Rc<Grammar>) -> Generator {
+        Generator::with_user_state(grammar, ())
+    }
+}
+
+impl<S: Clone> Generator<S> {
+    /// Generates sentences for a grammar with user state, checking each by parsing it with `user_state` to start with
+    pub fn with_user_state(grammar: Rc<Grammar<S>>, user_state: S) -> Generator<S> {
+        Generator {
+            grammar,
+            user_state,
+            rng: Rng::new(0),
+            max_depth: 12,
+            max_repetitions: 3,
+            max_attempts: 100,
+            regexes: HashMap::new(),
+            fits: HashMap::new(),
+            captures: HashMap::new(),
+            rule_name: String::new(),
+        }
+    }
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = Rng::new(seed);
+        self
+    }
+    /// Limits how deeply rules are nested in a sentence; the default is 12
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self.fits.clear();
+        self
+    }
+    /// Limits how many more times than required an unbounded `Quantity` or regex repetition repeats; the default is 3.
+    /// The limit for a `Quantity` falls to zero towards the max depth.
+    pub fn with_max_repetitions(mut self, max_repetitions: usize) -> Self {
+        self.max_repetitions = max_repetitions;
+        self
+    }
+    /// Limits how many sentences, or mutations, are tried before giving up; the default is 100
+    pub fn with_max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+    /// A sentence which the grammar matches completely
+    pub fn generate(&mut self) -> Result<String, GenerateError> {
+        self.generate_valid(None)
+    }
+    /// A sentence which the named rule matches completely
+    pub fn generate_rule(&mut self, rule_name: &str) -> Result<String, GenerateError> {
+        self.generate_valid(Some(rule_name))
+    }
+    /// A near miss: a valid sentence with one small random edit, which the grammar does not match completely
+    pub fn generate_invalid(&mut self) -> Result<String, GenerateError> {
+        let sentence = self.generate()?;
+        self.mutate(&sentence)
+    }
+    /// Applies small random edits to `text` until the grammar no longer matches it completely.
+    /// Each attempt starts again from `text` and makes one edit: deleting, inserting, replacing, duplicating,
+    /// swapping or truncating characters.
+    pub fn mutate(&mut self, text: &str) -> Result<String, GenerateError> {
+        let characters: Vec<char> = text.chars().collect();
+        for _ in 0..self.max_attempts {
+            let mutated: String = self.mutate_once(&characters).into_iter().collect();
+            if !self.parses(None, &mutated) {
+                return Ok(mutated);
+            }
+        }
+        Err(GenerateError::NoInvalidMutation { attempts: self.max_attempts })
+    }
+
+    fn generate_valid(&mut self, rule_name: Option<&str>) -> Result<String, GenerateError> {
+        for _ in 0..self.max_attempts {
+            let sentence = self.generate_sentence(rule_name)?;
+            if self.parses(rule_name, &sentence) {
+                return Ok(sentence);
+            }
+        }
+        Err(GenerateError::NoValidSentence { attempts: self.max_attempts })
+    }
+
+    fn generate_sentence(&mut self, rule_name: Option<&str>) -> Result<String, GenerateError> {
+        self.captures.clear();
+        let grammar = self.grammar.clone();
+        let mut out = String::new();
+        match rule_name {
+            None => self.generate_op(&[], grammar.as_ref(), self.max_depth + 1, &mut out)?,
+            Some(rule_name) => {
+                let reference = RuleReference::new(rule_name);
+                self.generate_op(&[RuleSet::Shared(grammar)], &reference, self.max_depth + 1, &mut out)?
+            },
+        }
+        Ok(out)
+    }
+
+    /// Whether the grammar, or the named rule, matches all of `text`
+    fn parses(&self, rule_name: Option<&str>, text: &str) -> bool {
+        let mut context = Box::new(ParserContext::new(text).with_user_state(self.user_state.clone()));
+        let result = match rule_name {
+            None => self.grammar.clone().parse_match(&mut context, 0),
+            Some(rule_name) => {
+                context.push_rule_set(self.grammar.clone());
+                Rc::new(RuleReference::new(rule_name)).parse_match(&mut context, 0)
+            },
+        };
+        result.is_some_and(|result| context.get_match(result).len() == text.len())
+    }
+
+    fn mutate_once(&mut self, characters: &[char]) -> Vec<char> {
+        let mut characters = characters.to_vec();
+        let length = characters.len();
+        let inserted = if length > 0 && self.rng.below(2) == 0 {
+            characters[self.rng.below(length)]
+        } else {
+            let mutation_characters: Vec<char> = MUTATION_CHARACTERS.chars().collect();
+            mutation_characters[self.rng.below(mutation_characters.len())]
+        };
+        if length == 0 {
+            characters.push(inserted);
+            return characters;
+        }
+        let index = self.rng.below(length);
+        match self.rng.below(6) {
+            0 => {
+                characters.remove(index);
+            },
+            1 => characters.insert(self.rng.below(length + 1), inserted),
+            2 => characters[index] = inserted,
+            3 => {
+                let end = self.rng.between(index + 1, length);
+                let duplicate: Vec<char> = characters[index..end].to_vec();
+                characters.splice(end..end, duplicate);
+            },
+            4 if length > 1 => characters.swap(index.min(length - 2), index.min(length - 2) + 1),
+            _ => characters.truncate(index),
+        }
+        characters
+    }
+
+    fn regex(&mut self, pattern: &str, multi_line: bool, case_insensitive: bool, dot_matches_new_line: bool) -> Result<Rc<Hir>, GenerateError> {
+        let key = (pattern.to_owned(), multi_line, case_insensitive, dot_matches_new_line);
+        if let Some(hir) = self.regexes.get(&key) {
+            return Ok(hir.clone());
+        }
+        let hir = regex_sampler::parse_regex(pattern, multi_line, case_insensitive, dot_matches_new_line)
+            .map(Rc::new)
+            .map_err(|message| GenerateError::InvalidRegex {
+                rule_name: self.rule_name.clone(),
+                pattern: pattern.to_owned(),
+                message,
+            })?;
+        self.regexes.insert(key, hir.clone());
+        Ok(hir)
+    }
+
+    /// Appends a sentence for `op`, nesting rules at most `budget` deep
+    fn generate_op(&mut self, stack: &[RuleSet<'_, S>], op: &dyn Parser<S>, budget: usize, out: &mut String) -> Result<(), GenerateError> {
+        match op.kind() {
+            OpKind::Literal(literal_text) => out.push_str(literal_text),
+            OpKind::Regex { pattern, multi_line, case_insensitive, dot_matches_new_line } => {
+                let hir = self.regex(pattern, multi_line, case_insensitive, dot_matches_new_line)?;
+                regex_sampler::sample(&hir, &mut self.rng, self.max_repetitions, out);
+            },
+            OpKind::Sequence(children) => {
+                for child in children {
+                    self.generate_op(stack, child.as_ref(), budget, out)?;
+                }
+            },
+            OpKind::Alternation(children) => {
+                let fitting: Vec<&Rc<dyn Parser<S>>> = children.iter().filter(|child| self.fits(stack, child.as_ref(), budget)).collect();
+                if fitting.is_empty() {
+                    return Err(GenerateError::TooDeep { rule_name: self.rule_name.clone() });
+                }
+                let child = fitting[self.rng.below(fitting.len())].clone();
+                self.generate_op(stack, child.as_ref(), budget, out)?;
+            },
+            OpKind::Quantity { child, minimum_occurrences, maximum_occurrences } => {
+                let count = if self.fits(stack, child.as_ref(), budget) {
+                    // Fewer repetitions deeper down keep sentences from growing exponentially with the max depth
+                    let max_repetitions = self.max_repetitions * budget / (self.max_depth + 1);
+                    let maximum_occurrences = maximum_occurrences.unwrap_or(usize::MAX).min(minimum_occurrences.saturating_add(max_repetitions));
+                    self.rng.between(minimum_occurrences, maximum_occurrences)
+                } else {
+                    minimum_occurrences
+                };
+                for _ in 0..count {
+                    self.generate_op(stack, child.as_ref(), budget, out)?;
+                }
+            },
+            OpKind::SepBy { element, separator, minimum_occurrences, maximum_occurrences, trailing_separator } => {
+                let count = if self.fits(stack, element.as_ref(), budget) && self.fits(stack, separator.as_ref(), budget) {
+                    let max_repetitions = self.max_repetitions * budget / (self.max_depth + 1);
+                    let maximum_occurrences = maximum_occurrences.unwrap_or(usize::MAX).min(minimum_occurrences.saturating_add(max_repetitions));
+                    self.rng.between(minimum_occurrences, maximum_occurrences)
+                } else {
+                    minimum_occurrences
+                };
+                for index in 0..count {
+                    if index > 0 {
+                        self.generate_op(stack, separator.as_ref(), budget, out)?;
+                    }
+                    self.generate_op(stack, element.as_ref(), budget, out)?;
+                }
+                if trailing_separator && count > 0 && self.rng.below(2) == 0 {
+                    self.generate_op(stack, separator.as_ref(), budget, out)?;
+                }
+            },
+            // The terminator is only checked against the children when the sentence is parsed
+            OpKind::Until { child, terminator } => {
+                let count = if self.fits(stack, child.as_ref(), budget) {
+                    self.rng.between(0, self.max_repetitions * budget / (self.max_depth + 1))
+                } else {
+                    0
+                };
+                for _ in 0..count {
+                    self.generate_op(stack, child.as_ref(), budget, out)?;
+                }
+                self.generate_op(stack, terminator.as_ref(), budget, out)?;
+            },
+            // The count is taken from the text already generated for the capture
+            OpKind::Counted { child, name, endianness: None } => {
+                let count = self.captures.get(name).and_then(|captured_text| captured_text.parse::<usize>().ok()).ok_or_else(|| GenerateError::Unsupported {
+                    rule_name: self.rule_name.clone(),
+                    op: op.to_peg_string(),
+                })?;
+                for _ in 0..count {
+                    self.generate_op(stack, child.as_ref(), budget, out)?;
+                }
+            },
+            // The scout and the check of a predicate are only checked when the sentence is parsed
+            OpKind::Action { child, .. } | OpKind::Lookahead { child, .. } | OpKind::Label { child, .. } | OpKind::Predicate { child, .. } => {
+                self.generate_op(stack, child.as_ref(), budget, out)?
+            },
+            OpKind::Capture { child, name } => {
+                let start = out.len();
+                self.generate_op(stack, child.as_ref(), budget, out)?;
+                self.captures.insert(name.to_owned(), out[start..].to_owned());
+            },
+            OpKind::BackReference(name) => {
+                if let Some(captured_text) = self.captures.get(name) {
+                    out.push_str(captured_text);
+                }
+            },
+            OpKind::RuleReference(rule_name) => {
+                let (resolved, rule_sets) = resolve(stack, rule_name).ok_or_else(|| GenerateError::RuleNotFound {
+                    rule_name: self.rule_name.clone(),
+                    reference: rule_name.to_owned(),
+                })?;
+                if !resolved.parameters.is_empty() {
+                    return Err(GenerateError::ParameterizedRule { rule_name: rule_name.to_owned() });
+                }
+                self.generate_rule_body(&rule_sets, rule_name, resolved.parser_operator.as_ref(), budget, out)?;
+            },
+            OpKind::Grammar(grammar) => {
+                let mut rule_sets = stack.to_vec();
+                rule_sets.push(RuleSet::Borrowed(grammar));
+                let (rule_name, parser_operator) = grammar.get_starting_rule().ok_or(GenerateError::EmptyGrammar)?;
+                if !grammar.get_rule_parameters(&rule_name).is_empty() {
+                    return Err(GenerateError::ParameterizedRule { rule_name: rule_name.to_string() });
+                }
+                self.generate_rule_body(&rule_sets, &rule_name, parser_operator.as_ref(), budget, out)?;
+            },
+            OpKind::AnyGrapheme
+            | OpKind::ByteClass(_)
+            | OpKind::ByteLiteral(_)
+            | OpKind::Counted { .. }
+            | OpKind::Dedent
+            | OpKind::Indent
+            | OpKind::Integer { .. }
+            | OpKind::OperatorTable(_)
+            | OpKind::RuleCall { .. }
+            | OpKind::Samedent
+            | OpKind::Take { .. }
+            | OpKind::Other => {
+                return Err(GenerateError::Unsupported {
+                    rule_name: self.rule_name.clone(),
+                    op: op.to_peg_string(),
+                })
+            },
+        }
+        Ok(())
+    }
+
+    fn generate_rule_body(&mut self, stack: &[RuleSet<'_, S>], rule_name: &str, parser_operator: &dyn Parser<S>, budget: usize, out: &mut String) -> Result<(), GenerateError> {
+        if budget == 0 {
+            return Err(GenerateError::TooDeep { rule_name: rule_name.to_owned() });
+        }
+        let previous_rule_name = std::mem::replace(&mut self.rule_name, rule_name.to_owned());
+        self.generate_op(stack, parser_operator, budget - 1, out)?;
+        self.rule_name = previous_rule_name;
+        Ok(())
+    }
+
+    /// Whether `op` can be generated nesting rules at most `budget` deep.
+    /// Ops which can not be generated at all are said to fit, so that generating them reports the error.
+    fn fits(&mut self, stack: &[RuleSet<'_, S>], op: &dyn Parser<S>, budget: usize) -> bool {
+        let key = (stack.iter().map(|rule_set| rule_set.grammar().get_id()).collect(), op.get_id(), budget);
+        if let Some(fits) = self.fits.get(&key) {
+            return *fits;
+        }
+        let fits = match op.kind() {
+            OpKind::Sequence(children) => children.iter().all(|child| self.fits(stack, child.as_ref(), budget)),
+            OpKind::Alternation(children) => children.iter().any(|child| self.fits(stack, child.as_ref(), budget)),
+            OpKind::Quantity { child, minimum_occurrences, .. } => minimum_occurrences == 0 || self.fits(stack, child.as_ref(), budget),
+            OpKind::SepBy { element, separator, minimum_occurrences, .. } => {
+                minimum_occurrences == 0 || (self.fits(stack, element.as_ref(), budget) && (minimum_occurrences == 1 || self.fits(stack, separator.as_ref(), budget)))
+            },
+            OpKind::Until { terminator, .. } => self.fits(stack, terminator.as_ref(), budget),
+            OpKind::Action { child, .. }
+            | OpKind::Capture { child, .. }
+            | OpKind::Label { child, .. }
+            | OpKind::Lookahead { child, .. }
+            | OpKind::Predicate { child, .. } => {
+                self.fits(stack, child.as_ref(), budget)
+            },
+            OpKind::RuleReference(rule_name) => match resolve(stack, rule_name) {
+                Some((resolved, rule_sets)) => budget > 0 && self.fits(&rule_sets, resolved.parser_operator.as_ref(), budget - 1),
+                None => true,
+            },
+            OpKind::Grammar(grammar) => match grammar.get_starting_rule() {
+                Some((_, parser_operator)) => {
+                    let mut rule_sets = stack.to_vec();
+                    rule_sets.push(RuleSet::Borrowed(grammar));
+                    budget > 0 && self.fits(&rule_sets, parser_operator.as_ref(), budget - 1)
+                },
+                None => true,
+            },
+            _ => true,
+        };
+        self.fits.insert(key, fits);
+        fits
+    }
+}
+
+/// Looks up a rule the way `ParserContext::resolve_rule` does, returning it with the stack of rule sets it is used with
+fn resolve<'a, S>(stack: &[RuleSet<'a, S>], rule_name: &str) -> Option<(crate::ops::ResolvedRule<S>, Vec<RuleSet<'a, S>>)> {
+    stack.iter().enumerate().rev().find_map(|(index, rule_set)| {
+        rule_set.grammar().resolve_rule(rule_name).map(|mut resolved| {
+            let rule_sets = match resolved.rule_sets.take() {
+                Some(rule_sets) => rule_sets.into_iter().map(RuleSet::Shared).collect(),
+                None => stack[..=index].to_vec(),
+            };
+            (resolved, rule_sets)
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::*;
+
+    fn arithmetic() -> Rc<Grammar> {
+        Rc::new(Grammar::new(
+            None,
+            vec![
+                ("Expr",   seq!(rul!("Term"), qtt!(seq!(reg!(r"\s*[+-]\s*"), rul!("Term")), 0, None))),
+                ("Term",   seq!(rul!("Factor"), qtt!(seq!(alt!(lit!("*"), lit!("/")), rul!("Factor")), 0, None))),
+                ("Factor", alt!(seq!(lit!("("), rul!("Expr"), lit!(")")), rul!("Number"))),
+                ("Number", reg!(r"-?[0-9]+(\.[0-9]{1,3})?")),
+            ]
+        ))
+    }
+
+    fn parses(gram: &Rc<Grammar>, text: &str) -> bool {
+        let mut context = Box::new(ParserContext::new(text));
+        gram.clone().parse(&mut context, 0).is_some_and(|result| result.len() == text.len())
+    }
+
+    #[test]
+    fn generated_sentences_parse() {
+        let gram = arithmetic();
+        let mut generator = Generator::new(gram.clone()).with_seed(1);
+        let sentences: Vec<String> = (0..50).map(|_| generator.generate().unwrap()).collect();
+        assert!(sentences.iter().all(|sentence| parses(&gram, sentence)));
+        assert!(sentences.iter().any(|sentence| sentence.contains('(')));
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_sentences() {
+        let mut first = Generator::new(arithmetic()).with_seed(42);
+        let mut second = Generator::new(arithmetic()).with_seed(42);
+        for _ in 0..10 {
+            assert_eq!(first.generate().unwrap(), second.generate().unwrap());
+        }
+        let mut third = Generator::new(arithmetic()).with_seed(43);
+        assert!((0..10).any(|_| first.generate().unwrap() != third.generate().unwrap()));
+    }
+
+    #[test]
+    fn max_depth_limits_nesting() {
+        let mut generator = Generator::new(arithmetic()).with_seed(3).with_max_depth(5);
+        for _ in 0..50 {
+            // Each level of parentheses uses three rules
+            assert!(!generator.generate().unwrap().contains("(("));
+        }
+        let unfinishable = Rc::new(Grammar::new(None, vec![("Loop", seq!(lit!("x"), rul!("Loop")))]));
+        let mut generator = Generator::new(unfinishable);
+        assert_eq!(generator.generate(), Err(GenerateError::TooDeep { rule_name: "Loop".to_owned() }));
+    }
+
+    #[test]
+    fn regexes_are_sampled() {
+        let gram = Rc::new(Grammar::new(None, vec![("Word", reg!(r"[a-f]{2,4}_\d+|x(?:yz)?" i))]));
+        let regex = regex::Regex::new(r"(?i)^(?:[a-f]{2,4}_\d+|x(?:yz)?)$").unwrap();
+        let mut generator = Generator::new(gram).with_seed(5).with_max_attempts(1);
+        for _ in 0..50 {
+            assert!(regex.is_match(&generator.generate().unwrap()));
+        }
+    }
+
+    #[test]
+    fn rules_lookaheads_and_back_references() {
+        let gram = Rc::new(Grammar::new(
+            None,
+            vec![
+                ("Tag",  seq!(lit!("<"), cap!(rul!("Name"), "name"), lit!(">"), lit!("</"), brf!("name"), lit!(">"))),
+                ("Name", Rc::new(Lookahead::new(reg!("[a-z]+"), lit!("!"), false))),
+            ]
+        ));
+        let mut generator = Generator::new(gram.clone()).with_seed(9);
+        let tag = generator.generate().unwrap();
+        assert!(parses(&gram, &tag));
+        let name = generator.generate_rule("Name").unwrap();
+        assert!(name.chars().all(|each| each.is_ascii_lowercase()) && !name.is_empty());
+        assert!(matches!(generator.generate_rule("Missing"), Err(GenerateError::RuleNotFound { .. })));
+    }
+
+    #[test]
+    fn mutations_do_not_parse() {
+        let gram = arithmetic();
+        let mut generator = Generator::new(gram.clone()).with_seed(11);
+        for _ in 0..20 {
+            let invalid = generator.generate_invalid().unwrap();
+            assert!(!parses(&gram, &invalid));
+        }
+        let anything = Rc::new(Grammar::new(None, vec![("Any", reg!("(?s).*"))]));
+        let mut generator = Generator::new(anything).with_max_attempts(5);
+        assert_eq!(generator.mutate("abc"), Err(GenerateError::NoInvalidMutation { attempts: 5 }));
+    }
+
+    #[test]
+    fn list_ops_are_generated() {
+        let gram = Rc::new(Grammar::new(
+            None,
+            vec![
+                ("Lists", seq!(
+                    cap!(reg!("[0-3]"), "n"),
+                    lit!(":"),
+                    cnt!(lit!("x"), "n"),
+                    lit!(" "),
+                    Rc::new(SepBy::new(reg!("[a-z]+"), lit!(",")).with_occurrences(1, Some(3)).with_trailing_separator(true)),
+                    lit!(" "),
+                    unt!(reg!("[a-z ]"), lit!(";"))
+                )),
+            ]
+        ));
+        let mut generator = Generator::new(gram.clone()).with_seed(13);
+        let sentences: Vec<String> = (0..50).map(|_| generator.generate().unwrap()).collect();
+        assert!(sentences.iter().all(|sentence| parses(&gram, sentence)));
+        assert!(sentences.iter().any(|sentence| sentence.contains(',')));
+    }
+
+    #[test]
+    fn unsupported_ops_are_errors() {
+        let gram = Rc::new(Grammar::new(None, vec![("Block", seq!(lit!(":"), idt!()))]));
+        let mut generator = Generator::new(gram);
+        assert_eq!(generator.generate(), Err(GenerateError::Unsupported { rule_name: "Block".to_owned(), op: "@indent".to_owned() }));
+    }
+}
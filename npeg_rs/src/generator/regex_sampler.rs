@@ -0,0 +1,81 @@
+use regex_syntax::hir::{Class, Hir, HirKind, Literal, RepetitionKind, RepetitionRange};
+use regex_syntax::ParserBuilder;
+
+use super::rng::Rng;
+
+/// Parses a pattern with the same flags as `Regex::new`
+pub(crate) fn parse_regex(pattern: &str, multi_line: bool, case_insensitive: bool, dot_matches_new_line: bool) -> Result<Hir, String> {
+    ParserBuilder::new()
+        .multi_line(multi_line)
+        .case_insensitive(case_insensitive)
+        .dot_matches_new_line(dot_matches_new_line)
+        .build()
+        .parse(pattern)
+        .map_err(|error| error.to_string())
+}
+
+/// Appends a string which the expression matches, ignoring anchors and word boundaries.
+/// Unbounded repetitions repeat at most `max_repetitions` more times than they must.
+pub(crate) fn sample(hir: &Hir, rng: &mut Rng, max_repetitions: usize, out: &mut String) {
+    match hir.kind() {
+        HirKind::Empty | HirKind::Anchor(_) | HirKind::WordBoundary(_) => {},
+        HirKind::Literal(Literal::Unicode(each)) => out.push(*each),
+        HirKind::Literal(Literal::Byte(byte)) => out.push(*byte as char),
+        HirKind::Class(Class::Unicode(class)) => {
+            let ranges: Vec<(u32, u32)> = class.ranges().iter().map(|range| (range.start() as u32, range.end() as u32)).collect();
+            if let Some(each) = sample_class(&ranges, rng) {
+                out.push(each);
+            }
+        },
+        HirKind::Class(Class::Bytes(class)) => {
+            // Only ASCII bytes can be written into a `str`
+            let ranges: Vec<(u32, u32)> = class
+                .ranges()
+                .iter()
+                .filter(|range| range.start() < 0x80)
+                .map(|range| (range.start() as u32, range.end().min(0x7F) as u32))
+                .collect();
+            if let Some(each) = sample_class(&ranges, rng) {
+                out.push(each);
+            }
+        },
+        HirKind::Repetition(repetition) => {
+            let (minimum, maximum) = match repetition.kind {
+                RepetitionKind::ZeroOrOne => (0, Some(1)),
+                RepetitionKind::ZeroOrMore => (0, None),
+                RepetitionKind::OneOrMore => (1, None),
+                RepetitionKind::Range(RepetitionRange::Exactly(count)) => (count as usize, Some(count as usize)),
+                RepetitionKind::Range(RepetitionRange::AtLeast(minimum)) => (minimum as usize, None),
+                RepetitionKind::Range(RepetitionRange::Bounded(minimum, maximum)) => (minimum as usize, Some(maximum as usize)),
+            };
+            let maximum = maximum.unwrap_or(usize::MAX).min(minimum + max_repetitions);
+            for _ in 0..rng.between(minimum, maximum) {
+                sample(&repetition.hir, rng, max_repetitions, out);
+            }
+        },
+        HirKind::Group(group) => sample(&group.hir, rng, max_repetitions, out),
+        HirKind::Concat(children) => {
+            for child in children {
+                sample(child, rng, max_repetitions, out);
+            }
+        },
+        HirKind::Alternation(children) => sample(&children[rng.below(children.len())], rng, max_repetitions, out),
+    }
+}
+
+/// Picks a character from inclusive ranges of code points.
+/// Printable ASCII is strongly preferred, so that `.` and negated classes give readable text.
+fn sample_class(ranges: &[(u32, u32)], rng: &mut Rng) -> Option<char> {
+    let printable: Vec<(u32, u32)> = ranges
+        .iter()
+        .filter(|(start, end)| *start <= 0x7E && *end >= 0x20)
+        .map(|(start, end)| ((*start).max(0x20), (*end).min(0x7E)))
+        .collect();
+    let ranges = if !printable.is_empty() && rng.below(10) > 0 { &printable[..] } else { ranges };
+    if ranges.is_empty() {
+        return None;
+    }
+    let (start, end) = ranges[rng.below(ranges.len())];
+    // Surrogates are not characters; fall back to the start of the range
+    char::from_u32(rng.between(start as usize, end as usize) as u32).or_else(|| char::from_u32(start))
+}
@@ -0,0 +1,211 @@
+use std::rc::Rc;
+
+use proptest::prelude::Rng;
+use proptest::strategy::{NewTree, Strategy, ValueTree};
+use proptest::test_runner::TestRunner;
+
+use super::{GenerateError, Generator};
+use crate::ops::Grammar;
+
+/// `GrammarStrategy`
+///
+/// A proptest `Strategy` for sentences which a rule of a `Grammar` matches completely, made with a `Generator`.
+/// Sentences shrink by generating again from the same seed with fewer repetitions, then with less nesting,
+/// keeping each attempt only if it is shorter.
+///
+/// Requires the `proptest` feature.
+///
+/// ```
+/// # use std::rc::Rc;
+/// # use npeg_rs::*;
+/// # use proptest::prelude::*;
+/// let gram = Rc::new(Grammar::new(None, vec![
+///     ("List", seq!(lit!("["), qtt!(reg!("[0-9]+,"), 0, None), lit!("]"))),
+/// ]));
+/// proptest!(|(list in GrammarStrategy::new(gram.clone(), "List"))| {
+///     prop_assert!(list.starts_with('['));
+/// });
+/// ```
+#[derive(Debug, Clone)]
+pub struct GrammarStrategy {
+    grammar: Rc<Grammar>,
+    rule_name: String,
+    max_depth: usize,
+    max_repetitions: usize,
+}
+
+impl GrammarStrategy {
+    /// Panics if the grammar has no rule called `rule_name`; see `Grammar::get_rule_by_name`
+    pub fn new(grammar: Rc<Grammar>, rule_name: &str) -> GrammarStrategy {
+        if grammar.get_rule_by_name(rule_name).is_none() {
+            panic!("Rule {} not found", rule_name)
+        }
+        GrammarStrategy {
+            grammar,
+            rule_name: rule_name.to_owned(),
+            max_depth: 12,
+            max_repetitions: 3,
+        }
+    }
+    /// See `Generator::with_max_depth`
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+    /// See `Generator::with_max_repetitions`
+    pub fn with_max_repetitions(mut self, max_repetitions: usize) -> Self {
+        self.max_repetitions = max_repetitions;
+        self
+    }
+    fn generate(&self, seed: u64, max_depth: usize, max_repetitions: usize) -> Result<String, GenerateError> {
+        Generator::new(self.grammar.clone())
+            .with_seed(seed)
+            .with_max_depth(max_depth)
+            .with_max_repetitions(max_repetitions)
+            .generate_rule(&self.rule_name)
+    }
+}
+
+impl Strategy for GrammarStrategy {
+    type Tree = GrammarValueTree;
+    type Value = String;
+    fn new_tree(&self, runner: &mut TestRunner) -> NewTree<Self> {
+        let seed = runner.rng().next_u64();
+        let sentence = self.generate(seed, self.max_depth, self.max_repetitions).map_err(|error| error.to_string())?;
+        Ok(GrammarValueTree {
+            strategy: self.clone(),
+            seed,
+            current: (self.max_depth, self.max_repetitions, sentence),
+            previous: None,
+            min_depth: 0,
+            min_repetitions: 0,
+        })
+    }
+}
+
+/// The `ValueTree` of a `GrammarStrategy`
+#[derive(Debug, Clone)]
+pub struct GrammarValueTree {
+    strategy: GrammarStrategy,
+    seed: u64,
+    /// The max depth and max repetitions the current sentence was generated with, and the sentence
+    current: (usize, usize, String),
+    /// What `current` was before the last successful `simplify()`
+    previous: Option<(usize, usize, String)>,
+    /// Shrinking stops at these limits; they rise when a smaller limit gives no shorter sentence
+    min_depth: usize,
+    min_repetitions: usize,
+}
+
+impl ValueTree for GrammarValueTree {
+    type Value = String;
+    fn current(&self) -> String {
+        self.current.2.clone()
+    }
+    fn simplify(&mut self) -> bool {
+        loop {
+            let (max_depth, max_repetitions, ref sentence) = self.current;
+            let (max_depth, max_repetitions) = if max_repetitions > self.min_repetitions {
+                (max_depth, max_repetitions - 1)
+            } else if max_depth > self.min_depth {
+                (max_depth - 1, max_repetitions)
+            } else {
+                return false;
+            };
+            match self.strategy.generate(self.seed, max_depth, max_repetitions) {
+                Ok(shorter) if shorter.len() < sentence.len() => {
+                    self.previous = Some(std::mem::replace(&mut self.current, (max_depth, max_repetitions, shorter)));
+                    return true;
+                },
+                _ if max_repetitions < self.current.1 => self.min_repetitions = self.current.1,
+                _ => self.min_depth = self.current.0,
+            }
+        }
+    }
+    fn complicate(&mut self) -> bool {
+        match self.previous.take() {
+            Some(previous) => {
+                // The simpler sentence passed, so stop shrinking whichever limit was lowered
+                if previous.1 > self.current.1 {
+                    self.min_repetitions = previous.1;
+                } else {
+                    self.min_depth = previous.0;
+                }
+                self.current = previous;
+                true
+            },
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use proptest::prelude::*;
+    use proptest::strategy::ValueTree;
+    use proptest::test_runner::{Config, RngAlgorithm, TestError, TestRng, TestRunner};
+
+    use crate::*;
+
+    fn arithmetic() -> Rc<Grammar> {
+        Rc::new(Grammar::new(
+            None,
+            vec![
+                ("Expr",   seq!(rul!("Term"), qtt!(seq!(alt!(lit!("+"), lit!("-")), rul!("Term")), 0, None))),
+                ("Term",   seq!(rul!("Factor"), qtt!(seq!(alt!(lit!("*"), lit!("/")), rul!("Factor")), 0, None))),
+                ("Factor", alt!(seq!(lit!("("), rul!("Expr"), lit!(")")), rul!("Number"))),
+                ("Number", reg!("[0-9]+")),
+            ]
+        ))
+    }
+
+    proptest! {
+        #[test]
+        fn generated_expressions_reparse_to_the_same_tree(expr in GrammarStrategy::new(arithmetic(), "Expr")) {
+            let gram = arithmetic();
+            let mut context = Box::new(ParserContext::new(&expr));
+            let tree = gram.clone().parse(&mut context, 0).unwrap();
+            prop_assert_eq!(tree.len(), expr.len());
+            let printed = tree.get_text(&expr).to_owned();
+            let mut reparse_context = Box::new(ParserContext::new(&printed));
+            let reparsed = gram.parse(&mut reparse_context, 0).unwrap();
+            prop_assert_eq!(format!("{:?}", reparsed), format!("{:?}", tree));
+        }
+    }
+
+    #[test]
+    fn simplify_gives_shorter_sentences() {
+        let strategy = GrammarStrategy::new(arithmetic(), "Expr").with_max_depth(9);
+        let mut runner = TestRunner::deterministic();
+        for _ in 0..10 {
+            let mut tree = strategy.new_tree(&mut runner).unwrap();
+            let mut length = tree.current().len();
+            while tree.simplify() {
+                assert!(tree.current().len() < length);
+                length = tree.current().len();
+            }
+            assert!(!tree.complicate() || tree.current().len() > length);
+        }
+    }
+
+    #[test]
+    fn failures_shrink() {
+        let strategy = GrammarStrategy::new(arithmetic(), "Expr");
+        let mut runner = TestRunner::new_with_rng(Config { failure_persistence: None, ..Config::default() }, TestRng::deterministic_rng(RngAlgorithm::ChaCha));
+        match runner.run(&strategy, |expr| {
+            prop_assert!(!expr.contains('*'));
+            Ok(())
+        }) {
+            Err(TestError::Fail(_, shrunk)) => assert!(shrunk.contains('*')),
+            result => panic!("Expected the property to fail, got {:?}", result),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Rule Missing not found")]
+    fn missing_rule_panics() {
+        GrammarStrategy::new(arithmetic(), "Missing");
+    }
+}
@@ -1,22 +1,90 @@
 
+mod analysis;
 mod core;
+mod export;
+mod generator;
 mod ops;
+mod optimizer;
+mod peg_text;
+mod vm;
 
 pub use crate::ops::{
+    Action,
     Alternation,
+    Associativity,
+    BackReference,
+    ByteClass,
+    Capture,
+    CharClass,
+    Counted,
+    Dedent,
+    Endianness,
     Grammar,
+    Indent,
+    Integer,
     Label,
     Literal,
+    LiteralSet,
     Lookahead,
+    Operator,
+    OperatorTable,
+    Predicate,
+    PredicateFn,
     Quantity,
     Regex,
+    ResolvedRule,
+    RuleCall,
     RuleReference,
+    Samedent,
+    SepBy,
     Sequence,
+    Take,
+    Until,
 };
 
+#[cfg(feature = "unicode-segmentation")]
+pub use crate::ops::AnyGrapheme;
+
 pub use crate::core::{
+    CaptureScope,
+    CapturedText,
+    Children,
+    FarthestFailure,
+    IndentationLevel,
+    Match,
+    MatchArena,
+    MatchId,
+    MemoryKey,
+    OpKind,
+    ParseError,
+    ParseTree,
     Parser,
-    ParserContext
+    ParserContext,
+    ParserMatch,
+    ParserState,
+    Profile,
+    ProfileEntry,
+    Symbol,
+    DEFAULT_MAX_DEPTH,
+    parse,
+    parse_bytes,
+};
+
+pub use crate::analysis::{Analysis, AnalysisWarning, FirstSet};
+
+pub use crate::generator::{GenerateError, Generator};
+#[cfg(feature = "proptest")]
+pub use crate::generator::{GrammarStrategy, GrammarValueTree};
+
+pub use crate::peg_text::PegSyntaxError;
+
+pub use crate::vm::{
+    ByteSet,
+    CaptureKind,
+    CompileError,
+    Instruction,
+    Program,
+    MAX_REPETITION_INSTRUCTIONS,
 };
 
 #[macro_use]
@@ -51,6 +119,80 @@ mod tests{
         //println!("{:?}", re.find(&"tac"[1..]));
     }
 
+    #[test]
+    fn test_parameterized_rule() {
+        let gram = Rc::new(
+            Grammar::new(
+                None,
+                vec![
+                    ("Prog",  alt!(call!("List", rul!("Word")), call!("List", rul!("Num")))),
+                    ("Word",  reg!("[a-z]+")),
+                    ("Num",   reg!("[0-9]+")),
+                ]
+            ).with_parameterized_rule(
+                "List",
+                vec!["X"],
+                seq!(rul!("X"), qtt!(seq!(lit!(","), rul!("X")), 0, None), qtt!(lit!(","), 0, Some(1)))
+            )
+        );
+
+        let mut context = Box::new(ParserContext::new("12,3,"));
+        let result = gram.clone().parse(&mut context, 0).unwrap();
+        assert_eq!(result.len(), 5);
+        assert_eq!(result.get_label(), Some("Prog"));
+        let list = result.get_child(0).unwrap();
+        assert_eq!(list.get_label(), Some("List"));
+
+        let mut context = Box::new(ParserContext::new("ab,cd"));
+        let result = gram.clone().parse(&mut context, 0).unwrap();
+        assert_eq!(result.len(), 5);
+
+        let mut context = Box::new(ParserContext::new("ab,12"));
+        let result = gram.parse(&mut context, 0).unwrap();
+        assert_eq!(result.len(), 3);
+    }
+
+    #[test]
+    fn test_parameterized_rule_memoizes_each_argument_separately() {
+        // Both calls parse the body of `Wrap` at position 0; the result for `Num` must not be reused for `Word`
+        let gram = Rc::new(
+            Grammar::new(
+                None,
+                vec![
+                    ("Prog",  alt!(seq!(call!("Wrap", rul!("Num")), lit!("!")), call!("Wrap", rul!("Word")))),
+                    ("Word",  reg!("[a-z]+")),
+                    ("Num",   reg!("[0-9]+")),
+                ]
+            )
+            .with_parameterized_rule("Wrap", vec!["X"], seq!(rul!("X")))
+        );
+        let mut context = Box::new(ParserContext::new("12"));
+        assert!(gram.clone().parse(&mut context, 0).is_none());
+        let mut context = Box::new(ParserContext::new("ab"));
+        let result = gram.parse(&mut context, 0).unwrap();
+        assert_eq!(result.len(), 2);
+    }
+
+    #[test]
+    fn test_parameterized_rule_forwards_arguments() {
+        let gram = Rc::new(
+            Grammar::new(
+                None,
+                vec![
+                    ("Prog",  call!("Parens", rul!("Num"))),
+                    ("Num",   reg!("[0-9]+")),
+                ]
+            )
+            .with_parameterized_rule("Parens", vec!["Y"], seq!(lit!("("), call!("List", rul!("Y")), lit!(")")))
+            .with_parameterized_rule("List", vec!["X"], seq!(rul!("X"), qtt!(seq!(lit!(","), rul!("X")), 0, None)))
+        );
+        let mut context = Box::new(ParserContext::new("(1,22,333)"));
+        let result = gram.clone().parse(&mut context, 0).unwrap();
+        assert_eq!(result.len(), 10);
+        let mut context = Box::new(ParserContext::new("(1,a)"));
+        assert!(gram.parse(&mut context, 0).is_none());
+    }
+
     #[test]
     fn test_a(){
         let lit_a = lit!("a");
@@ -65,4 +207,41 @@ mod tests{
         let mut context = Box::new(ParserContext::new("0"));
         println!("{:?}", alt.parse(&mut context, 0))
     }
+
+    #[test]
+    fn test_multibyte_text() {
+        let gram = Rc::new(
+            Grammar::new(
+                None,
+                vec![
+                    ("Prog",  seq!(lit!("ñ"), rul!("Accent"), rul!("Greek"), rul!("Word"), rul!("Twice"), rul!("Face"), rul!("Block"))),
+                    ("Accent", Rc::new(CharClass::new(&['é', 'ü']))),
+                    ("Greek", reg!("[α-ω]+")),
+                    ("Word",  Rc::new(LiteralSet::new(&["日本", "日"]))),
+                    ("Twice", seq!(cap!(lit!("語"), "w"), brf!("w"))),
+                    ("Face",  qtt!(alt!(lit!("ö"), lit!("🙂")), 1, None)),
+                    ("Block", seq!(lit!("\n"), idt!(), lit!("ä"))),
+                ]
+            )
+        );
+        let result = parse(gram.clone(), "ñéαβγ日本語語🙂ö🙂\n\tä").unwrap().unwrap();
+        let texts: Vec<&str> = result.get_children().map(|child| child.text()).collect();
+        assert_eq!(texts, vec!["ñ", "é", "αβγ", "日本", "語語", "🙂ö🙂", "\n\tä"]);
+        assert_eq!(result.get_end_position(), "ñéαβγ日本語語🙂ö🙂\n\tä".len());
+        assert!(parse(gram, "ñéαβγ日本語本🙂\n\tä").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_matches_never_end_within_a_character() {
+        // Each of these would match the first byte of "é"
+        assert!(parse(bcl!(0xc3..=0xc3), "é").unwrap().is_none());
+        assert!(parse(byt!(b"\xc3"), "é").unwrap().is_none());
+        assert!(parse(int!(1, Big), "é").unwrap().is_none());
+        assert_eq!(parse(int!(2, Big), "é").unwrap().unwrap().text(), "é");
+        // Nor start within one
+        let mut context = Box::new(ParserContext::new("éx"));
+        assert!(reg!(".").parse(&mut context, 1).is_none());
+        assert!(lit!("x").parse(&mut context, 1).is_none());
+        assert!(lit!("x").parse(&mut context, 2).is_some());
+    }
 }
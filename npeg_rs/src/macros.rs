@@ -10,6 +10,71 @@ macro_rules! lit {
     }
 }
 
+/// A `Literal` of bytes, e.g. `byt!(b"\x89PNG")`
+#[macro_export]
+macro_rules! byt {
+    ($l:literal)=>{
+        {
+            use std::rc::Rc;
+            use $crate::Literal;
+            Rc::new(Literal::from_bytes($l))
+        }
+    }
+}
+
+/// A `ByteClass`, e.g. `bcl!(b'0'..=b'9', b'a'..=b'f')`
+#[macro_export]
+macro_rules! bcl {
+    ($($r:expr),+)=>{
+        {
+            use std::rc::Rc;
+            use $crate::ByteClass;
+            Rc::new(ByteClass::new(&[$($r),+]))
+        }
+    }
+}
+
+/// An `Integer` of a width in bytes and an `Endianness`, e.g. `int!(4, Little)`
+#[macro_export]
+macro_rules! int {
+    ($width:literal, $endianness:ident)=>{
+        {
+            use std::rc::Rc;
+            use $crate::Integer;
+            Rc::new(Integer::new($width, $crate::Endianness::$endianness))
+        }
+    }
+}
+
+/// A `Take` of the length captured under a name, big-endian unless an `Endianness` is given,
+/// e.g. `take!("length")` or `take!("length", Little)`
+#[macro_export]
+macro_rules! take {
+    ($l:literal)=>{
+        $crate::take!($l, Big)
+    };
+    ($l:literal, $endianness:ident)=>{
+        {
+            use std::rc::Rc;
+            use $crate::Take;
+            Rc::new(Take::new($l, $crate::Endianness::$endianness))
+        }
+    };
+}
+
+/// An `AnyGrapheme`; requires the `unicode-segmentation` feature
+#[cfg(feature = "unicode-segmentation")]
+#[macro_export]
+macro_rules! gph {
+    ()=>{
+        {
+            use std::rc::Rc;
+            use $crate::AnyGrapheme;
+            Rc::new(AnyGrapheme::new())
+        }
+    }
+}
+
 #[macro_export]
 macro_rules! seq {
     ($($e:expr),*)=>{
@@ -17,10 +82,7 @@ macro_rules! seq {
             use std::rc::Rc;
             use $crate::Sequence;
             use $crate::Parser;
-            let mut items:Vec<Rc<dyn Parser>> = Vec::new();
-            $(
-                items.push($e);
-            )*  
+            let items:Vec<Rc<dyn Parser<_>>> = vec![$($e),*];
             Rc::new(Sequence::new(items))
         }
     }
@@ -33,10 +95,7 @@ macro_rules! alt {
             use std::rc::Rc;
             use $crate::Alternation;
             use $crate::Parser;
-            let mut items:Vec<Rc<dyn Parser>> = Vec::new();
-            $(
-                items.push($e);
-            )*  
+            let items:Vec<Rc<dyn Parser<_>>> = vec![$($e),*];
             Rc::new(Alternation::new(items))
         }
     }
@@ -53,6 +112,116 @@ macro_rules! lbl {
     }
 }
 
+#[macro_export]
+macro_rules! cap {
+    ($e:expr,$l:literal) => {
+        {
+            use std::rc::Rc;
+            use $crate::Capture;
+            Rc::new(Capture::new($e,$l))
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! brf {
+    ($l:literal) => {
+        {
+            use std::rc::Rc;
+            use $crate::BackReference;
+            Rc::new(BackReference::new($l))
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! idt {
+    () => {
+        {
+            use std::rc::Rc;
+            use $crate::Indent;
+            Rc::new(Indent::new())
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! sdt {
+    () => {
+        {
+            use std::rc::Rc;
+            use $crate::Samedent;
+            Rc::new(Samedent::new())
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! ddt {
+    () => {
+        {
+            use std::rc::Rc;
+            use $crate::Dedent;
+            Rc::new(Dedent::new())
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! act {
+    ($e:expr, $f:expr) => {
+        {
+            use std::rc::Rc;
+            use $crate::Action;
+            Rc::new(Action::new($e, $f))
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! prd {
+    ($e:expr, $l:literal, $f:expr) => {
+        {
+            use std::rc::Rc;
+            use $crate::Predicate;
+            Rc::new(Predicate::new($e, $l, $f))
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! sep {
+    ($e:expr, $s:expr) => {
+        {
+            use std::rc::Rc;
+            use $crate::SepBy;
+            Rc::new(SepBy::new($e, $s))
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! unt {
+    ($e:expr, $t:expr) => {
+        {
+            use std::rc::Rc;
+            use $crate::Until;
+            Rc::new(Until::new($e, $t))
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! cnt {
+    ($e:expr, $l:literal) => {
+        {
+            use std::rc::Rc;
+            use $crate::Counted;
+            Rc::new(Counted::new($e, $l))
+        }
+    }
+}
+
 #[macro_export]
 macro_rules! rul {
     ($l:literal) => {
@@ -63,6 +232,19 @@ macro_rules! rul {
         }
     }
 }
+
+#[macro_export]
+macro_rules! call {
+    ($l:literal $(, $e:expr)+) => {
+        {
+            use std::rc::Rc;
+            use $crate::RuleCall;
+            use $crate::Parser;
+            let arguments:Vec<Rc<dyn Parser<_>>> = vec![$($e),+];
+            Rc::new(RuleCall::new($l, arguments))
+        }
+    }
+}
 #[macro_export]
 macro_rules! qtt {
     ($e:expr, $min:literal, $max:expr) => {
@@ -75,6 +257,8 @@ macro_rules! qtt {
 }
 
 //pub fn new(pattern: &str, multi_line:bool, case_insensitive:bool,dot_matches_new_line:bool) -> Self {
+#[doc(hidden)]
+#[macro_export]
 macro_rules! reg_helper {
     ($pattern:tt, $multi_line:literal, $case_insensitive:literal, $dot_matches_new_line:literal) => {{
         use std::rc::Rc;
@@ -85,20 +269,20 @@ macro_rules! reg_helper {
 
 #[macro_export]
 macro_rules! reg {
-    ($pattern:tt     ) => {reg_helper!($pattern, false , false, false )};
-    ($pattern:tt m   ) => {reg_helper!($pattern, true  , false, false )};
-    ($pattern:tt i   ) => {reg_helper!($pattern, false , true , false )};
-    ($pattern:tt s   ) => {reg_helper!($pattern, false , false, true  )};
-    ($pattern:tt mi  ) => {reg_helper!($pattern, true  , true , false )};
-    ($pattern:tt im  ) => {reg_helper!($pattern, true  , true , false )};
-    ($pattern:tt ms  ) => {reg_helper!($pattern, true  , false, true  )};
-    ($pattern:tt sm  ) => {reg_helper!($pattern, true  , false, true  )};
-    ($pattern:tt is  ) => {reg_helper!($pattern, false , true , true  )};
-    ($pattern:tt si  ) => {reg_helper!($pattern, false , true , true  )};
-    ($pattern:tt mis ) => {reg_helper!($pattern, true  , true , true  )};
-    ($pattern:tt msi ) => {reg_helper!($pattern, true  , true , true  )};
-    ($pattern:tt ims ) => {reg_helper!($pattern, true  , true , true  )};
-    ($pattern:tt ism ) => {reg_helper!($pattern, true  , true , true  )};
-    ($pattern:tt smi ) => {reg_helper!($pattern, true  , true , true  )};
-    ($pattern:tt sim ) => {reg_helper!($pattern, true  , true , true  )};
+    ($pattern:tt     ) => {$crate::reg_helper!($pattern, false , false, false )};
+    ($pattern:tt m   ) => {$crate::reg_helper!($pattern, true  , false, false )};
+    ($pattern:tt i   ) => {$crate::reg_helper!($pattern, false , true , false )};
+    ($pattern:tt s   ) => {$crate::reg_helper!($pattern, false , false, true  )};
+    ($pattern:tt mi  ) => {$crate::reg_helper!($pattern, true  , true , false )};
+    ($pattern:tt im  ) => {$crate::reg_helper!($pattern, true  , true , false )};
+    ($pattern:tt ms  ) => {$crate::reg_helper!($pattern, true  , false, true  )};
+    ($pattern:tt sm  ) => {$crate::reg_helper!($pattern, true  , false, true  )};
+    ($pattern:tt is  ) => {$crate::reg_helper!($pattern, false , true , true  )};
+    ($pattern:tt si  ) => {$crate::reg_helper!($pattern, false , true , true  )};
+    ($pattern:tt mis ) => {$crate::reg_helper!($pattern, true  , true , true  )};
+    ($pattern:tt msi ) => {$crate::reg_helper!($pattern, true  , true , true  )};
+    ($pattern:tt ims ) => {$crate::reg_helper!($pattern, true  , true , true  )};
+    ($pattern:tt ism ) => {$crate::reg_helper!($pattern, true  , true , true  )};
+    ($pattern:tt smi ) => {$crate::reg_helper!($pattern, true  , true , true  )};
+    ($pattern:tt sim ) => {$crate::reg_helper!($pattern, true  , true , true  )};
 }
\ No newline at end of file
@@ -0,0 +1,169 @@
+use std::any::type_name;
+use std::fmt;
+use std::rc::Rc;
+use crate::core::{
+    OpKind,
+    OpaqueIdentifier,
+    Parser,
+    ParserContext,
+    ParserMatch,
+    MatchId,
+};
+
+/// The update made by an `Action`; given the child's match, the matched text and the current user state
+type UpdateFn<S> = dyn Fn(&ParserMatch, &str, &S) -> S;
+
+/// Matches what its child matches, and then replaces the user state with the result of `update`;
+/// see `ParserContext::set_user_state()`. The update is given the child's match, the matched text,
+/// and the current state.
+///
+/// The change is undone if the parser backtracks over the match, e.g. when a later item of a `Sequence` fails.
+/// Results are memoized, along with the state they leave behind, so `update` is not called again for the same match.
+/// For byte input which is not UTF-8, the matched text is empty; use `ParserMatch::get_bytes()` instead.
+///
+/// An `Action` only parses with a context whose user state is an `S`:
+/// ```compile_fail
+/// # use npeg_rs::*;
+/// let count = act!(lit!("a"), |_, _, count: &u32| count + 1);
+/// let mut context = Box::new(ParserContext::new("a").with_user_state("a count"));
+/// count.parse(&mut context, 0);
+/// ```
+pub struct Action<S = ()> {
+    id                  : OpaqueIdentifier,
+    child               : Rc<dyn Parser<S>>,
+    update              : Box<UpdateFn<S>>,
+}
+
+impl<S> Action<S>{
+    pub fn new(child: Rc<dyn Parser<S>>, update: impl Fn(&ParserMatch, &str, &S) -> S + 'static) -> Self {
+        Self{
+            id: OpaqueIdentifier::new(),
+            child,
+            update: Box::new(update),
+        }
+    }
+    pub fn get_child(&self) -> &Rc<dyn Parser<S>> {
+        &self.child
+    }
+}
+
+impl<S> fmt::Debug for Action<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Action")
+            .field("id", &self.id)
+            .field("child", &self.child)
+            .field("user_state_type", &type_name::<S>())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S> Parser<S> for Action<S>{
+    fn get_id(&self)->usize {
+        self.id.id()
+    }
+    fn kind(&self)->OpKind<'_, S> {
+        OpKind::Action {
+            child: &self.child,
+            user_state_type: type_name::<S>(),
+        }
+    }
+    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<MatchId> {
+        let child_match = self.child.clone().parse_match(context, start_position)?;
+        let parser_match = context.get_match(child_match);
+        let matched_bytes = parser_match.get_bytes(context.get_input());
+        let matched_text = std::str::from_utf8(matched_bytes).unwrap_or_default();
+        let user_state = (self.update)(&parser_match, matched_text, context.get_user_state());
+        context.set_user_state(user_state);
+        Some(child_match)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::*;
+
+    #[derive(Debug, Default, Clone)]
+    struct TypeNames(Vec<String>);
+
+    fn declare(_: &ParserMatch, name: &str, type_names: &TypeNames) -> TypeNames {
+        let mut type_names = type_names.clone();
+        type_names.0.push(name.to_owned());
+        type_names
+    }
+
+    fn is_type_name(_: &ParserMatch, name: &str, context: &ParserContext<TypeNames>) -> bool {
+        context.get_user_state().0.iter().any(|type_name| type_name == name)
+    }
+
+    /// In C, `a*b;` declares a pointer `b` if `a` is a type, and is otherwise a multiplication
+    fn statements() -> Rc<Grammar<TypeNames>> {
+        Rc::new(Grammar::new(
+            Some("Program"),
+            vec![
+                ("Program", qtt!(alt!(rul!("Typedef"), rul!("Declaration"), rul!("Expression")), 0, None)),
+                ("Typedef", seq!(lit!("typedef "), act!(rul!("Name"), declare), lit!(";"))),
+                ("Declaration", seq!(prd!(rul!("Name"), "a type name", is_type_name), lit!("*"), rul!("Name"), lit!(";"))),
+                ("Expression", seq!(rul!("Name"), lit!("*"), rul!("Name"), lit!(";"))),
+                ("Name", reg!("[a-z]+")),
+            ]
+        ))
+    }
+
+    fn statement_kinds(text: &str, context: ParserContext<TypeNames>) -> Vec<&'static str> {
+        let mut context = Box::new(context);
+        let result = statements().parse(&mut context, 0).unwrap();
+        assert_eq!(result.get_end_position(), text.len());
+        result
+            .get_children()
+            .map(|statement| statement.get_child(0).unwrap().get_label_symbol().unwrap().as_str())
+            .collect()
+    }
+
+    #[test]
+    fn actions_change_what_predicates_accept() {
+        let text = "a*b;typedef a;a*b;";
+        let context = ParserContext::new(text).with_user_state(TypeNames::default());
+        assert_eq!(statement_kinds(text, context), vec!["Expression", "Typedef", "Declaration"]);
+        let context = ParserContext::new(text).with_user_state(TypeNames(vec!["a".to_owned()]));
+        assert_eq!(statement_kinds(text, context), vec!["Declaration", "Typedef", "Declaration"]);
+    }
+
+    #[test]
+    fn actions_are_undone_by_backtracking() {
+        let gram = alt!(
+            seq!(act!(reg!("[a-z]+"), declare), lit!("!")),
+            seq!(prd!(reg!("[a-z]+"), "a type name", is_type_name), lit!("?")),
+            reg!("[a-z]+")
+        );
+        let mut context = Box::new(ParserContext::new("b?").with_user_state(TypeNames::default()));
+        let result = gram.clone().parse(&mut context, 0).unwrap();
+        assert_eq!(result.len(), 1);
+        assert!(context.get_user_state().0.is_empty());
+
+        let mut context = Box::new(ParserContext::new("b!").with_user_state(TypeNames::default()));
+        assert!(gram.parse(&mut context, 0).is_some());
+        assert_eq!(context.get_user_state().0, vec!["b"]);
+    }
+
+    #[test]
+    fn tools_see_the_child_and_user_state_type() {
+        let gram = statements();
+        let (_, typedef) = gram.get_rule_by_name("Typedef").unwrap();
+        assert_eq!(typedef.to_peg_string(), "\"typedef \" @action(Name) \";\"");
+        let OpKind::Sequence(children) = typedef.kind() else {
+            panic!("Expected a Sequence")
+        };
+        let OpKind::Action { child, user_state_type } = children[1].kind() else {
+            panic!("Expected an Action")
+        };
+        assert!(matches!(child.kind(), OpKind::RuleReference("Name")));
+        assert_eq!(user_state_type, std::any::type_name::<TypeNames>());
+        let analysis = gram.analyze();
+        assert_eq!(analysis.is_nullable(children[1].as_ref()), Some(false));
+        let first = analysis.get_rule_first_set("Declaration").unwrap();
+        assert!(!first.is_any() && first.contains(b'a') && !first.contains(b'*'));
+        assert!(gram.to_dot().contains("\"Typedef\" -> \"Name\";"));
+    }
+}
@@ -1,50 +1,137 @@
+use std::fmt;
 use std::rc::Rc;
 use crate::core::{
+    OpKind,
     OpaqueIdentifier,
     Parser,
     ParserContext,
-    ParserMatch,
+    MatchId,
 };
-#[derive(Debug)]
 // TODO: rename to FirstAlternative
 
-pub struct Alternation {
+pub struct Alternation<S = ()> {
     id                  : OpaqueIdentifier,
-    children            : Vec<Rc<dyn Parser>>,
+    children            : Vec<Rc<dyn Parser<S>>>,
 }
-impl Alternation{
-    pub fn new(children: Vec<Rc<dyn Parser>>) -> Self {
-        if children.len() == 0 {
+impl<S> Alternation<S>{
+    pub fn new(children: Vec<Rc<dyn Parser<S>>>) -> Self {
+        if children.is_empty() {
             panic!("Zero Length Alternations are not permitted")
         }
         Self {
             id:OpaqueIdentifier::new(),
-            children:children
+            children
         }
     }
 }
-impl Parser for Alternation{
+impl<S> fmt::Debug for Alternation<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Alternation")
+            .field("id", &self.id)
+            .field("children", &self.children)
+            .finish()
+    }
+}
+
+impl<S> Parser<S> for Alternation<S>{
     fn get_id(&self)->usize {
         self.id.id()
     }
-    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext>, start_position: usize) -> Option<Rc<ParserMatch>> {
-        let mut end_position = start_position;
-        self.children
-            .iter()
-            .find_map(|child|
-                child.clone().parse(context, end_position).map(|ma| {
-                    end_position += ma.len();
-                    ma
-                })
-            )
-            .as_ref()
-            .map(|sub_match|
-                ParserMatch::new(
-                    start_position,
-                    end_position,
-                    None,
-                    vec![sub_match.clone()].into()
-                )
-            )
-    }
-}
\ No newline at end of file
+    fn kind(&self)->OpKind<'_, S> {
+        OpKind::Alternation(&self.children)
+    }
+    /// Only the children which can start with the next byte are tried; see `ParserContext::get_dispatch_table()`
+    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<MatchId> {
+        let sub_match = if context.is_nfc() {
+            // Canonically equivalent text may start with another byte
+            self.children
+                .iter()
+                .find_map(|child| child.clone().parse_match(context, start_position))
+        } else {
+            let dispatch_table = context.get_dispatch_table(self.get_id(), &self.children);
+            let next_byte = context.get_input().get(start_position).copied();
+            let sub_match = dispatch_table
+                .get_candidates(next_byte)
+                .iter()
+                .find_map(|&index| self.children[index].clone().parse_match(context, start_position));
+            if sub_match.is_none() {
+                // Children which were not tried are expected here all the same
+                for child in &self.children {
+                    context.record_failure(start_position, &**child);
+                }
+            }
+            sub_match
+        }?;
+        let end_position = context.get_match_end_position(sub_match);
+        Some(context.new_match(start_position, end_position, None, &[sub_match]))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use crate::*;
+    use crate::core::OpaqueIdentifier;
+
+    /// Counts how often it is parsed
+    #[derive(Debug)]
+    struct Counter {
+        id: OpaqueIdentifier,
+        child: Rc<dyn Parser>,
+        count: Cell<usize>,
+    }
+    impl Parser for Counter {
+        fn get_id(&self) -> usize {
+            self.id.id()
+        }
+        fn parse_internal(self: Rc<Self>, context: &mut Box<ParserContext>, start_position: usize) -> Option<MatchId> {
+            self.count.set(self.count.get() + 1);
+            self.child.clone().parse_match(context, start_position)
+        }
+    }
+    fn counter(child: Rc<dyn Parser>) -> Rc<Counter> {
+        Rc::new(Counter { id: OpaqueIdentifier::new(), child, count: Cell::new(0) })
+    }
+
+    #[test]
+    fn alternation_only_tries_children_which_can_start_with_the_next_byte() {
+        let gram = Rc::new(Grammar::new(
+            None,
+            vec![
+                ("Statement", alt!(rul!("Keyword"), reg!("[0-9]+"), lit!("while"))),
+                ("Keyword",   alt!(lit!("if"), lit!("else"), lit!("return"))),
+            ]
+        ));
+        let mut context = Box::new(ParserContext::new("while").with_profiling(true));
+        assert_eq!(gram.clone().parse(&mut context, 0).map(|result| result.len()), Some(5));
+        let profile = context.get_profile().unwrap();
+        let invocations = |description: &str| profile.get_op_entries().iter().find(|(each, _)| *each == description).map_or(0, |(_, entry)| entry.invocations);
+        assert_eq!(invocations("RuleReference: Keyword"), 0);
+        assert_eq!(invocations("Regex: r\"[0-9]+\""), 0);
+        assert_eq!(invocations("Literal: \"while\""), 1);
+
+        let mut context = Box::new(ParserContext::new("else").with_profiling(true));
+        assert_eq!(gram.parse(&mut context, 0).map(|result| result.len()), Some(4));
+        let profile = context.get_profile().unwrap();
+        assert_eq!(profile.get_op_entries().iter().filter(|(description, _)| description.starts_with("Literal")).count(), 1);
+    }
+
+    #[test]
+    fn alternation_tries_children_which_may_match_nothing() {
+        let other = counter(lit!("y"));
+        let gram = Rc::new(Grammar::new(
+            None,
+            vec![
+                ("Start", seq!(cap!(reg!("[a-w]*"), "word"), alt!(lit!("a"), brf!("word")), call!("Either", lit!("x")), qtt!(alt!(lit!("z"), other.clone()), 0, Some(1)), lit!(";"))),
+            ]
+        ).with_parameterized_rule("Either", vec!["Other"], alt!(lit!("a"), rul!("Other"), qtt!(lit!("b"), 0, None))));
+        for (text, expected) in [(";", Some(1)), ("x;", Some(2)), ("y;", Some(2)), ("q;", None)] {
+            let mut context = Box::new(ParserContext::new(text));
+            assert_eq!(gram.clone().parse(&mut context, 0).map(|result| result.len()), expected, "{:?}", text);
+        }
+        // Ops defined outside this crate are always tried
+        assert_eq!(other.count.get(), 3);
+    }
+}
@@ -0,0 +1,68 @@
+use std::rc::Rc;
+use unicode_segmentation::UnicodeSegmentation;
+use crate::core::{
+    OpKind,
+    OpaqueIdentifier,
+    Parser,
+    ParserContext,
+    MatchId,
+};
+#[derive(Debug)]
+/// Matches one extended grapheme cluster; what a user sees as one character,
+/// e.g. a letter with its accents, or an emoji made of several code points.
+/// Fails at the end of the input.
+pub struct AnyGrapheme {
+    id                  : OpaqueIdentifier,
+}
+impl AnyGrapheme{
+    pub fn new() -> Self {
+        Self {
+            id: OpaqueIdentifier::new(),
+        }
+    }
+}
+impl Default for AnyGrapheme {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<S> Parser<S> for AnyGrapheme{
+    fn get_id(&self)->usize {
+        self.id.id()
+    }
+    fn kind(&self)->OpKind<'_, S> {
+        OpKind::AnyGrapheme
+    }
+    fn describe_failure(&self)->Option<String> {
+        Some("any grapheme".into())
+    }
+    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<MatchId> {
+        let grapheme = context.get_text_at(start_position).graphemes(true).next()?;
+        Some(context.new_match(start_position, start_position + grapheme.len(), None, &[]))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn grapheme_clusters_are_matched_whole() {
+        let text = "e\u{301}👩‍👩‍👧x";
+        let graphemes = qtt!(lbl!(gph!(), "grapheme"), 0, None);
+        let result = parse(graphemes, text).unwrap().unwrap();
+        let matched: Vec<&str> = result.get_children().map(|grapheme| grapheme.text()).collect();
+        assert_eq!(matched, vec!["e\u{301}", "👩‍👩‍👧", "x"]);
+        // A regex "." matches only the first code point
+        assert_eq!(parse(reg!("."), text).unwrap().unwrap().text(), "e");
+    }
+
+    #[test]
+    fn grapheme_is_written_as_peg_text() {
+        let gram: Grammar = Grammar::new(None, vec![("A", qtt!(gph!(), 1, None))]);
+        assert_eq!(gram.to_peg_string(), "A <- @grapheme+\n");
+        let read = Grammar::from_peg_string(&gram.to_peg_string()).unwrap();
+        assert_eq!(parse(std::rc::Rc::new(read), "e\u{301}x").unwrap().unwrap().get_children().count(), 2);
+    }
+}
@@ -0,0 +1,126 @@
+use std::rc::Rc;
+use crate::core::{
+    OpKind,
+    OpaqueIdentifier,
+    Parser,
+    ParserContext,
+    MatchId,
+};
+#[derive(Debug)]
+/// Matches exactly the text of the most recent `Capture` stored under `name`.
+/// Fails if there is no such capture.
+pub struct BackReference {
+    id                  : OpaqueIdentifier,
+    name                : String,
+}
+
+impl BackReference{
+    pub fn new(name: &str) -> Self {
+        Self{
+            id: OpaqueIdentifier::new(),
+            name: name.into()
+        }
+    }
+}
+impl<S> Parser<S> for BackReference{
+    fn get_id(&self)->usize {
+        self.id.id()
+    }
+    fn kind(&self)->OpKind<'_, S> {
+        OpKind::BackReference(&self.name)
+    }
+    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<MatchId> {
+        let end_position = if context.is_nfc() {
+            let captured_text = context.get_capture(&self.name)?.get_text(context.get_full_text());
+            context.match_text(start_position, captured_text)?
+        } else {
+            let input = context.get_input();
+            let captured_bytes = context.get_capture(&self.name)?.get_bytes(input);
+            if !input[start_position..].starts_with(captured_bytes) {
+                return None;
+            }
+            start_position + captured_bytes.len()
+        };
+        Some(context.new_match(start_position, end_position, None, &[]))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::*;
+
+    #[test]
+    fn raw_string_closes_with_same_number_of_hashes() {
+        let raw_string = seq!(
+            lit!("r"),
+            cap!(reg!("#*"), "hashes"),
+            lit!("\""),
+            qtt!(alt!(reg!("[^\"]"), Rc::new(Lookahead::new(lit!("\""), brf!("hashes"), false))), 0, None),
+            lit!("\""),
+            brf!("hashes")
+        );
+        let text = r####"r##"a "# b"##"####;
+        let mut context = Box::new(ParserContext::new(text));
+        let result = raw_string.clone().parse(&mut context, 0).unwrap();
+        assert_eq!(result.len(), text.len());
+
+        let mut context = Box::new(ParserContext::new(r####"r##"a"#"####));
+        assert!(raw_string.parse(&mut context, 0).is_none());
+    }
+
+    #[test]
+    fn nested_tags_match_their_own_capture() {
+        let gram = Rc::new(Grammar::new(
+            None,
+            vec![
+                ("Element", seq!(
+                    lit!("<"), cap!(reg!("[a-z]+"), "tag"), lit!(">"),
+                    qtt!(rul!("Element"), 0, None),
+                    lit!("</"), brf!("tag"), lit!(">")
+                )),
+            ]
+        ));
+        let text = "<a><bb></bb><c></c></a>";
+        let mut context = Box::new(ParserContext::new(text));
+        let result = gram.clone().parse(&mut context, 0).unwrap();
+        assert_eq!(result.len(), text.len());
+
+        let mut context = Box::new(ParserContext::new("<a><b></a></b>"));
+        assert!(gram.parse(&mut context, 0).is_none());
+    }
+
+    #[test]
+    fn captures_are_discarded_when_alternation_backtracks() {
+        let alt = alt!(
+            seq!(cap!(lit!("a"), "x"), lit!("!")),
+            seq!(lit!("a"), brf!("x"))
+        );
+        let mut context = Box::new(ParserContext::new("aa"));
+        assert!(alt.parse(&mut context, 0).is_none());
+    }
+
+    #[test]
+    fn captures_are_discarded_when_quantity_backtracks() {
+        let seq = seq!(qtt!(seq!(cap!(reg!("[a-z]+"), "x"), lit!(",")), 1, None), brf!("x"));
+        let mut context = Box::new(ParserContext::new("a,a"));
+        let result = seq.clone().parse(&mut context, 0).unwrap();
+        assert_eq!(result.len(), 3);
+        // The last repetition captures "bb" before failing to find ","
+        let mut context = Box::new(ParserContext::new("a,bb"));
+        assert!(seq.parse(&mut context, 0).is_none());
+    }
+
+    #[test]
+    fn memoized_results_restore_captures() {
+        let capture = cap!(lit!("a"), "x");
+        let alt = alt!(
+            seq!(capture.clone(), lit!("b"), lit!("!")),
+            seq!(capture.clone(), lit!("b"), brf!("x"))
+        );
+        let mut context = Box::new(ParserContext::new("aba"));
+        let result = alt.parse(&mut context, 0).unwrap();
+        assert_eq!(result.len(), 3);
+    }
+}
@@ -0,0 +1,64 @@
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+use crate::core::{
+    OpKind,
+    OpaqueIdentifier,
+    Parser,
+    ParserContext,
+    MatchId,
+};
+use crate::vm::ByteSet;
+#[derive(Debug)]
+/// Matches one byte within any of a list of ranges; for byte input, see `ParserContext::from_bytes()`.
+///
+/// On text input, a range which includes bytes above `0x7F` may match part of a character.
+pub struct ByteClass {
+    id                  : OpaqueIdentifier,
+    bytes               : ByteSet,
+    /// As given, for `kind()` and `describe_failure()`
+    ranges              : Vec<RangeInclusive<u8>>,
+}
+impl ByteClass{
+    pub fn new(ranges: &[RangeInclusive<u8>]) -> Self {
+        let mut bytes = ByteSet::default();
+        for range in ranges {
+            for byte in range.clone() {
+                bytes.insert(byte);
+            }
+        }
+        if bytes.is_empty() {
+            panic!("Empty ByteClass is not permitted")
+        }
+        Self {
+            id:OpaqueIdentifier::new(),
+            bytes,
+            ranges: ranges.to_vec(),
+        }
+    }
+    pub fn get_bytes(&self) -> &ByteSet {
+        &self.bytes
+    }
+}
+impl<S> Parser<S> for ByteClass{
+    fn get_id(&self)->usize {
+        self.id.id()
+    }
+    fn kind(&self)->OpKind<'_, S> {
+        OpKind::ByteClass(&self.ranges)
+    }
+    fn describe_failure(&self)->Option<String> {
+        let ranges: Vec<String> = self.ranges
+            .iter()
+            .map(|range| format!("{:#04x}..={:#04x}", range.start(), range.end()))
+            .collect();
+        Some(format!("a byte in {}", ranges.join(", ")))
+    }
+    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<MatchId> {
+        let byte = *context.get_input().get(start_position)?;
+        if self.bytes.contains(byte) {
+            Some(context.new_match(start_position, start_position + 1, None, &[]))
+        } else {
+            None
+        }
+    }
+}
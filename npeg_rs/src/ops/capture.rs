@@ -0,0 +1,55 @@
+use std::fmt;
+use std::rc::Rc;
+use crate::core::{
+    OpKind,
+    OpaqueIdentifier,
+    Parser,
+    ParserContext,
+    MatchId,
+};
+/// Stores the text matched by the child under `name`, to be matched again by a `BackReference`.
+///
+/// Captures are discarded when the parser backtracks over them,
+/// and when the rule invocation they were made in returns.
+pub struct Capture<S = ()> {
+    id                  : OpaqueIdentifier,
+    child               : Rc<dyn Parser<S>>,
+    name                : Rc<String>,
+}
+
+impl<S> Capture<S>{
+    pub fn new(child: Rc<dyn Parser<S>>, name: &str) -> Self {
+        Self{
+            id: OpaqueIdentifier::new(),
+            child,
+            name: Rc::new(name.into())
+        }
+    }
+}
+impl<S> fmt::Debug for Capture<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Capture")
+            .field("id", &self.id)
+            .field("child", &self.child)
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+impl<S> Parser<S> for Capture<S>{
+    fn get_id(&self)->usize {
+        self.id.id()
+    }
+    fn kind(&self)->OpKind<'_, S> {
+        OpKind::Capture {
+            child: &self.child,
+            name: &self.name,
+        }
+    }
+    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<MatchId> {
+        self.child.clone().parse_match(context, start_position).inspect(|parser_match| {
+            let end_position = context.get_match_end_position(*parser_match);
+            context.push_capture(self.name.clone(), start_position, end_position);
+        })
+    }
+}
@@ -0,0 +1,71 @@
+use std::fmt;
+use std::rc::Rc;
+use crate::core::{
+    OpKind,
+    OpaqueIdentifier,
+    Parser,
+    ParserContext,
+    MatchId,
+};
+/// Matches one of a set of characters; the same as an `Alternation` of single character `Literal`s,
+/// with the same matches, but decided with one lookup. See `Grammar::optimize()`.
+pub struct CharClass<S = ()> {
+    id                  : OpaqueIdentifier,
+    /// Sorted, for binary search
+    characters          : Vec<char>,
+    /// The equivalent literals, for `kind()`
+    children            : Vec<Rc<dyn Parser<S>>>,
+}
+impl<S> CharClass<S>{
+    pub fn new(characters: &[char]) -> Self {
+        if characters.is_empty() {
+            panic!("Empty CharClass is not permitted")
+        }
+        let mut sorted_characters = characters.to_vec();
+        sorted_characters.sort_unstable();
+        sorted_characters.dedup();
+        Self {
+            id:OpaqueIdentifier::new(),
+            characters: sorted_characters,
+            children: characters
+                .iter()
+                .map(|character| Rc::new(crate::ops::Literal::new(character.encode_utf8(&mut [0; 4]))) as Rc<dyn Parser<S>>)
+                .collect(),
+        }
+    }
+    pub fn get_characters(&self) -> &[char] {
+        &self.characters
+    }
+}
+impl<S> fmt::Debug for CharClass<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CharClass")
+            .field("id", &self.id)
+            .field("characters", &self.characters)
+            .field("children", &self.children)
+            .finish()
+    }
+}
+
+impl<S> Parser<S> for CharClass<S>{
+    fn get_id(&self)->usize {
+        self.id.id()
+    }
+    /// Seen by tools as the `Alternation` it replaces
+    fn kind(&self)->OpKind<'_, S> {
+        OpKind::Alternation(&self.children)
+    }
+    fn describe_failure(&self)->Option<String> {
+        Some(self.to_peg_string())
+    }
+    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<MatchId> {
+        let (character, length) = context.get_char_at(start_position)?;
+        if self.characters.binary_search(&character).is_err() {
+            return None;
+        }
+        let end_position = start_position + length;
+        // Shaped like the match of an `Alternation` of `Literal`s
+        let literal_match = context.new_match(start_position, end_position, None, &[]);
+        Some(context.new_match(start_position, end_position, None, &[literal_match]))
+    }
+}
@@ -0,0 +1,135 @@
+use std::fmt;
+use std::rc::Rc;
+use crate::core::{
+    OpKind,
+    OpaqueIdentifier,
+    Parser,
+    ParserContext,
+    MatchId,
+};
+use super::Endianness;
+/// Matches `child` as many times as given by the most recent `Capture` stored under `name`, e.g. `3 a b c`
+/// when `3` is captured; the match has the matches of `child` as children.
+///
+/// The captured text is read as a decimal number, or `with_binary_count(...)`, as an unsigned integer like `Take` does.
+/// Fails if there is no such capture, if it is not a number, or if `child` matches fewer times.
+/// Once `child` matches nothing, the remaining times would match the same, so the match ends there.
+pub struct Counted<S = ()> {
+    id                  : OpaqueIdentifier,
+    child               : Rc<dyn Parser<S>>,
+    name                : String,
+    endianness          : Option<Endianness>,
+}
+
+impl<S> Counted<S>{
+    pub fn new(child: Rc<dyn Parser<S>>, name: &str) -> Self {
+        Self{
+            id: OpaqueIdentifier::new(),
+            child,
+            name: name.into(),
+            endianness: None,
+        }
+    }
+    /// Reads the captured bytes as an unsigned integer, for byte input; see `ParserContext::from_bytes()`
+    pub fn with_binary_count(mut self, endianness: Endianness) -> Self {
+        self.endianness = Some(endianness);
+        self
+    }
+    pub fn get_child(&self) -> &Rc<dyn Parser<S>> {
+        &self.child
+    }
+    /// The number of times to match, read from the capture
+    fn get_count(&self, context: &ParserContext<S>) -> Option<usize> {
+        let captured_bytes = context.get_capture(&self.name)?.get_bytes(context.get_input());
+        match self.endianness {
+            Some(endianness) if captured_bytes.len() <= 8 => usize::try_from(endianness.read(captured_bytes)).ok(),
+            Some(_) => None,
+            None => std::str::from_utf8(captured_bytes).ok()?.parse().ok(),
+        }
+    }
+}
+impl<S> fmt::Debug for Counted<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Counted")
+            .field("id", &self.id)
+            .field("child", &self.child)
+            .field("name", &self.name)
+            .field("endianness", &self.endianness)
+            .finish()
+    }
+}
+
+impl<S> Parser<S> for Counted<S>{
+    fn get_id(&self)->usize {
+        self.id.id()
+    }
+    fn kind(&self)->OpKind<'_, S> {
+        OpKind::Counted {
+            child: &self.child,
+            name: &self.name,
+            endianness: self.endianness,
+        }
+    }
+    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<MatchId> {
+        let count = self.get_count(context)?;
+        let remaining_length = context.get_input().len() - start_position;
+        if count > remaining_length && !context.can_match_nothing(&self.child) {
+            // Each match of `child` consumes at least a byte
+            return None;
+        }
+        let mut end_position = start_position;
+        let mark = context.get_arena_mut().start_pending();
+        while context.get_arena_mut().count_pending(mark) < count {
+            match self.child.clone().parse_match(context, end_position) {
+                Some(child_match) => {
+                    let child_end = context.get_match_end_position(child_match);
+                    context.get_arena_mut().push_pending(child_match);
+                    if child_end == end_position {
+                        break;
+                    }
+                    end_position = child_end;
+                },
+                None => {
+                    context.get_arena_mut().discard_pending(mark);
+                    return None;
+                },
+            }
+        }
+        Some(context.get_arena_mut().finish_match(start_position, end_position, mark))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn count_is_taken_from_capture() {
+        let list = seq!(cap!(reg!("[0-9]+"), "count"), lit!(":"), cnt!(seq!(lit!(" "), reg!("[a-z]+")), "count"));
+        let result = parse(list.clone(), "3: a b c d").unwrap().unwrap();
+        assert_eq!(result.text(), "3: a b c");
+        assert_eq!(result.get_child(2).unwrap().get_children().len(), 3);
+        assert_eq!(parse(list.clone(), "0: a").unwrap().unwrap().text(), "0:");
+        assert!(parse(list, "3: a b").unwrap().is_none());
+    }
+
+    #[test]
+    fn binary_count() {
+        let array = seq!(cap!(int!(2, Little), "count"), Rc::new(Counted::new(int!(4, Big), "count").with_binary_count(Endianness::Little)));
+        let result = parse_bytes(array, b"\x02\x00\x00\x00\x00\x01\x00\x00\x00\x02\xff").unwrap().unwrap();
+        assert_eq!(result.get_end_position(), 10);
+        let values: Vec<u64> = result.get_child(1).unwrap().get_children().map(|value| Endianness::Big.read(value.bytes())).collect();
+        assert_eq!(values, vec![1, 2]);
+    }
+
+    #[test]
+    fn hostile_count() {
+        let list = seq!(cap!(reg!("[0-9]+"), "count"), lit!(":"), cnt!(lit!("a"), "count"));
+        assert!(parse(list, "99999999999999:aaa").unwrap().is_none());
+        let list = seq!(cap!(reg!("[0-9]+"), "count"), lit!(":"), cnt!(qtt!(lit!("a"), 0, None), "count"));
+        let result = parse(list, "99999999999999:aa").unwrap().unwrap();
+        assert_eq!(result.text(), "99999999999999:aa");
+        assert_eq!(result.get_child(2).unwrap().get_children().len(), 2);
+    }
+}
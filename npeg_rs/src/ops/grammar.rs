@@ -1,57 +1,244 @@
+use std::fmt;
 use std::rc::Rc;
 use crate::core::{
+    OpKind,
     OpaqueIdentifier,
     Parser,
     ParserContext,
-    ParserMatch,
+    MatchId,
+    Symbol,
 };
-#[derive(Debug)]
-pub struct Grammar {
+
+/// A rule name and its op
+type Rule<S> = (Rc<String>, Rc<dyn Parser<S>>);
+
+/// A rule found by `Grammar::resolve_rule` or `ParserContext::resolve_rule`
+pub struct ResolvedRule<S = ()> {
+    pub rule_name: Rc<String>,
+    pub parser_operator: Rc<dyn Parser<S>>,
+    pub parameters: Vec<Rc<String>>,
+    /// The stack of grammars the rule must be parsed with, if it is different to the current one.
+    /// This is the case for rules of imported grammars, and rules found in an enclosing grammar.
+    pub rule_sets: Option<Vec<Rc<Grammar<S>>>>,
+}
+
+pub struct Grammar<S = ()> {
     id                  : OpaqueIdentifier,
-    rule_set:Vec<(Rc<String>, Rc<dyn Parser>)>,
-    starting_rule_name:Option<String>
+    rule_set:Vec<Rule<S>>,
+    rule_parameters:Vec<(Rc<String>, Vec<Rc<String>>)>,
+    starting_rule_name:Option<String>,
+    imports:Vec<(String, Rc<Grammar<S>>)>,
+    base:Option<Rc<Grammar<S>>>,
 }
-impl Grammar {
-    pub fn new(starting_rule:Option<&str>, rules:Vec<(&str, Rc<dyn Parser>)>) -> Self{
+impl<S> Grammar<S> {
+    pub fn new(starting_rule:Option<&str>, rules:Vec<(&str, Rc<dyn Parser<S>>)>) -> Self{
         // TODO: check for duplicate rule definitions
         // TODO: if the top level rules are Self::Label this is an issue; the grammar parser will override the Label
         Self {
             id: OpaqueIdentifier::new(),
             rule_set:rules.into_iter().map(|(name, rule)|(Rc::new(name.into()), rule)).collect(),
-            starting_rule_name:starting_rule.map(|item| item.to_owned())
-            
+            rule_parameters:vec![],
+            starting_rule_name:starting_rule.map(|item| item.to_owned()),
+            imports:vec![],
+            base:None,
+        }
+    }
+    /// Adds a rule after those already defined
+    pub fn with_rule(mut self, rule_name:&str, rule:Rc<dyn Parser<S>>) -> Self {
+        self.rule_set.push((Rc::new(rule_name.into()), rule));
+        self
+    }
+    /// Makes the rules of `grammar` available under `namespace`, so that they can be referenced as `rul!("namespace::Rule")`.
+    /// Rules of the imported grammar are parsed within that grammar; references inside them do not see the importing grammar.
+    pub fn with_import(mut self, namespace:&str, grammar:Rc<Grammar<S>>) -> Self {
+        if namespace.contains("::") {
+            panic!("Import namespace {} must not contain '::'", namespace)
+        }
+        self.imports.push((namespace.into(), grammar));
+        self
+    }
+    /// Inherits all rules and imports of `base`. Rules defined in this grammar override rules of the same name in `base`,
+    /// including where they are referenced from rules defined by `base`.
+    /// Unless a starting rule is given, the starting rule of `base` is used.
+    /// ```
+    /// # use npeg_rs::*;
+    /// # use std::rc::Rc;
+    /// let sql = Rc::new(Grammar::new(None, vec![
+    ///     ("Select",     seq!(lit!("SELECT "), rul!("Identifier"))),
+    ///     ("Identifier", reg!("[a-z]+")),
+    /// ]));
+    /// let dialect = Rc::new(Grammar::new(None, vec![
+    ///     ("Identifier", alt!(reg!("[a-z]+"), reg!("`[^`]*`"))),
+    /// ]).with_base(sql));
+    /// let mut context = Box::new(ParserContext::new("SELECT `a b`"));
+    /// let result = dialect.parse(&mut context, 0).unwrap();
+    /// assert_eq!(result.len(), 12);
+    /// ```
+    pub fn with_base(mut self, base:Rc<Grammar<S>>) -> Self {
+        self.base = Some(base);
+        self
+    }
+    /// Adds a rule which takes parameters. Within `rule` the parameters are referenced like any other rule
+    /// (`rul!("X")`), and the rule itself must be invoked with a `RuleCall` (`call!("List", rul!("Arg"))`)
+    /// ```
+    /// # use npeg_rs::*;
+    /// let gram: Grammar = Grammar::new(None, vec![
+    ///     ("Args", call!("List", rul!("Arg"))),
+    ///     ("Arg",  reg!("[a-z]+")),
+    /// ]).with_parameterized_rule("List", vec!["X"], seq!(rul!("X"), qtt!(seq!(lit!(","), rul!("X")), 0, None)));
+    /// ```
+    pub fn with_parameterized_rule(mut self, rule_name:&str, parameters:Vec<&str>, rule:Rc<dyn Parser<S>>) -> Self {
+        let rule_name:Rc<String> = Rc::new(rule_name.into());
+        self.rule_parameters.push((
+            rule_name.clone(),
+            parameters.into_iter().map(|parameter_name| Rc::new(parameter_name.into())).collect()
+        ));
+        self.rule_set.push((rule_name, rule));
+        self
+    }
+    /// Returns the parameter names of the rule, which is empty unless the rule was added by `with_parameterized_rule`
+    pub fn get_rule_parameters(&self, rule_name:&str) -> &[Rc<String>] {
+        if self.has_own_rule(rule_name) {
+            self
+            .rule_parameters
+            .iter()
+            .find(|(each_rule_name, _parameters)| rule_name==&each_rule_name[..])
+            .map_or(&[], |(_rule_name, parameters)| &parameters[..])
+        } else {
+            self.base.as_ref().map_or(&[], |base| base.get_rule_parameters(rule_name))
         }
     }
+    fn has_own_rule(&self, rule_name:&str) -> bool {
+        self.rule_set.iter().any(|(each_rule_name, _each_parser_operator)| rule_name==&each_rule_name[..])
+    }
     /// Takes a string and returns the corresponding rule, if it exists.
+    /// Rules not defined by this grammar are looked up in the base grammar.
     /// The result is an Rc::clone() of the original data
-    pub fn get_rule_by_name(&self, rule_name:&str) -> Option<(Rc<String>, Rc<dyn Parser>)>{
+    pub fn get_rule_by_name(&self, rule_name:&str) -> Option<(Rc<String>, Rc<dyn Parser<S>>)>{
         self
         .rule_set
         .iter()
         .find(|(each_rule_name, _each_parser_operator)| rule_name==&each_rule_name[..])
         .cloned()
+        .or_else(|| self.base.as_ref().and_then(|base| base.get_rule_by_name(rule_name)))
     }
-    
-    pub fn get_starting_rule(&self) -> Option<(Rc<String>, Rc<dyn Parser>)>{
+    /// Returns the grammar imported under `namespace` by this grammar or its base grammar
+    pub fn get_import(&self, namespace:&str) -> Option<Rc<Grammar<S>>>{
         self
-        .starting_rule_name
-        .as_ref()
-        .map(|rule_name|self.get_rule_by_name(rule_name))
-        .flatten()
+        .imports
+        .iter()
+        .find(|(each_namespace, _grammar)| namespace==&each_namespace[..])
+        .map(|(_namespace, grammar)| grammar.clone())
+        .or_else(|| self.base.as_ref().and_then(|base| base.get_import(namespace)))
+    }
+    /// Like `get_rule_by_name` but also accepts namespaced names (`json::Value`) which are resolved through imports
+    pub fn resolve_rule(&self, rule_name:&str) -> Option<ResolvedRule<S>>{
+        match rule_name.split_once("::") {
+            Some((namespace, rest)) => {
+                let import = self.get_import(namespace)?;
+                import.resolve_rule(rest).map(|resolved| ResolvedRule {
+                    rule_sets: resolved.rule_sets.or_else(|| Some(vec![import.clone()])),
+                    ..resolved
+                })
+            },
+            None => self.get_rule_by_name(rule_name).map(|(rule_name, parser_operator)| ResolvedRule {
+                parameters: self.get_rule_parameters(&rule_name).to_vec(),
+                rule_name,
+                parser_operator,
+                rule_sets: None,
+            }),
+        }
+    }
+    /// The rules defined by this grammar itself, in order; not including those inherited from the base grammar
+    pub fn get_rules(&self) -> &[Rule<S>] {
+        &self.rule_set
+    }
+    /// All rules that can be referenced in this grammar without a namespace;
+    /// its own rules followed by the rules it inherits from the base grammar and does not override
+    pub fn get_all_rules(&self) -> Vec<(Rc<String>, Rc<dyn Parser<S>>)> {
+        let mut rules = self.rule_set.clone();
+        if let Some(base) = &self.base {
+            rules.extend(
+                base
+                .get_all_rules()
+                .into_iter()
+                .filter(|(rule_name, _parser_operator)| !self.has_own_rule(rule_name))
+            );
+        }
+        rules
+    }
+    /// The grammars imported by this grammar and by its base grammar, with their namespaces
+    pub fn get_all_imports(&self) -> Vec<(String, Rc<Grammar<S>>)> {
+        let mut imports = self.imports.clone();
+        if let Some(base) = &self.base {
+            imports.extend(
+                base
+                .get_all_imports()
+                .into_iter()
+                .filter(|(namespace, _grammar)| !self.imports.iter().any(|(each_namespace, _grammar)| each_namespace == namespace))
+            );
+        }
+        imports
+    }
+    pub fn get_base(&self) -> Option<&Rc<Grammar<S>>> {
+        self.base.as_ref()
+    }
+    /// The starting rule given to `Grammar::new`, if any; see `get_starting_rule_name` for the rule actually used
+    pub fn get_declared_starting_rule_name(&self) -> Option<&str>{
+        self.starting_rule_name.as_deref()
+    }
+    pub fn get_starting_rule_name(&self) -> Option<Rc<String>>{
+        match (&self.starting_rule_name, &self.base) {
+            (Some(rule_name), _)  => Some(Rc::new(rule_name.clone())),
+            (None, Some(base))    => base.get_starting_rule_name(),
+            (None, None)          => self.rule_set.first().map(|(rule_name, _parser_operator)| rule_name.clone()),
+        }
+    }
+    pub fn get_starting_rule(&self) -> Option<(Rc<String>, Rc<dyn Parser<S>>)>{
+        self
+        .get_starting_rule_name()
+        .and_then(|rule_name|self.get_rule_by_name(&rule_name))
         .or(self.rule_set.first().cloned())
     }
 }
 
-impl Parser for Grammar {
+impl<S> fmt::Debug for Grammar<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Grammar")
+            .field("id", &self.id)
+            .field("rule_set", &self.rule_set)
+            .field("rule_parameters", &self.rule_parameters)
+            .field("starting_rule_name", &self.starting_rule_name)
+            .field("imports", &self.imports)
+            .field("base", &self.base)
+            .finish()
+    }
+}
+
+impl<S> Parser<S> for Grammar<S> {
     fn get_id(&self)->usize {
         self.id.id()
     }
-    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext>, start_position: usize) -> Option<Rc<ParserMatch>> {
+    fn kind(&self)->OpKind<'_, S> {
+        OpKind::Grammar(self)
+    }
+    /// Writes the whole grammar, one rule per line
+    fn to_peg_string(&self)->String{
+        self.to_string()
+    }
+    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<MatchId> {
         context.push_rule_set(self.clone());
         if let Some((rule_name, parser_operator)) = context.get_starting_rule(){
+            if !context.get_rule_parameters(&rule_name).is_empty() {
+                panic!("Starting rule must not take parameters")
+            }
+            let previous_instantiation = context.set_instantiation(None);
+            let capture_scope = context.open_capture_scope();
             let result = parser_operator
-                .parse(context, start_position)
-                .map(|res| res.with_label(rule_name));
+                .parse_match(context, start_position)
+                .map(|res| context.relabel_match(res, Symbol::intern(&rule_name)));
+            context.close_capture_scope(capture_scope);
+            context.set_instantiation(previous_instantiation);
             context.pop_rule_set();
             result
         }else{
@@ -59,4 +246,97 @@ impl Parser for Grammar {
             panic!("Starting rule not found")
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::*;
+
+    fn json() -> Rc<Grammar> {
+        Rc::new(Grammar::new(
+            None,
+            vec![
+                ("Value",  alt!(rul!("String"), rul!("Number"))),
+                ("String", reg!("\"[^\"]*\"")),
+                ("Number", reg!("[0-9]+")),
+            ]
+        ))
+    }
+
+    #[test]
+    fn namespaced_rule_resolves_in_imported_grammar() {
+        let gram = Rc::new(
+            Grammar::new(
+                None,
+                vec![
+                    ("Prog",   seq!(rul!("Number"), lit!("="), rul!("json::Value"))),
+                    // Must not be visible from inside the json grammar
+                    ("Number", lit!("x")),
+                ]
+            ).with_import("json", json())
+        );
+        let mut context = Box::new(ParserContext::new("x=123"));
+        let result = gram.clone().parse(&mut context, 0).unwrap();
+        assert_eq!(result.len(), 5);
+        assert_eq!(result.get_child(2).unwrap().get_label(), Some("json::Value"));
+
+        let mut context = Box::new(ParserContext::new("x=x"));
+        assert!(gram.parse(&mut context, 0).is_none());
+    }
+
+    #[test]
+    fn nested_namespaces_resolve_through_each_import() {
+        let config = Rc::new(Grammar::new(None, vec![("Entry", seq!(reg!("[a-z]+"), lit!(":"), rul!("json::Value")))]).with_import("json", json()));
+        let gram = Rc::new(Grammar::new(None, vec![("Prog", rul!("config::json::Number"))]).with_import("config", config));
+        let mut context = Box::new(ParserContext::new("42"));
+        let result = gram.parse(&mut context, 0).unwrap();
+        assert_eq!(result.len(), 2);
+    }
+
+    #[test]
+    fn derived_grammar_overrides_base_rules() {
+        let sql = Rc::new(Grammar::new(
+            Some("Select"),
+            vec![
+                ("Identifier", reg!("[a-z]+")),
+                ("Select",     seq!(lit!("SELECT "), rul!("Identifier"), qtt!(seq!(lit!(", "), rul!("Identifier")), 0, None))),
+            ]
+        ));
+        let dialect = Rc::new(Grammar::new(None, vec![("Identifier", alt!(reg!("[a-z]+"), reg!("\\[[^\\]]*\\]")))]).with_base(sql.clone()));
+
+        let mut context = Box::new(ParserContext::new("SELECT a, [b c]"));
+        let result = sql.parse(&mut context, 0).unwrap();
+        assert_eq!(result.len(), 8);
+
+        let mut context = Box::new(ParserContext::new("SELECT a, [b c]"));
+        let result = dialect.parse(&mut context, 0).unwrap();
+        assert_eq!(result.len(), 15);
+        assert_eq!(result.get_label(), Some("Select"));
+    }
+
+    #[test]
+    fn nested_grammar_falls_back_to_enclosing_rules() {
+        let inner = Rc::new(Grammar::new(
+            None,
+            vec![
+                ("Inner", seq!(rul!("Digit"), rul!("Outer"))),
+                ("Digit", reg!("[0-9]")),
+            ]
+        ));
+        let gram = Rc::new(Grammar::new(
+            None,
+            vec![
+                ("Prog",  seq!(lit!("<"), inner, lit!(">"))),
+                ("Outer", seq!(lit!("."), qtt!(rul!("Digit"), 0, Some(1)))),
+                // Shadowed by the inner grammar, but used by `Outer`
+                ("Digit", lit!("x")),
+            ]
+        ));
+        let mut context = Box::new(ParserContext::new("<1.x>"));
+        let result = gram.clone().parse(&mut context, 0).unwrap();
+        assert_eq!(result.len(), 5);
+        let mut context = Box::new(ParserContext::new("<1.1>"));
+        assert!(gram.parse(&mut context, 0).is_none());
+    }
+}
@@ -0,0 +1,181 @@
+//! Ops for languages where block structure is given by indentation (the off-side rule), e.g.
+//!
+//! ```text
+//! Block <- Indent Statement (Samedent Statement)* Dedent
+//! ```
+//!
+//! Each op is positioned at the start of a line, and measures the indentation there
+//! by counting the spaces and tabs (one column each) before the first other character.
+//! The widths of the enclosing blocks are kept on a stack in the `ParserState`,
+//! so they are restored when the parser backtracks.
+use std::rc::Rc;
+use crate::core::{
+    OpKind,
+    OpaqueIdentifier,
+    Parser,
+    ParserContext,
+    MatchId,
+};
+
+fn indentation_width(input: &[u8], start_position: usize) -> usize {
+    input[start_position..]
+        .iter()
+        .take_while(|byte| **byte == b' ' || **byte == b'\t')
+        .count()
+}
+
+#[derive(Debug)]
+/// Matches indentation wider than the current block, and starts a new block at that width
+pub struct Indent {
+    id                  : OpaqueIdentifier,
+}
+impl Indent{
+    pub fn new() -> Self {
+        Self {
+            id: OpaqueIdentifier::new(),
+        }
+    }
+}
+impl Default for Indent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<S> Parser<S> for Indent{
+    fn get_id(&self)->usize {
+        self.id.id()
+    }
+    fn kind(&self)->OpKind<'_, S> {
+        OpKind::Indent
+    }
+    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<MatchId> {
+        let width = indentation_width(context.get_input(), start_position);
+        if width > context.get_indentation() {
+            context.push_indentation(width);
+            Some(context.new_match(start_position, start_position + width, None, &[]))
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug)]
+/// Matches indentation of exactly the width of the current block
+pub struct Samedent {
+    id                  : OpaqueIdentifier,
+}
+impl Samedent{
+    pub fn new() -> Self {
+        Self {
+            id: OpaqueIdentifier::new(),
+        }
+    }
+}
+impl Default for Samedent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<S> Parser<S> for Samedent{
+    fn get_id(&self)->usize {
+        self.id.id()
+    }
+    fn kind(&self)->OpKind<'_, S> {
+        OpKind::Samedent
+    }
+    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<MatchId> {
+        let width = indentation_width(context.get_input(), start_position);
+        if width == context.get_indentation() {
+            Some(context.new_match(start_position, start_position + width, None, &[]))
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug)]
+/// Ends the current block without consuming input.
+/// Matches where the indentation is narrower than the current block, or at the end of the text.
+pub struct Dedent {
+    id                  : OpaqueIdentifier,
+}
+impl Dedent{
+    pub fn new() -> Self {
+        Self {
+            id: OpaqueIdentifier::new(),
+        }
+    }
+}
+impl Default for Dedent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<S> Parser<S> for Dedent{
+    fn get_id(&self)->usize {
+        self.id.id()
+    }
+    fn kind(&self)->OpKind<'_, S> {
+        OpKind::Dedent
+    }
+    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<MatchId> {
+        let input = context.get_input();
+        let at_end = start_position == input.len();
+        let width = indentation_width(input, start_position);
+        match context.pop_indentation() {
+            Some(block_width) if at_end || width < block_width => {
+                Some(context.new_match(start_position, start_position, None, &[]))
+            },
+            _ => None,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::*;
+
+    fn python_like() -> Rc<Grammar> {
+        Rc::new(Grammar::new(
+            None,
+            vec![
+                ("Program",   seq!(rul!("Statement"), qtt!(seq!(sdt!(), rul!("Statement")), 0, None))),
+                ("Statement", alt!(
+                    seq!(rul!("Name"), lit!(":\n"), rul!("Block")),
+                    seq!(rul!("Name"), lit!("\n"))
+                )),
+                ("Block",     seq!(idt!(), rul!("Statement"), qtt!(seq!(sdt!(), rul!("Statement")), 0, None), ddt!())),
+                ("Name",      reg!("[a-z]+")),
+            ]
+        ))
+    }
+
+    #[test]
+    fn blocks_follow_indentation() {
+        let text = "a:\n  b\n  c:\n    d\n  e\nf\n";
+        let mut context = Box::new(ParserContext::new(text));
+        let result = python_like().parse(&mut context, 0).unwrap();
+        assert_eq!(result.len(), text.len());
+    }
+
+    #[test]
+    fn inconsistent_dedent_is_rejected() {
+        // `c` is neither in the block of `a` nor at the top level
+        let text = "a:\n    b\n  c\n";
+        let mut context = Box::new(ParserContext::new(text));
+        let result = python_like().parse(&mut context, 0).unwrap();
+        assert_eq!(result.len(), "a:\n    b\n".len());
+    }
+
+    #[test]
+    fn indentation_is_restored_when_backtracking() {
+        // The first alternative pushes an indentation level before failing
+        let seq = seq!(alt!(seq!(idt!(), lit!("x")), lit!("  y\n")), sdt!(), lit!("z"));
+        let mut context = Box::new(ParserContext::new("  y\nz"));
+        let result = seq.parse(&mut context, 0).unwrap();
+        assert_eq!(result.len(), 5);
+        assert_eq!(context.get_indentation(), 0);
+    }
+}
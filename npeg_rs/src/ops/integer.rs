@@ -0,0 +1,135 @@
+use std::rc::Rc;
+use crate::core::{
+    OpKind,
+    OpaqueIdentifier,
+    Parser,
+    ParserContext,
+    MatchId,
+};
+
+/// The byte order of an `Integer`, or of the length read by `Take`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endianness {
+    /// Most significant byte first; network byte order
+    Big,
+    /// Least significant byte first
+    Little,
+}
+impl Endianness {
+    /// Reads `bytes` as an unsigned integer; panics if there are more than 8
+    pub fn read(&self, bytes: &[u8]) -> u64 {
+        assert!(bytes.len() <= 8, "An integer of {} bytes does not fit in a u64", bytes.len());
+        let fold = |value: u64, byte: &u8| value << 8 | *byte as u64;
+        match self {
+            Endianness::Big => bytes.iter().fold(0, fold),
+            Endianness::Little => bytes.iter().rev().fold(0, fold),
+        }
+    }
+}
+
+#[derive(Debug)]
+/// Matches an unsigned integer of a fixed number of bytes; for byte input, see `ParserContext::from_bytes()`.
+///
+/// Any value matches, unless limited by `with_range()`.
+pub struct Integer {
+    id                  : OpaqueIdentifier,
+    width               : usize,
+    endianness          : Endianness,
+    minimum             : u64,
+    maximum             : u64,
+}
+impl Integer{
+    /// `width` is in bytes, from 1 to 8
+    pub fn new(width: usize, endianness: Endianness) -> Self {
+        if !(1..=8).contains(&width) {
+            panic!("Integer width must be from 1 to 8 bytes, not {}", width)
+        }
+        Self {
+            id:OpaqueIdentifier::new(),
+            width,
+            endianness,
+            minimum: 0,
+            maximum: u64::MAX,
+        }
+    }
+    /// Only matches values from `minimum` to `maximum`, inclusive; e.g. a magic number or a supported version
+    pub fn with_range(mut self, minimum: u64, maximum: u64) -> Self {
+        self.minimum = minimum;
+        self.maximum = maximum;
+        self
+    }
+    pub fn get_width(&self) -> usize {
+        self.width
+    }
+    pub fn get_endianness(&self) -> Endianness {
+        self.endianness
+    }
+}
+impl<S> Parser<S> for Integer{
+    fn get_id(&self)->usize {
+        self.id.id()
+    }
+    fn kind(&self)->OpKind<'_, S> {
+        OpKind::Integer {
+            width: self.width,
+            endianness: self.endianness,
+            minimum: self.minimum,
+            maximum: self.maximum,
+        }
+    }
+    fn describe_failure(&self)->Option<String> {
+        let description = format!("a {} byte {:?} endian integer", self.width, self.endianness);
+        if self.minimum == 0 && self.maximum == u64::MAX {
+            Some(description)
+        } else {
+            Some(format!("{} from {} to {}", description, self.minimum, self.maximum))
+        }
+    }
+    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<MatchId> {
+        let end_position = start_position + self.width;
+        let bytes = context.get_input().get(start_position..end_position)?;
+        let value = self.endianness.read(bytes);
+        if value < self.minimum || value > self.maximum {
+            return None;
+        }
+        Some(context.new_match(start_position, end_position, None, &[]))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::*;
+
+    #[test]
+    fn integers_are_read_in_either_byte_order() {
+        assert_eq!(Endianness::Big.read(&[0x12, 0x34]), 0x1234);
+        assert_eq!(Endianness::Little.read(&[0x12, 0x34]), 0x3412);
+        assert_eq!(Endianness::Little.read(&[0xff; 8]), u64::MAX);
+        assert_eq!(Endianness::Big.read(&[]), 0);
+    }
+
+    #[test]
+    fn integer_matches_values_in_range() {
+        let version = Rc::new(Integer::new(2, Endianness::Little).with_range(1, 3));
+        assert_eq!(parse_bytes(version.clone(), &[0x02, 0x00]).unwrap().unwrap().get_end_position(), 2);
+        assert!(parse_bytes(version.clone(), &[0x00, 0x02]).unwrap().is_none());
+        assert!(parse_bytes(version, &[0x02]).unwrap().is_none());
+    }
+
+    #[test]
+    fn header_is_parsed_with_byte_literals_and_classes() {
+        let header = seq!(
+            byt!(b"\x89PNG"),
+            lbl!(int!(4, Big), "size"),
+            lbl!(qtt!(bcl!(b'a'..=b'z', b'A'..=b'Z'), 4, Some(8)), "kind")
+        );
+        let input = b"\x89PNG\x00\x00\x01\x00IHDR\xff";
+        let result = parse_bytes(header.clone(), input).unwrap().unwrap();
+        assert_eq!(result.get_end_position(), 12);
+        assert_eq!(result.get_child(2).unwrap().text(), "IHDR");
+        assert_eq!(Endianness::Big.read(result.get_child(1).unwrap().bytes()), 256);
+        assert!(parse_bytes(header, b"\x89PNG\x00\x00\x01\x00IH\x00R").unwrap().is_none());
+    }
+}
@@ -1,32 +1,50 @@
+use std::fmt;
 use std::rc::Rc;
 use crate::core::{
+    OpKind,
     OpaqueIdentifier,
     Parser,
     ParserContext,
-    ParserMatch,
+    MatchId,
+    Symbol,
 };
-#[derive(Debug)]
 /// label:[exp]
-pub struct Label {
+pub struct Label<S = ()> {
     id                  : OpaqueIdentifier,
-    child               : Rc<dyn Parser>,
-    label               : Rc<String>,
+    child               : Rc<dyn Parser<S>>,
+    label               : Symbol,
 }
 
-impl Label{
-    pub fn new(child: Rc<dyn Parser>, label: &str) -> Self {
+impl<S> Label<S>{
+    pub fn new(child: Rc<dyn Parser<S>>, label: &str) -> Self {
         Self{
             id: OpaqueIdentifier::new(),
-            child: child,
-            label: Rc::new(label.into())
+            child,
+            label: Symbol::intern(label)
         }
     }
 }
-impl Parser for Label{
+impl<S> fmt::Debug for Label<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Label")
+            .field("id", &self.id)
+            .field("child", &self.child)
+            .field("label", &self.label)
+            .finish()
+    }
+}
+
+impl<S> Parser<S> for Label<S>{
     fn get_id(&self)->usize {
         self.id.id()
     }
-    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext>, start_position: usize) -> Option<Rc<ParserMatch>> {
-        self.child.clone().parse(context, start_position).map(|item| item.with_label(self.label.clone()))
+    fn kind(&self)->OpKind<'_, S> {
+        OpKind::Label {
+            child: &self.child,
+            label: self.label.as_str(),
+        }
+    }
+    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<MatchId> {
+        self.child.clone().parse_match(context, start_position).map(|item| context.relabel_match(item, self.label))
     }
 }
\ No newline at end of file
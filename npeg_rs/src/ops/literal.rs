@@ -1,40 +1,65 @@
 use std::rc::Rc;
 use crate::core::{
+    OpKind,
     OpaqueIdentifier,
     Parser,
     ParserContext,
-    ParserMatch,
+    MatchId,
 };
 #[derive(Debug)]
 pub struct Literal {
     id                  : OpaqueIdentifier,
-    literal_text        : String,
+    literal_bytes       : Vec<u8>,
+    /// `None` if the literal was made from bytes which are not UTF-8
+    literal_text        : Option<String>,
 }
 impl Literal{
     pub fn new(literal_text: &str) -> Self {
-        if literal_text.len() == 0 {
+        if literal_text.is_empty() {
             panic!("Zero Length Literal is not permitted")
         }
         Self {
             id:OpaqueIdentifier::new(),
-            literal_text: literal_text.into(),
+            literal_bytes: literal_text.as_bytes().to_vec(),
+            literal_text: Some(literal_text.into()),
         }
     }
+    /// For byte input; see `ParserContext::from_bytes()`.
+    /// Bytes which are not UTF-8 are seen by tools as `OpKind::ByteLiteral`.
+    pub fn from_bytes(literal_bytes: &[u8]) -> Self {
+        if literal_bytes.is_empty() {
+            panic!("Zero Length Literal is not permitted")
+        }
+        Self {
+            id:OpaqueIdentifier::new(),
+            literal_bytes: literal_bytes.to_vec(),
+            literal_text: String::from_utf8(literal_bytes.to_vec()).ok(),
+        }
+    }
+    pub fn get_bytes(&self) -> &[u8] {
+        &self.literal_bytes
+    }
 }
-impl Parser for Literal{
+impl<S> Parser<S> for Literal{
     fn get_id(&self)->usize {
         self.id.id()
     }
-    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext>, start_position: usize) -> Option<Rc<ParserMatch>> {
-        if context.get_full_text()[start_position..].starts_with(&self.literal_text[..]) {
-            Some(ParserMatch::new(
-                start_position,
-                start_position + self.literal_text.len(),
-                None,
-                vec![].into()
-            ))
-        } else {
-            None
+    fn kind(&self)->OpKind<'_, S> {
+        match &self.literal_text {
+            Some(literal_text) => OpKind::Literal(literal_text),
+            None => OpKind::ByteLiteral(&self.literal_bytes),
         }
     }
+    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<MatchId> {
+        let end_position = match &self.literal_text {
+            Some(literal_text) => context.match_text(start_position, literal_text)?,
+            None => {
+                if !context.get_input()[start_position..].starts_with(&self.literal_bytes) {
+                    return None;
+                }
+                start_position + self.literal_bytes.len()
+            },
+        };
+        Some(context.new_match(start_position, end_position, None, &[]))
+    }
 }
\ No newline at end of file
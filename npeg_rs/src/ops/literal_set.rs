@@ -0,0 +1,106 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
+use crate::core::{
+    OpKind,
+    OpaqueIdentifier,
+    Parser,
+    ParserContext,
+    MatchId,
+};
+#[derive(Debug, Default)]
+struct TrieNode {
+    children            : BTreeMap<u8, usize>,
+    /// The index of the literal which ends here
+    literal_index       : Option<usize>,
+}
+/// Matches the first of a list of literals which the input starts with; the same as an `Alternation` of `Literal`s,
+/// with the same matches, but decided by one walk of a trie. See `Grammar::optimize()`.
+pub struct LiteralSet<S = ()> {
+    id                  : OpaqueIdentifier,
+    /// The root is the first node
+    trie                : Vec<TrieNode>,
+    /// The equivalent literals, for `kind()`
+    children            : Vec<Rc<dyn Parser<S>>>,
+}
+impl<S> LiteralSet<S>{
+    pub fn new(literals: &[&str]) -> Self {
+        if literals.is_empty() {
+            panic!("Empty LiteralSet is not permitted")
+        }
+        let mut trie = vec![TrieNode::default()];
+        for (literal_index, literal_text) in literals.iter().enumerate() {
+            let mut node = 0;
+            for byte in literal_text.bytes() {
+                node = match trie[node].children.get(&byte) {
+                    Some(child) => *child,
+                    None => {
+                        trie.push(TrieNode::default());
+                        let child = trie.len() - 1;
+                        trie[node].children.insert(byte, child);
+                        child
+                    },
+                };
+            }
+            // A repeated literal can never be chosen; the first one wins
+            trie[node].literal_index.get_or_insert(literal_index);
+        }
+        Self {
+            id:OpaqueIdentifier::new(),
+            trie,
+            children: literals.iter().map(|literal_text| Rc::new(crate::ops::Literal::new(literal_text)) as Rc<dyn Parser<S>>).collect(),
+        }
+    }
+}
+impl<S> fmt::Debug for LiteralSet<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LiteralSet")
+            .field("id", &self.id)
+            .field("trie", &self.trie)
+            .field("children", &self.children)
+            .finish()
+    }
+}
+
+impl<S> Parser<S> for LiteralSet<S>{
+    fn get_id(&self)->usize {
+        self.id.id()
+    }
+    /// Seen by tools as the `Alternation` it replaces
+    fn kind(&self)->OpKind<'_, S> {
+        OpKind::Alternation(&self.children)
+    }
+    fn describe_failure(&self)->Option<String> {
+        Some(self.to_peg_string())
+    }
+    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<MatchId> {
+        if context.is_nfc() {
+            // Canonically equivalent text may not follow the trie
+            let end_position = self.children.iter().find_map(|child| match child.kind() {
+                OpKind::Literal(literal_text) => context.match_text(start_position, literal_text),
+                _ => None,
+            })?;
+            let literal_match = context.new_match(start_position, end_position, None, &[]);
+            return Some(context.new_match(start_position, end_position, None, &[literal_match]));
+        }
+        // Of the literals the input starts with, an ordered choice picks the one listed first, not the longest
+        let mut chosen: Option<(usize, usize)> = None;
+        let mut node = 0;
+        for (length, byte) in context.get_input()[start_position..].iter().copied().enumerate() {
+            match self.trie[node].children.get(&byte) {
+                Some(child) => node = *child,
+                None => break,
+            }
+            if let Some(literal_index) = self.trie[node].literal_index {
+                if chosen.is_none_or(|(chosen_index, _)| literal_index < chosen_index) {
+                    chosen = Some((literal_index, length + 1));
+                }
+            }
+        }
+        let (_, length) = chosen?;
+        let end_position = start_position + length;
+        // Shaped like the match of an `Alternation` of `Literal`s
+        let literal_match = context.new_match(start_position, end_position, None, &[]);
+        Some(context.new_match(start_position, end_position, None, &[literal_match]))
+    }
+}
@@ -1,43 +1,62 @@
+use std::fmt;
 use std::rc::Rc;
 use crate::core::{
+    OpKind,
     OpaqueIdentifier,
     Parser,
     ParserContext,
-    ParserMatch,
+    MatchId,
 };
-#[derive(Debug)]
-pub struct Lookahead {
+pub struct Lookahead<S = ()> {
     id                  : OpaqueIdentifier,
-    child               : Rc<dyn Parser>,
-    scout               : Rc<dyn Parser>,
+    child               : Rc<dyn Parser<S>>,
+    scout               : Rc<dyn Parser<S>>,
     accept_match        : bool,
 }
-impl Lookahead{
+impl<S> Lookahead<S>{
     pub fn new(
-        child: Rc<dyn Parser>,
-        scout: Rc<dyn Parser>,
+        child: Rc<dyn Parser<S>>,
+        scout: Rc<dyn Parser<S>>,
         accept_match: bool,
     ) -> Self {
         Self {
             id: OpaqueIdentifier::new(),
-            child: child.into(),
-            scout: scout.into(),
+            child,
+            scout,
             accept_match,
         }
     }
 }
 
-impl Parser for Lookahead{
+impl<S> fmt::Debug for Lookahead<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Lookahead")
+            .field("id", &self.id)
+            .field("child", &self.child)
+            .field("scout", &self.scout)
+            .field("accept_match", &self.accept_match)
+            .finish()
+    }
+}
+
+impl<S> Parser<S> for Lookahead<S>{
     fn get_id(&self)->usize {
         self.id.id()
     }
-    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext>, start_position: usize) -> Option<Rc<ParserMatch>> {
-        let mut end_position = start_position;
-        let res_child = self.child.clone().parse(context, start_position).as_ref().map(|parser_match| {
-            end_position += parser_match.len();
-            parser_match.clone()
-        });
-        let res_scout = self.scout.clone().parse(context, end_position);
+    fn kind(&self)->OpKind<'_, S> {
+        OpKind::Lookahead {
+            child: &self.child,
+            scout: &self.scout,
+            accept_match: self.accept_match,
+        }
+    }
+    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<MatchId> {
+        let res_child = self.child.clone().parse_match(context, start_position);
+        let end_position = res_child.map_or(start_position, |parser_match| context.get_match_end_position(parser_match));
+        // The scout does not consume any input, so it must not leave captures behind either
+        let state = context.get_state();
+        let res_scout = self.scout.clone().parse_match(context, end_position);
+        context.set_state(state);
         if (self.accept_match && res_scout.is_some()) || (!self.accept_match && res_scout.is_none()) {
             res_child
         } else {
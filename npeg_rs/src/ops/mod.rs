@@ -1,22 +1,56 @@
-/// These are sometimes referred to as non-terminals or terminals depending if they have children
+//! These are sometimes referred to as non-terminals or terminals depending if they have children
 
 
+pub(crate) mod action;
 pub(crate) mod alternation;
+#[cfg(feature = "unicode-segmentation")]
+pub(crate) mod any_grapheme;
+pub(crate) mod back_reference;
+pub(crate) mod byte_class;
+pub(crate) mod capture;
+pub(crate) mod char_class;
+pub(crate) mod counted;
 pub(crate) mod grammar;
+pub(crate) mod indentation;
+pub(crate) mod integer;
 pub(crate) mod label;
 pub(crate) mod literal;
+pub(crate) mod literal_set;
 pub(crate) mod lookahead;
+pub(crate) mod operator_table;
+pub(crate) mod predicate;
 pub(crate) mod quantity;
 pub(crate) mod regex;
+pub(crate) mod rule_call;
 pub(crate) mod rule_reference;
+pub(crate) mod sep_by;
 pub(crate) mod sequence;
+pub(crate) mod take;
+pub(crate) mod until;
 
+pub use self::action         :: Action;
 pub use self::alternation    :: Alternation;
-pub use self::grammar        :: Grammar;
+#[cfg(feature = "unicode-segmentation")]
+pub use self::any_grapheme   :: AnyGrapheme;
+pub use self::back_reference :: BackReference;
+pub use self::byte_class     :: ByteClass;
+pub use self::capture        :: Capture;
+pub use self::char_class     :: CharClass;
+pub use self::counted        :: Counted;
+pub use self::grammar        :: {Grammar, ResolvedRule};
+pub use self::indentation    :: {Dedent, Indent, Samedent};
+pub use self::integer        :: {Endianness, Integer};
 pub use self::label          :: Label;
 pub use self::literal        :: Literal;
+pub use self::literal_set    :: LiteralSet;
 pub use self::lookahead      :: Lookahead;
+pub use self::operator_table :: {Associativity, Operator, OperatorTable};
+pub use self::predicate      :: {Predicate, PredicateFn};
 pub use self::quantity       :: Quantity;
 pub use self::regex          :: Regex;
+pub use self::rule_call      :: RuleCall;
 pub use self::rule_reference :: RuleReference;
-pub use self::sequence       :: Sequence;
\ No newline at end of file
+pub use self::sep_by         :: SepBy;
+pub use self::sequence       :: Sequence;
+pub use self::take           :: Take;
+pub use self::until          :: Until;
\ No newline at end of file
@@ -0,0 +1,343 @@
+use std::fmt;
+use std::rc::Rc;
+use crate::core::{
+    OpKind,
+    OpaqueIdentifier,
+    Parser,
+    ParserContext,
+    MatchId,
+    Symbol,
+};
+
+/// How a chain of infix operators of the same precedence nests; see `OperatorTable::with_infix()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Associativity {
+    /// `a - b - c` is `(a - b) - c`
+    Left,
+    /// `a ^ b ^ c` is `a ^ (b ^ c)`
+    Right,
+}
+
+/// An operator of an `OperatorTable`
+pub struct Operator<S = ()> {
+    parser              : Rc<dyn Parser<S>>,
+    label               : Symbol,
+    precedence          : u32,
+    associativity       : Associativity,
+}
+impl<S> Operator<S> {
+    pub fn get_parser(&self) -> &Rc<dyn Parser<S>> {
+        &self.parser
+    }
+    pub fn get_label(&self) -> &'static str {
+        self.label.as_str()
+    }
+    pub fn get_precedence(&self) -> u32 {
+        self.precedence
+    }
+    /// Prefix operators are `Right` and postfix operators are `Left`
+    pub fn get_associativity(&self) -> Associativity {
+        self.associativity
+    }
+}
+
+impl<S> fmt::Debug for Operator<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Operator")
+            .field("parser", &self.parser)
+            .field("label", &self.label)
+            .field("precedence", &self.precedence)
+            .field("associativity", &self.associativity)
+            .finish()
+    }
+}
+
+/// Parses expressions of atoms and operators by precedence climbing, like a Pratt parser,
+/// so that each precedence level does not need a rule of its own.
+///
+/// The match has one child, the expression. Each operator is an op, e.g. `lit!("+")`, and is labeled;
+/// an operator's match is labeled with it and has as children,
+/// in order, the operand and operator matches: `[operator, operand]` for prefix operators, `[left, operator, right]`
+/// for infix operators and `[operand, operator]` for postfix operators. Atoms keep the match of the atom op.
+///
+/// Operators with a higher precedence bind more tightly. Where several operators of the same kind match,
+/// the first one added is used, as with an `Alternation`, so `**` should be added before `*`.
+/// An operator whose match consumes nothing is treated as not matching.
+///
+/// ```
+/// # use npeg_rs::*;
+/// # use std::rc::Rc;
+/// let expression = Rc::new(
+///     OperatorTable::new(reg!("[0-9]+"))
+///         .with_infix(lit!("+"), "add", 1, Associativity::Left)
+///         .with_infix(lit!("*"), "multiply", 2, Associativity::Left)
+///         .with_prefix(lit!("-"), "negate", 3)
+/// );
+/// let result = parse(expression, "1+-2*3").unwrap().unwrap();
+/// let sum = result.get_child(0).unwrap();
+/// assert_eq!(sum.get_label(), Some("add"));
+/// assert_eq!(sum.get_child(2).unwrap().get_label(), Some("multiply"));
+/// ```
+pub struct OperatorTable<S = ()> {
+    id                  : OpaqueIdentifier,
+    atom                : Rc<dyn Parser<S>>,
+    prefix              : Vec<Operator<S>>,
+    infix               : Vec<Operator<S>>,
+    postfix             : Vec<Operator<S>>,
+}
+
+impl<S> OperatorTable<S> {
+    pub fn new(atom: Rc<dyn Parser<S>>) -> Self {
+        Self{
+            id: OpaqueIdentifier::new(),
+            atom,
+            prefix: vec![],
+            infix: vec![],
+            postfix: vec![],
+        }
+    }
+    pub fn with_prefix(mut self, operator: Rc<dyn Parser<S>>, label: &str, precedence: u32) -> Self {
+        self.prefix.push(Operator { parser: operator, label: Symbol::intern(label), precedence, associativity: Associativity::Right });
+        self
+    }
+    pub fn with_infix(mut self, operator: Rc<dyn Parser<S>>, label: &str, precedence: u32, associativity: Associativity) -> Self {
+        self.infix.push(Operator { parser: operator, label: Symbol::intern(label), precedence, associativity });
+        self
+    }
+    pub fn with_postfix(mut self, operator: Rc<dyn Parser<S>>, label: &str, precedence: u32) -> Self {
+        self.postfix.push(Operator { parser: operator, label: Symbol::intern(label), precedence, associativity: Associativity::Left });
+        self
+    }
+    pub fn get_atom(&self) -> &Rc<dyn Parser<S>> {
+        &self.atom
+    }
+    pub fn get_prefix_operators(&self) -> &[Operator<S>] {
+        &self.prefix
+    }
+    pub fn get_infix_operators(&self) -> &[Operator<S>] {
+        &self.infix
+    }
+    pub fn get_postfix_operators(&self) -> &[Operator<S>] {
+        &self.postfix
+    }
+
+    /// The first of `operators` which matches at `start_position`, and its match.
+    /// A match which consumes nothing does not count, as the operator would apply again and again at the same position.
+    fn find_operator<'a>(operators: &'a [Operator<S>], context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<(&'a Operator<S>, MatchId)> {
+        for operator in operators {
+            let state = context.get_state();
+            if let Some(operator_match) = operator.parser.clone().parse_match(context, start_position) {
+                if context.get_match_end_position(operator_match) > start_position {
+                    return Some((operator, operator_match));
+                }
+                context.set_state(state);
+            }
+        }
+        None
+    }
+
+    /// An expression whose operators, outside of any operand of a prefix operator, all have at least `minimum_precedence`
+    fn parse_expression(&self, context: &mut Box<ParserContext<S>>, start_position: usize, minimum_precedence: u32) -> Option<MatchId> {
+        // Nested operands count towards the maximum depth, like nested ops
+        if !context.enter(start_position) {
+            return None;
+        }
+        let result = self.parse_operand(context, start_position)
+            .and_then(|operand| self.parse_operators(context, operand, minimum_precedence));
+        context.leave();
+        result
+    }
+
+    fn parse_operand(&self, context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<MatchId> {
+        let state = context.get_state();
+        if let Some((operator, operator_match)) = Self::find_operator(&self.prefix, context, start_position) {
+            let operator_end = context.get_match_end_position(operator_match);
+            if let Some(operand) = self.parse_expression(context, operator_end, operator.precedence) {
+                let end_position = context.get_match_end_position(operand);
+                return Some(context.new_match(start_position, end_position, Some(operator.label), &[operator_match, operand]));
+            }
+            // The atom may start like a prefix operator, e.g. a negative number
+            context.set_state(state);
+        }
+        self.atom.clone().parse_match(context, start_position)
+    }
+
+    fn parse_operators(&self, context: &mut Box<ParserContext<S>>, mut left: MatchId, minimum_precedence: u32) -> Option<MatchId> {
+        let start_position = context.get_arena().get(left).get_start_position();
+        loop {
+            let position = context.get_match_end_position(left);
+            let state = context.get_state();
+            if let Some((operator, operator_match)) = Self::find_operator(&self.postfix, context, position) {
+                if operator.precedence >= minimum_precedence {
+                    let end_position = context.get_match_end_position(operator_match);
+                    left = context.new_match(start_position, end_position, Some(operator.label), &[left, operator_match]);
+                    continue;
+                }
+                context.set_state(state);
+                break;
+            }
+            let Some((operator, operator_match)) = Self::find_operator(&self.infix, context, position) else {
+                break;
+            };
+            if operator.precedence < minimum_precedence {
+                context.set_state(state);
+                break;
+            }
+            let right_precedence = match operator.associativity {
+                Associativity::Left => operator.precedence.saturating_add(1),
+                Associativity::Right => operator.precedence,
+            };
+            let operator_end = context.get_match_end_position(operator_match);
+            let Some(right) = self.parse_expression(context, operator_end, right_precedence) else {
+                // A trailing operator is left for whatever follows the expression
+                context.set_state(state);
+                break;
+            };
+            let end_position = context.get_match_end_position(right);
+            left = context.new_match(start_position, end_position, Some(operator.label), &[left, operator_match, right]);
+        }
+        Some(left)
+    }
+}
+
+impl<S> fmt::Debug for OperatorTable<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OperatorTable")
+            .field("id", &self.id)
+            .field("atom", &self.atom)
+            .field("prefix", &self.prefix)
+            .field("infix", &self.infix)
+            .field("postfix", &self.postfix)
+            .finish()
+    }
+}
+
+impl<S> Parser<S> for OperatorTable<S>{
+    fn get_id(&self)->usize {
+        self.id.id()
+    }
+    fn kind(&self)->OpKind<'_, S> {
+        OpKind::OperatorTable(self)
+    }
+    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<MatchId> {
+        let expression = self.parse_expression(context, start_position, 0)?;
+        let end_position = context.get_match_end_position(expression);
+        // Held by a match of its own, like the child of an `Alternation`, so that a `Label` or rule does not replace its label
+        Some(context.new_match(start_position, end_position, None, &[expression]))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::*;
+
+    /// The labeled matches within `result`, written with parentheses
+    fn labeled_descendants(result: &Match) -> Vec<String> {
+        result
+            .get_children()
+            .flat_map(|child| match child.get_label() {
+                Some(_) => vec![nesting(&child)],
+                None => labeled_descendants(&child),
+            })
+            .collect()
+    }
+
+    fn nesting(result: &Match) -> String {
+        let operands = labeled_descendants(result);
+        if operands.is_empty() {
+            result.text().to_owned()
+        } else {
+            format!("{}({})", result.get_label().unwrap_or_default(), operands.join(" "))
+        }
+    }
+
+    fn arithmetic() -> Rc<OperatorTable> {
+        Rc::new(
+            OperatorTable::new(alt!(lbl!(reg!("[0-9]+"), "number"), seq!(lit!("("), rul!("Expression"), lit!(")"))))
+                .with_infix(lit!("+"), "add", 1, Associativity::Left)
+                .with_infix(lit!("-"), "subtract", 1, Associativity::Left)
+                .with_infix(lit!("**"), "power", 3, Associativity::Right)
+                .with_infix(lit!("*"), "multiply", 2, Associativity::Left)
+                .with_prefix(lit!("-"), "negate", 4)
+                .with_postfix(lit!("!"), "factorial", 5)
+        )
+    }
+
+    fn parse_arithmetic(text: &str) -> Option<String> {
+        let gram = Rc::new(Grammar::new(Some("Expression"), vec![("Expression", arithmetic())]));
+        let result = parse(gram, text).unwrap()?;
+        // The match of the starting rule holds the expression
+        Some(nesting(&result.get_child(0).unwrap()))
+    }
+
+    #[test]
+    fn operators_nest_by_precedence() {
+        assert_eq!(parse_arithmetic("1+2*3").unwrap(), "add(1 multiply(2 3))");
+        assert_eq!(parse_arithmetic("1*2+3").unwrap(), "add(multiply(1 2) 3)");
+        assert_eq!(parse_arithmetic("(1+2)*3").unwrap(), "multiply(Expression(add(1 2)) 3)");
+    }
+
+    #[test]
+    fn operators_nest_by_associativity() {
+        assert_eq!(parse_arithmetic("1-2-3").unwrap(), "subtract(subtract(1 2) 3)");
+        assert_eq!(parse_arithmetic("1**2**3").unwrap(), "power(1 power(2 3))");
+    }
+
+    #[test]
+    fn prefix_and_postfix_operators() {
+        assert_eq!(parse_arithmetic("-1!").unwrap(), "negate(factorial(1))");
+        assert_eq!(parse_arithmetic("--1*2").unwrap(), "multiply(negate(negate(1)) 2)");
+        assert_eq!(parse_arithmetic("2**-1").unwrap(), "power(2 negate(1))");
+    }
+
+    #[test]
+    fn trailing_operator_is_not_matched() {
+        let mut context = Box::new(ParserContext::new("1+2*"));
+        let result = arithmetic().parse(&mut context, 0).unwrap();
+        assert_eq!(result.get_end_position(), 3);
+        assert!(parse_arithmetic("*1").is_none());
+    }
+
+    #[test]
+    fn tools_see_the_atom_and_operators() {
+        let gram = Grammar::new(Some("Expression"), vec![("Expression", arithmetic())]);
+        let text = gram.to_peg_string();
+        assert_eq!(
+            text,
+            concat!(
+                "Expression <- @operators(number:r\"[0-9]+\" / \"(\" Expression \")\", prefix(\"-\", negate, 4), ",
+                "infix(\"+\", add, 1, left), infix(\"-\", subtract, 1, left), infix(\"**\", power, 3, right), ",
+                "infix(\"*\", multiply, 2, left), postfix(\"!\", factorial, 5))\n",
+            )
+        );
+        let read = Grammar::from_peg_string(&text).unwrap();
+        assert_eq!(read.to_peg_string(), text);
+        let read = Rc::new(read);
+        let result = parse(read, "-1+2**3!").unwrap().unwrap();
+        assert_eq!(nesting(&result.get_child(0).unwrap()), "add(negate(1) power(2 factorial(3)))");
+
+        let analysis = gram.analyze();
+        assert_eq!(analysis.is_rule_nullable("Expression"), Some(false));
+        let first = analysis.get_rule_first_set("Expression").unwrap();
+        assert!(!first.is_any());
+        assert!("-(0123456789".bytes().all(|byte| first.contains(byte)));
+        assert!(!first.contains(b'+') && !first.contains(b'!'));
+        assert!(gram.to_railroad_svg().contains("factorial"));
+    }
+
+    #[test]
+    fn operators_which_match_nothing_are_not_matched() {
+        let table = OperatorTable::new(lbl!(reg!("[0-9]+"), "number"))
+            .with_postfix(qtt!(lit!("!"), 0, None), "factorial", 1)
+            .with_prefix(qtt!(lit!("-"), 0, None), "negate", 2)
+            .with_infix(qtt!(lit!("+"), 0, None), "add", 3, Associativity::Left);
+        let table = Rc::new(table.with_postfix(lit!("?"), "maybe", 1));
+        let nested = |text: &str| nesting(&parse(table.clone(), text).unwrap().unwrap().get_child(0).unwrap());
+        assert_eq!(nested("1"), "1");
+        assert_eq!(nested("1?"), "maybe(1)");
+        assert_eq!(nested("-1!!"), "factorial(negate(1))");
+        assert_eq!(parse(table.clone(), "1 2").unwrap().unwrap().get_end_position(), 1);
+    }
+}
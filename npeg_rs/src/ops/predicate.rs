@@ -0,0 +1,132 @@
+use std::fmt;
+use std::rc::Rc;
+use crate::core::{
+    OpKind,
+    OpaqueIdentifier,
+    Parser,
+    ParserContext,
+    ParserMatch,
+    MatchId,
+};
+
+/// The check made by a `Predicate`; given the child's match, the matched text and the context
+pub type PredicateFn<S = ()> = dyn Fn(&ParserMatch, &str, &ParserContext<S>) -> bool;
+
+/// Matches what its child matches, if `predicate` accepts the match; for checks which a grammar can not express,
+/// such as an identifier which is not a reserved word of the current dialect, or a number less than 256.
+///
+/// A rejected match fails like any other, so the parser backtracks, and `description` is recorded as what was expected;
+/// see `ParserContext::get_farthest_failure()`.
+///
+/// Results are memoized, so the predicate should give the same answer for the same match and `ParserState`.
+/// For byte input which is not UTF-8, the matched text is empty; use `ParserMatch::get_bytes()` instead.
+pub struct Predicate<S = ()> {
+    id                  : OpaqueIdentifier,
+    child               : Rc<dyn Parser<S>>,
+    description         : String,
+    predicate           : Box<PredicateFn<S>>,
+}
+
+impl<S> Predicate<S>{
+    pub fn new(child: Rc<dyn Parser<S>>, description: &str, predicate: impl Fn(&ParserMatch, &str, &ParserContext<S>) -> bool + 'static) -> Self {
+        Self{
+            id: OpaqueIdentifier::new(),
+            child,
+            description: description.into(),
+            predicate: Box::new(predicate),
+        }
+    }
+    pub fn get_child(&self) -> &Rc<dyn Parser<S>> {
+        &self.child
+    }
+}
+
+impl<S> fmt::Debug for Predicate<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Predicate")
+            .field("id", &self.id)
+            .field("child", &self.child)
+            .field("description", &self.description)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S> Parser<S> for Predicate<S>{
+    fn get_id(&self)->usize {
+        self.id.id()
+    }
+    fn kind(&self)->OpKind<'_, S> {
+        OpKind::Predicate {
+            child: &self.child,
+            description: &self.description,
+        }
+    }
+    fn describe_failure(&self)->Option<String> {
+        Some(self.description.clone())
+    }
+    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<MatchId> {
+        let child_match = self.child.clone().parse_match(context, start_position)?;
+        let context: &ParserContext<S> = context;
+        let parser_match = context.get_match(child_match);
+        let matched_bytes = parser_match.get_bytes(context.get_input());
+        let matched_text = std::str::from_utf8(matched_bytes).unwrap_or_default();
+        (self.predicate)(&parser_match, matched_text, context).then_some(child_match)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::*;
+
+    fn identifier(reserved_words: &'static [&'static str]) -> Rc<Predicate> {
+        Rc::new(Predicate::new(reg!("[a-z]+"), "an identifier which is not reserved", move |_, text, _| {
+            !reserved_words.contains(&text)
+        }))
+    }
+
+    #[test]
+    fn rejected_match_backtracks() {
+        let statement = alt!(seq!(identifier(&["let", "if"]), lit!("=1")), seq!(lit!("let "), lbl!(identifier(&["let"]), "name")));
+        let result = parse(statement.clone(), "let x").unwrap().unwrap();
+        assert_eq!(result.get_child(0).unwrap().get_child(1).unwrap().text(), "x");
+        assert_eq!(parse(statement.clone(), "lex=1").unwrap().unwrap().len(), 5);
+        let mut context = Box::new(ParserContext::new("let let"));
+        assert!(statement.parse(&mut context, 0).is_none());
+        let farthest_failure = context.get_farthest_failure().unwrap();
+        assert_eq!(farthest_failure.get_position(), 4);
+        assert_eq!(&*farthest_failure.get_expected()[0], "an identifier which is not reserved");
+        assert_eq!(farthest_failure.get_expected().len(), 1);
+    }
+
+    #[test]
+    fn tools_see_the_child_and_description() {
+        let gram = Grammar::new(None, vec![("Name", qtt!(identifier(&["let"]), 0, Some(1)))]);
+        let text = gram.to_peg_string();
+        assert_eq!(text, "Name <- @predicate(r\"[a-z]+\", \"an identifier which is not reserved\")?\n");
+        assert_eq!(Grammar::<()>::from_peg_string(&text).unwrap_err().get_position(), 8);
+        let analysis = gram.analyze();
+        let (_, name) = gram.get_rule_by_name("Name").unwrap();
+        let OpKind::Quantity { child, .. } = name.kind() else {
+            panic!("Expected a Quantity")
+        };
+        assert!(matches!(child.kind(), OpKind::Predicate { description: "an identifier which is not reserved", .. }));
+        assert_eq!(analysis.is_nullable(child.as_ref()), Some(false));
+        let first = analysis.get_first_set(child.as_ref()).unwrap();
+        assert!(!first.is_any() && first.contains(b'x') && !first.contains(b'1'));
+    }
+
+    #[test]
+    fn predicate_sees_the_match_and_context() {
+        let byte = prd!(reg!("[0-9]+"), "a number less than 256", |parser_match: &ParserMatch, text: &str, context: &ParserContext| {
+            assert_eq!(parser_match.get_text(context.get_full_text()), text);
+            text.parse::<u32>().is_ok_and(|number| number < 256)
+        });
+        let address = seq!(byte.clone(), lit!("."), byte.clone(), lit!("."), byte.clone(), lit!("."), byte);
+        assert!(parse(address.clone(), "192.168.0.255").unwrap().is_some());
+        let mut context = Box::new(ParserContext::new("192.168.256.1"));
+        assert!(address.parse(&mut context, 0).is_none());
+        assert_eq!(context.get_farthest_failure().unwrap().get_position(), 8);
+    }
+}
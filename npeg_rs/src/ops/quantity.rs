@@ -1,64 +1,87 @@
+use std::fmt;
 use std::rc::Rc;
 use crate::core::{
+    OpKind,
     OpaqueIdentifier,
     Parser,
     ParserContext,
-    ParserMatch,
+    MatchId,
 };
-#[derive(Debug)]
 /// Quantity (Repetitions)
 ///  [exp]+ or [exp]* or [exp]? or [exp]{x:y}
-pub struct Quantity {
+///
+/// Stops once the child matches nothing and there are enough occurrences, since it would only do so again.
+pub struct Quantity<S = ()> {
     id                  : OpaqueIdentifier,
-    child               : Rc<dyn Parser>,
+    child               : Rc<dyn Parser<S>>,
     minimum_occurrences : usize,
     maximum_occurrences : usize,
 }
 
-impl Quantity{
+impl<S> Quantity<S>{
     pub fn new(
-        child: Rc<dyn Parser>,
+        child: Rc<dyn Parser<S>>,
         minimum_occurrences: usize,
         maximum_occurrences: Option<usize>,
     ) -> Self {
-        let maximum_occurrences = maximum_occurrences.unwrap_or(usize::max_value());
+        let maximum_occurrences = maximum_occurrences.unwrap_or(usize::MAX);
         if maximum_occurrences - minimum_occurrences < 1 {
             panic!("Zero length Quantity is not permitted")
         }
         Self {
             id:OpaqueIdentifier::new(),
-            child: child,
+            child,
             minimum_occurrences,
             maximum_occurrences,
         }
     }
 }
 
-impl Parser for Quantity{
+impl<S> fmt::Debug for Quantity<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Quantity")
+            .field("id", &self.id)
+            .field("child", &self.child)
+            .field("minimum_occurrences", &self.minimum_occurrences)
+            .field("maximum_occurrences", &self.maximum_occurrences)
+            .finish()
+    }
+}
+
+impl<S> Parser<S> for Quantity<S>{
     fn get_id(&self)->usize {
         self.id.id()
     }
-    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext>, start_position: usize) -> Option<Rc<ParserMatch>> {
+    fn kind(&self)->OpKind<'_, S> {
+        OpKind::Quantity {
+            child: &self.child,
+            minimum_occurrences: self.minimum_occurrences,
+            maximum_occurrences: (self.maximum_occurrences != usize::MAX).then_some(self.maximum_occurrences),
+        }
+    }
+    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<MatchId> {
         let mut end_position = start_position;
-        let sub_matches: Vec<Rc<ParserMatch>> = (0..self.maximum_occurrences)
-            .map_while(|_| match self.child.clone().parse(context, end_position) {
+        let mark = context.get_arena_mut().start_pending();
+        while context.get_arena_mut().count_pending(mark) < self.maximum_occurrences {
+            match self.child.clone().parse_match(context, end_position) {
                 Some(ma) => {
-                    end_position += ma.len();
-                    Some(ma.clone())
+                    let child_end = context.get_match_end_position(ma);
+                    context.get_arena_mut().push_pending(ma);
+                    // A child which matched nothing would match nothing again, forever
+                    if child_end == end_position && context.get_arena_mut().count_pending(mark) >= self.minimum_occurrences {
+                        break;
+                    }
+                    end_position = child_end;
                 }
-                _ => None,
-            })
-            .collect();
-        if sub_matches.len() < self.minimum_occurrences || sub_matches.len() > self.maximum_occurrences {
+                _ => break,
+            }
+        }
+        if context.get_arena_mut().count_pending(mark) < self.minimum_occurrences {
+            context.get_arena_mut().discard_pending(mark);
             None
         } else {
             // TODO: Capture sub matches
-            Some(ParserMatch::new(
-                start_position,
-                end_position,
-                None,
-                sub_matches.into()
-            ))
+            Some(context.get_arena_mut().finish_match(start_position, end_position, mark))
         }
     }
 }
\ No newline at end of file
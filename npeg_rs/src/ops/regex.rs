@@ -1,9 +1,10 @@
 use std::rc::Rc;
 use crate::core::{
+    OpKind,
     OpaqueIdentifier,
     Parser,
     ParserContext,
-    ParserMatch,
+    MatchId,
 };
 #[derive(Debug)]
 pub struct Regex {
@@ -28,30 +29,45 @@ impl Regex{
         }
     }
 }
-impl Parser for Regex{
+impl<S> Parser<S> for Regex{
     fn get_id(&self)->usize {
         self.id.id()
     }
-    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext>, start_position: usize) -> Option<Rc<ParserMatch>> {
+    fn kind(&self)->OpKind<'_, S> {
+        OpKind::Regex {
+            pattern: &self.pattern[1..],
+            multi_line: self.multi_line,
+            case_insensitive: self.case_insensitive,
+            dot_matches_new_line: self.dot_matches_new_line,
+        }
+    }
+    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<MatchId> {
         let regex = context.get_compiled_regex(
             &self.pattern[..],
             self.multi_line,
             self.case_insensitive,
             self.dot_matches_new_line
         );
-        let text_to_match = &context.get_full_text()[start_position..];
-        regex.find(text_to_match).map(|re_match| {
-            if re_match.start() != 0 {
-                panic!("Regular expression matched but not at the specified position")
-            }
-            // TODO: verify that we obtain the correct length for the regular expression match
-            // The regex library talks some nonsense about byte offsets for unicode...
-            ParserMatch::new(
-                start_position,
-                start_position + re_match.end() - re_match.start(),
-                None,
-                vec![].into(),
-            )
-        })
+        let text_to_match = context.get_text_at(start_position);
+        // The `^` anchor only binds to the first alternative of a pattern like `a|b`,
+        // so a later alternative may match further on, which is not a match here
+        let re_match = regex.find(text_to_match).filter(|re_match| re_match.start() == 0)?;
+        // Offsets are in bytes, like positions, and always between characters
+        let end_position = start_position + re_match.end();
+        Some(context.new_match(start_position, end_position, None, &[]))
     }
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn regex_only_matches_at_position() {
+        // The anchor only applies to the first alternative, so "x" is found later on
+        assert!(parse(reg!("[0-9]+|x"), "ax").unwrap().is_none());
+        assert_eq!(parse(reg!("[0-9]+|x"), "x1").unwrap().unwrap().text(), "x");
+        assert_eq!(parse(reg!("[à-ÿ]+"), "éèx").unwrap().unwrap().text(), "éè");
+    }
+}
@@ -0,0 +1,82 @@
+use std::fmt;
+use std::rc::Rc;
+use crate::core::{
+    OpKind,
+    OpaqueIdentifier,
+    Parser,
+    ParserContext,
+    MatchId,
+    Symbol,
+};
+/// rule(arg, ...)
+///
+/// Invokes a rule added by `Grammar::with_parameterized_rule`, binding each of its parameters to an argument.
+pub struct RuleCall<S = ()> {
+    id                  : OpaqueIdentifier,
+    rule_name           : String,
+    arguments           : Vec<Rc<dyn Parser<S>>>,
+    /// The label of the match; the rule name without its namespace, as the resolved rule is named
+    symbol              : Symbol,
+}
+
+impl<S> RuleCall<S>{
+    pub fn new(rule_name:&str, arguments:Vec<Rc<dyn Parser<S>>>)-> Self{
+        if arguments.is_empty() {
+            panic!("RuleCall without arguments is not permitted; use a RuleReference")
+        }
+        Self {
+            id: OpaqueIdentifier::new(),
+            rule_name: rule_name.into(),
+            arguments,
+            symbol: Symbol::intern(rule_name.rsplit("::").next().unwrap_or(rule_name)),
+        }
+    }
+}
+impl<S> fmt::Debug for RuleCall<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RuleCall")
+            .field("id", &self.id)
+            .field("rule_name", &self.rule_name)
+            .field("arguments", &self.arguments)
+            .field("symbol", &self.symbol)
+            .finish()
+    }
+}
+
+impl<S> Parser<S> for RuleCall<S>{
+    fn get_id(&self)->usize {
+        self.id.id()
+    }
+    fn kind(&self)->OpKind<'_, S> {
+        OpKind::RuleCall {
+            rule_name: &self.rule_name,
+            arguments: &self.arguments,
+        }
+    }
+    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<MatchId> {
+        if let Some(resolved) = context.resolve_rule(self.rule_name.as_ref()){
+            if resolved.parameters.len() != self.arguments.len() {
+                panic!(
+                    "Rule {} takes {} parameters but {} arguments were supplied",
+                    self.rule_name,
+                    resolved.parameters.len(),
+                    self.arguments.len()
+                )
+            }
+            let instantiation = context.instantiate(self.get_id(), &resolved.parameters, &self.arguments);
+            let previous_rule_sets = resolved.rule_sets.map(|rule_sets| context.replace_rule_sets(rule_sets));
+            let previous_instantiation = context.set_instantiation(Some(instantiation));
+            let capture_scope = context.open_capture_scope();
+            let result = resolved.parser_operator.parse_match(context, start_position).map(|res| context.relabel_match(res, self.symbol));
+            context.close_capture_scope(capture_scope);
+            context.set_instantiation(previous_instantiation);
+            if let Some(previous_rule_sets) = previous_rule_sets {
+                context.replace_rule_sets(previous_rule_sets);
+            }
+            result
+        }else{
+            // TODO: Probably the user would like a nice message, not a crash
+            panic!("Invalid rule call not found in current grammar")
+        }
+    }
+}
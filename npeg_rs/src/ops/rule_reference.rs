@@ -1,32 +1,60 @@
 use std::rc::Rc;
 use crate::core::{
+    OpKind,
     OpaqueIdentifier,
     Parser,
     ParserContext,
-    ParserMatch,
+    MatchId,
+    Symbol,
 };
 #[derive(Debug)]
 // rule
 pub struct RuleReference {
     id                  : OpaqueIdentifier,
     rule_name           : String,
+    /// The label of the match
+    symbol              : Symbol,
 }
 
 impl RuleReference{
     pub fn new(rule_name:&str)-> Self{
         Self {
             id: OpaqueIdentifier::new(),
-            rule_name: rule_name.into()
+            rule_name: rule_name.into(),
+            symbol: Symbol::intern(rule_name),
         }
     }
 }
-impl Parser for RuleReference{
+impl<S> Parser<S> for RuleReference{
     fn get_id(&self)->usize {
         self.id.id()
     }
-    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext>, start_position: usize) -> Option<Rc<ParserMatch>> {
-        if let Some((_old_rule_name, parser_operator)) = context.get_rule(self.rule_name.as_ref()){
-            parser_operator.parse(context, start_position).map(|res| res.with_label(Rc::new(self.rule_name.clone())))
+    fn kind(&self)->OpKind<'_, S> {
+        OpKind::RuleReference(&self.rule_name)
+    }
+    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<MatchId> {
+        if let Some((argument, instantiation)) = context.get_argument(self.rule_name.as_ref()){
+            // A parameter of the enclosing parameterized rule; the argument is parsed where it was written
+            let previous_rule_sets = context.replace_rule_sets(instantiation.get_rule_sets());
+            let previous_instantiation = context.set_instantiation(instantiation.get_enclosing());
+            let result = argument.parse_match(context, start_position);
+            context.set_instantiation(previous_instantiation);
+            context.replace_rule_sets(previous_rule_sets);
+            result
+        }else if let Some(resolved) = context.resolve_rule(self.rule_name.as_ref()){
+            if !resolved.parameters.is_empty() {
+                panic!("Parameterized rule {} must be invoked with a RuleCall", self.rule_name)
+            }
+            let previous_rule_sets = resolved.rule_sets.map(|rule_sets| context.replace_rule_sets(rule_sets));
+            let previous_instantiation = context.set_instantiation(None);
+            let capture_scope = context.open_capture_scope();
+            let result = resolved.parser_operator.parse_match(context, start_position).map(|res| context.relabel_match(res, self.symbol));
+            context.close_capture_scope(capture_scope);
+            context.set_instantiation(previous_instantiation);
+            if let Some(previous_rule_sets) = previous_rule_sets {
+                context.replace_rule_sets(previous_rule_sets);
+            }
+            result
         }else{
             // TODO: Probably the user would like a nice message, not a crash
             panic!("Invalid rule reference not found in current grammar")
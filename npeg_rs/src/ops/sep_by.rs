@@ -0,0 +1,145 @@
+use std::fmt;
+use std::rc::Rc;
+use crate::core::{
+    OpKind,
+    OpaqueIdentifier,
+    Parser,
+    ParserContext,
+    MatchId,
+};
+/// Matches a list of elements with a separator between each, e.g. `1, 2, 3`; the same as
+/// `(element (separator element)*)?`, but the match has only the elements as children, not the separators.
+///
+/// A separator after the last element is only matched `with_trailing_separator(true)`.
+/// The list stops early if a separator and element together match nothing, since they could repeat forever.
+pub struct SepBy<S = ()> {
+    id                  : OpaqueIdentifier,
+    element             : Rc<dyn Parser<S>>,
+    separator           : Rc<dyn Parser<S>>,
+    minimum_occurrences : usize,
+    maximum_occurrences : usize,
+    trailing_separator  : bool,
+}
+
+impl<S> SepBy<S>{
+    pub fn new(element: Rc<dyn Parser<S>>, separator: Rc<dyn Parser<S>>) -> Self {
+        Self{
+            id: OpaqueIdentifier::new(),
+            element,
+            separator,
+            minimum_occurrences: 0,
+            maximum_occurrences: usize::MAX,
+            trailing_separator: false,
+        }
+    }
+    /// The number of elements allowed; any number by default
+    pub fn with_occurrences(mut self, minimum_occurrences: usize, maximum_occurrences: Option<usize>) -> Self {
+        self.minimum_occurrences = minimum_occurrences;
+        self.maximum_occurrences = maximum_occurrences.unwrap_or(usize::MAX);
+        self
+    }
+    pub fn with_trailing_separator(mut self, trailing_separator: bool) -> Self {
+        self.trailing_separator = trailing_separator;
+        self
+    }
+    pub fn get_element(&self) -> &Rc<dyn Parser<S>> {
+        &self.element
+    }
+    pub fn get_separator(&self) -> &Rc<dyn Parser<S>> {
+        &self.separator
+    }
+}
+impl<S> fmt::Debug for SepBy<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SepBy")
+            .field("id", &self.id)
+            .field("element", &self.element)
+            .field("separator", &self.separator)
+            .field("minimum_occurrences", &self.minimum_occurrences)
+            .field("maximum_occurrences", &self.maximum_occurrences)
+            .field("trailing_separator", &self.trailing_separator)
+            .finish()
+    }
+}
+
+impl<S> Parser<S> for SepBy<S>{
+    fn get_id(&self)->usize {
+        self.id.id()
+    }
+    fn kind(&self)->OpKind<'_, S> {
+        OpKind::SepBy {
+            element: &self.element,
+            separator: &self.separator,
+            minimum_occurrences: self.minimum_occurrences,
+            maximum_occurrences: (self.maximum_occurrences != usize::MAX).then_some(self.maximum_occurrences),
+            trailing_separator: self.trailing_separator,
+        }
+    }
+    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<MatchId> {
+        let mut end_position = start_position;
+        let mark = context.get_arena_mut().start_pending();
+        if self.maximum_occurrences > 0 {
+            if let Some(element_match) = self.element.clone().parse_match(context, start_position) {
+                end_position = context.get_match_end_position(element_match);
+                context.get_arena_mut().push_pending(element_match);
+                loop {
+                    let state = context.get_state();
+                    let Some(separator_match) = self.separator.clone().parse_match(context, end_position) else {
+                        break;
+                    };
+                    let separator_end = context.get_match_end_position(separator_match);
+                    if context.get_arena_mut().count_pending(mark) < self.maximum_occurrences {
+                        if let Some(element_match) = self.element.clone().parse_match(context, separator_end) {
+                            let element_end = context.get_match_end_position(element_match);
+                            if element_end > end_position {
+                                end_position = element_end;
+                                context.get_arena_mut().push_pending(element_match);
+                                continue;
+                            }
+                        }
+                    }
+                    if self.trailing_separator && separator_end > end_position {
+                        end_position = separator_end;
+                    } else {
+                        context.set_state(state);
+                    }
+                    break;
+                }
+            }
+        }
+        if context.get_arena_mut().count_pending(mark) < self.minimum_occurrences {
+            context.get_arena_mut().discard_pending(mark);
+            None
+        } else {
+            Some(context.get_arena_mut().finish_match(start_position, end_position, mark))
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::*;
+
+    fn elements(parser: Rc<dyn Parser>, text: &str) -> Option<(usize, Vec<&str>)> {
+        let result = parse(parser, text).unwrap()?;
+        Some((result.get_end_position(), result.get_children().map(|element| element.text()).collect()))
+    }
+
+    #[test]
+    fn separators_are_not_children() {
+        let list = sep!(reg!("[0-9]+"), lit!(","));
+        assert_eq!(elements(list.clone(), "1,22,333"), Some((8, vec!["1", "22", "333"])));
+        assert_eq!(elements(list.clone(), "1,22,"), Some((4, vec!["1", "22"])));
+        assert_eq!(elements(list, "x"), Some((0, vec![])));
+    }
+
+    #[test]
+    fn trailing_separator_and_occurrences() {
+        let list = Rc::new(SepBy::new(reg!("[0-9]+"), lit!(",")).with_trailing_separator(true).with_occurrences(2, Some(3)));
+        assert_eq!(elements(list.clone(), "1,22,"), Some((5, vec!["1", "22"])));
+        assert_eq!(elements(list.clone(), "1,2,3,4"), Some((6, vec!["1", "2", "3"])));
+        assert_eq!(elements(list, "1,"), None);
+    }
+}
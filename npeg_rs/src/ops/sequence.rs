@@ -1,55 +1,60 @@
+use std::fmt;
 use std::rc::Rc;
 use crate::core::{
+    OpKind,
     OpaqueIdentifier,
     Parser,
     ParserContext,
-    ParserMatch,
+    MatchId,
 };
-#[derive(Debug)]
-pub struct Sequence {
+pub struct Sequence<S = ()> {
     id                  : OpaqueIdentifier,
-    children            : Vec<Rc<dyn Parser>>,
+    children            : Vec<Rc<dyn Parser<S>>>,
 }
-impl Sequence{
-    pub fn new(children: Vec<Rc<dyn Parser>>) -> Self {
-        if children.len() == 0 {
+impl<S> Sequence<S>{
+    pub fn new(children: Vec<Rc<dyn Parser<S>>>) -> Self {
+        if children.is_empty() {
             panic!("Zero length Sequence is not permitted")
         }
         Self { 
             id:OpaqueIdentifier::new(),
-            children:children
+            children
         }
     }
 }
 
-impl Parser for Sequence{
+impl<S> fmt::Debug for Sequence<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sequence")
+            .field("id", &self.id)
+            .field("children", &self.children)
+            .finish()
+    }
+}
+
+impl<S> Parser<S> for Sequence<S>{
     fn get_id(&self)->usize {
         self.id.id()
     }
-    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext>, start_position: usize) -> Option<Rc<ParserMatch>> {
+    fn kind(&self)->OpKind<'_, S> {
+        OpKind::Sequence(&self.children)
+    }
+    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<MatchId> {
         let mut end_position = start_position;
-        let sub_matches: Vec<Rc<ParserMatch>> = self.children
-            .iter()
-            .map_while(|child|
-                match child.clone().parse(context, end_position) {
-                    Some(ma) => {
-                        end_position += ma.len();
-                        Some(ma.clone())
-                    }
-                    _ => None,
+        let mark = context.get_arena_mut().start_pending();
+        for child in &self.children {
+            match child.clone().parse_match(context, end_position) {
+                Some(ma) => {
+                    end_position = context.get_match_end_position(ma);
+                    context.get_arena_mut().push_pending(ma);
+                }
+                _ => {
+                    context.get_arena_mut().discard_pending(mark);
+                    return None;
                 }
-            )
-            .collect();
-        if sub_matches.len() == self.children.len() {
-            // TODO: Capture sub matches
-            Some(ParserMatch::new(
-                start_position,
-                end_position,
-                None,
-                sub_matches.into()
-            ))
-        } else {
-            None
+            }
         }
+        // TODO: Capture sub matches
+        Some(context.get_arena_mut().finish_match(start_position, end_position, mark))
     }
 }
\ No newline at end of file
@@ -0,0 +1,96 @@
+use std::rc::Rc;
+use crate::core::{
+    OpKind,
+    OpaqueIdentifier,
+    Parser,
+    ParserContext,
+    MatchId,
+};
+use super::Endianness;
+#[derive(Debug)]
+/// Matches as many bytes as given by the most recent `Capture` stored under `name`, read as an unsigned integer;
+/// for length-prefixed fields in byte input, e.g.
+///
+/// ```
+/// # use npeg_rs::*;
+/// let field = seq!(cap!(int!(2, Big), "length"), take!("length"));
+/// let result = parse_bytes(field, b"\x00\x03abcd").unwrap().unwrap();
+/// assert_eq!(result.get_child(1).unwrap().bytes(), b"abc");
+/// ```
+///
+/// Fails if there is no such capture, if it is more than 8 bytes long, or if too few bytes remain.
+pub struct Take {
+    id                  : OpaqueIdentifier,
+    name                : String,
+    endianness          : Endianness,
+}
+
+impl Take{
+    pub fn new(name: &str, endianness: Endianness) -> Self {
+        Self{
+            id: OpaqueIdentifier::new(),
+            name: name.into(),
+            endianness,
+        }
+    }
+}
+impl<S> Parser<S> for Take{
+    fn get_id(&self)->usize {
+        self.id.id()
+    }
+    fn kind(&self)->OpKind<'_, S> {
+        OpKind::Take {
+            name: &self.name,
+            endianness: self.endianness,
+        }
+    }
+    fn describe_failure(&self)->Option<String> {
+        Some(format!("as many bytes as captured in {}", self.name))
+    }
+    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<MatchId> {
+        let input = context.get_input();
+        let length_bytes = context.get_capture(&self.name)?.get_bytes(input);
+        if length_bytes.len() > 8 {
+            return None;
+        }
+        let length = usize::try_from(self.endianness.read(length_bytes)).ok()?;
+        let end_position = start_position.checked_add(length)?;
+        if end_position > input.len() {
+            return None;
+        }
+        Some(context.new_match(start_position, end_position, None, &[]))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn take_matches_the_captured_length() {
+        let fields = qtt!(seq!(cap!(int!(1, Big), "length"), lbl!(take!("length"), "field")), 0, None);
+        let result = parse_bytes(fields, b"\x02ab\x00\x03\xff\xfe\xfd\x05a").unwrap().unwrap();
+        // The last field is longer than the bytes which remain
+        assert_eq!(result.get_end_position(), 8);
+        let field_bytes: Vec<&[u8]> = result
+            .get_children()
+            .map(|field| field.get_child(1).unwrap().bytes())
+            .collect();
+        assert_eq!(field_bytes, vec![&b"ab"[..], &b""[..], &b"\xff\xfe\xfd"[..]]);
+    }
+
+    #[test]
+    fn take_reads_little_endian_lengths() {
+        let field = seq!(cap!(int!(2, Little), "length"), take!("length", Little));
+        let mut input = vec![0x00, 0x01];
+        input.extend([b'x'; 256]);
+        assert_eq!(parse_bytes(field.clone(), &input).unwrap().unwrap().get_end_position(), 258);
+        assert!(parse_bytes(field, &input[..257]).unwrap().is_none());
+    }
+
+    #[test]
+    fn take_fails_without_capture() {
+        assert!(parse_bytes(take!("length"), b"abc").unwrap().is_none());
+    }
+}
@@ -0,0 +1,101 @@
+use std::fmt;
+use std::rc::Rc;
+use crate::core::{
+    OpKind,
+    OpaqueIdentifier,
+    Parser,
+    ParserContext,
+    MatchId,
+};
+/// Matches `child` repeatedly until `terminator` matches, e.g. the body of a block comment up to its `*/`;
+/// the same as `(!terminator child)* terminator`. The match has the matches of `child` as children,
+/// followed by the match of `terminator`.
+///
+/// Fails if `child` fails, or matches nothing, before `terminator` matches.
+pub struct Until<S = ()> {
+    id                  : OpaqueIdentifier,
+    child               : Rc<dyn Parser<S>>,
+    terminator          : Rc<dyn Parser<S>>,
+}
+
+impl<S> Until<S>{
+    pub fn new(child: Rc<dyn Parser<S>>, terminator: Rc<dyn Parser<S>>) -> Self {
+        Self{
+            id: OpaqueIdentifier::new(),
+            child,
+            terminator,
+        }
+    }
+    pub fn get_child(&self) -> &Rc<dyn Parser<S>> {
+        &self.child
+    }
+    pub fn get_terminator(&self) -> &Rc<dyn Parser<S>> {
+        &self.terminator
+    }
+}
+impl<S> fmt::Debug for Until<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Until")
+            .field("id", &self.id)
+            .field("child", &self.child)
+            .field("terminator", &self.terminator)
+            .finish()
+    }
+}
+
+impl<S> Parser<S> for Until<S>{
+    fn get_id(&self)->usize {
+        self.id.id()
+    }
+    fn kind(&self)->OpKind<'_, S> {
+        OpKind::Until {
+            child: &self.child,
+            terminator: &self.terminator,
+        }
+    }
+    fn parse_internal(self:Rc<Self>, context: &mut Box<ParserContext<S>>, start_position: usize) -> Option<MatchId> {
+        let mut end_position = start_position;
+        let mark = context.get_arena_mut().start_pending();
+        loop {
+            if let Some(terminator_match) = self.terminator.clone().parse_match(context, end_position) {
+                end_position = context.get_match_end_position(terminator_match);
+                context.get_arena_mut().push_pending(terminator_match);
+                return Some(context.get_arena_mut().finish_match(start_position, end_position, mark));
+            }
+            let child_end = self.child
+                .clone()
+                .parse_match(context, end_position)
+                .map(|child_match| (child_match, context.get_match_end_position(child_match)))
+                .filter(|(_, child_end)| *child_end > end_position);
+            match child_end {
+                Some((child_match, child_end)) => {
+                    end_position = child_end;
+                    context.get_arena_mut().push_pending(child_match);
+                },
+                None => {
+                    context.get_arena_mut().discard_pending(mark);
+                    return None;
+                },
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn repeats_until_terminator() {
+        let comment = seq!(lit!("/*"), unt!(reg!("(?s)."), lit!("*/")));
+        let result = parse(comment.clone(), "/* a*b */ x").unwrap().unwrap();
+        assert_eq!(result.text(), "/* a*b */");
+        let body = result.get_child(1).unwrap();
+        assert_eq!(body.get_children().len(), 6);
+        assert_eq!(body.get_children().last().unwrap().text(), "*/");
+        assert!(parse(comment, "/* a*b").unwrap().is_none());
+        // The terminator is tried first
+        assert_eq!(parse(unt!(reg!("[a-z]"), lit!("b")), "ab").unwrap().unwrap().get_children().len(), 2);
+    }
+}
@@ -0,0 +1,385 @@
+//! Rewrites a grammar into an equivalent one which parses faster; see `Grammar::optimize()`
+
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::core::{OpKind, Parser};
+use crate::ops::{
+    Alternation,
+    Capture,
+    CharClass,
+    Grammar,
+    Label,
+    LiteralSet,
+    Lookahead,
+    Quantity,
+    RuleCall,
+    Sequence,
+};
+
+/// Rules with at most this many ops may be inlined
+const INLINE_MAX_OPS: usize = 8;
+
+impl<S: 'static> Grammar<S> {
+    /// Returns an equivalent grammar which parses faster:
+    ///
+    /// - an `Alternation` of `Literal`s becomes a `CharClass` or a `LiteralSet`, which match the same way
+    /// - a `Sequence` inside a `Sequence` is flattened, and so is an `Alternation` inside an `Alternation`
+    /// - a `Sequence` or `Alternation` of one op is replaced by the op, unless the op's match is labeled
+    /// - common first ops are factored out of neighbouring alternatives: `a b / a c` becomes `a (b / c)`
+    /// - small rules which refer to no other rules are inlined as a `Label` with the rule's name
+    ///
+    /// The optimized grammar matches the same text, and its matches have the same labeled matches, in the same
+    /// order and with the same positions; unlabeled matches may be merged or removed.
+    /// Base grammars and imports are optimized too. Rules of a base grammar which the derived grammar overrides
+    /// are never inlined, but a grammar must be optimized after the grammars deriving from it are defined;
+    /// overriding a rule of an optimized grammar has no effect where the rule was inlined.
+    ///
+    /// ```
+    /// # use npeg_rs::*;
+    /// let gram: Grammar = Grammar::new(None, vec![
+    ///     ("Op",    alt!(lit!("+"), lit!("-"), lit!("*"))),
+    ///     ("Digit", reg!("[0-9]")),
+    ///     ("Num",   seq!(rul!("Digit"), seq!(qtt!(rul!("Digit"), 0, None)))),
+    /// ]);
+    /// assert_eq!(gram.optimize().to_string(), "Op <- \"+\" / \"-\" / \"*\"\nDigit <- r\"[0-9]\"\nNum <- Digit:r\"[0-9]\" (Digit:r\"[0-9]\")*\n");
+    /// ```
+    pub fn optimize(&self) -> Grammar<S> {
+        optimize_grammar(self, &HashSet::new())
+    }
+}
+
+/// A rule name, its parameters and its optimized body
+type OptimizedRule<S> = (Rc<String>, Vec<Rc<String>>, Rc<dyn Parser<S>>);
+
+/// `overridden` holds the names of the rules defined by grammars deriving from `grammar`
+fn optimize_grammar<S: 'static>(grammar: &Grammar<S>, overridden: &HashSet<String>) -> Grammar<S> {
+    let mut optimizer = Optimizer::default();
+    let rules: Vec<OptimizedRule<S>> = grammar
+        .get_rules()
+        .iter()
+        .map(|(rule_name, parser_operator)| {
+            let parameters = grammar.get_rule_parameters(rule_name).to_vec();
+            optimizer.parameters = parameters.clone();
+            (rule_name.clone(), parameters, optimizer.optimize_op(parser_operator))
+        })
+        .collect();
+    optimizer.inlinable = rules
+        .iter()
+        .filter(|(rule_name, parameters, parser_operator)| {
+            parameters.is_empty() && !overridden.contains(rule_name.as_str()) && count_ops(parser_operator.as_ref()) <= INLINE_MAX_OPS && is_self_contained(parser_operator.as_ref())
+        })
+        .map(|(rule_name, _, parser_operator)| (rule_name.to_string(), parser_operator.clone()))
+        .collect();
+
+    let mut optimized = Grammar::new(grammar.get_declared_starting_rule_name(), vec![]);
+    for (rule_name, parameters, parser_operator) in &rules {
+        optimizer.parameters = parameters.clone();
+        let parser_operator = optimizer.optimize_op(parser_operator);
+        optimized = if parameters.is_empty() {
+            optimized.with_rule(rule_name, parser_operator)
+        } else {
+            optimized.with_parameterized_rule(rule_name, parameters.iter().map(|parameter| parameter.as_str()).collect(), parser_operator)
+        };
+    }
+    for (namespace, import) in grammar.get_all_imports() {
+        if grammar.get_base().is_none_or(|base| base.get_import(&namespace).is_none()) {
+            optimized = optimized.with_import(&namespace, Rc::new(optimize_grammar(&import, &HashSet::new())));
+        }
+    }
+    if let Some(base) = grammar.get_base() {
+        let mut overridden = overridden.clone();
+        overridden.extend(grammar.get_rules().iter().map(|(rule_name, _)| rule_name.to_string()));
+        optimized = optimized.with_base(Rc::new(optimize_grammar(base, &overridden)));
+    }
+    optimized
+}
+
+struct Optimizer<S> {
+    /// Optimized bodies of the rules which may be inlined, by name
+    inlinable: HashMap<String, Rc<dyn Parser<S>>>,
+    /// The parameters of the rule being optimized, which are not rules and are never inlined
+    parameters: Vec<Rc<String>>,
+}
+
+// Not derived, since that would require `S: Default`
+impl<S> Default for Optimizer<S> {
+    fn default() -> Self {
+        Optimizer {
+            inlinable: HashMap::new(),
+            parameters: vec![],
+        }
+    }
+}
+
+impl<S: 'static> Optimizer<S> {
+    fn optimize_op(&self, op: &Rc<dyn Parser<S>>) -> Rc<dyn Parser<S>> {
+        match op.kind() {
+            OpKind::Sequence(children) => self.sequence(children.iter().map(|child| self.optimize_op(child)).collect()),
+            OpKind::Alternation(children) => self.alternation(children.iter().map(|child| self.optimize_op(child)).collect()),
+            OpKind::Quantity { child, minimum_occurrences, maximum_occurrences } => {
+                Rc::new(Quantity::new(self.optimize_op(child), minimum_occurrences, maximum_occurrences))
+            },
+            OpKind::Lookahead { child, scout, accept_match } => Rc::new(Lookahead::new(self.optimize_op(child), self.optimize_op(scout), accept_match)),
+            OpKind::Label { child, label } => Rc::new(Label::new(self.optimize_op(child), label)),
+            OpKind::Capture { child, name } => Rc::new(Capture::new(self.optimize_op(child), name)),
+            OpKind::RuleReference(rule_name) if !self.parameters.iter().any(|parameter| parameter.as_str() == rule_name) => {
+                match self.inlinable.get(rule_name) {
+                    // Labeled with the rule name, just as a `RuleReference` labels its match
+                    Some(parser_operator) => Rc::new(Label::new(parser_operator.clone(), rule_name)),
+                    None => op.clone(),
+                }
+            },
+            OpKind::RuleCall { rule_name, arguments } => Rc::new(RuleCall::new(rule_name, arguments.iter().map(|argument| self.optimize_op(argument)).collect())),
+            OpKind::Grammar(grammar) => Rc::new(optimize_grammar(grammar, &HashSet::new())),
+            _ => op.clone(),
+        }
+    }
+
+    fn sequence(&self, children: Vec<Rc<dyn Parser<S>>>) -> Rc<dyn Parser<S>> {
+        let mut flattened = vec![];
+        for child in children {
+            match child.kind() {
+                OpKind::Sequence(grandchildren) => flattened.extend(grandchildren.iter().cloned()),
+                _ => flattened.push(child),
+            }
+        }
+        if flattened.len() == 1 && has_unlabeled_match(flattened[0].as_ref()) {
+            return flattened.remove(0);
+        }
+        Rc::new(Sequence::new(flattened))
+    }
+
+    fn alternation(&self, children: Vec<Rc<dyn Parser<S>>>) -> Rc<dyn Parser<S>> {
+        let mut flattened = vec![];
+        for child in children {
+            match child.kind() {
+                OpKind::Alternation(grandchildren) => flattened.extend(grandchildren.iter().cloned()),
+                _ => flattened.push(child),
+            }
+        }
+        let children = self.factor_prefixes(flattened);
+        if children.len() == 1 && has_unlabeled_match(children[0].as_ref()) {
+            return children[0].clone();
+        }
+        let literals: Option<Vec<&str>> = children
+            .iter()
+            .map(|child| match child.kind() {
+                OpKind::Literal(literal_text) => Some(literal_text),
+                _ => None,
+            })
+            .collect();
+        match literals {
+            Some(literals) if literals.iter().all(|literal_text| literal_text.chars().count() == 1) => {
+                Rc::new(CharClass::new(&literals.iter().filter_map(|literal_text| literal_text.chars().next()).collect::<Vec<char>>()))
+            },
+            Some(literals) => Rc::new(LiteralSet::new(&literals)),
+            None => Rc::new(Alternation::new(children)),
+        }
+    }
+
+    /// Neighbouring alternatives which are sequences starting with the same op, compared as PEG text,
+    /// are replaced by one sequence: `a b / a c / d` becomes `a (b / c) / d`
+    fn factor_prefixes(&self, children: Vec<Rc<dyn Parser<S>>>) -> Vec<Rc<dyn Parser<S>>> {
+        let mut factored: Vec<Rc<dyn Parser<S>>> = vec![];
+        let mut index = 0;
+        while index < children.len() {
+            let head = sequence_head(children[index].as_ref());
+            let mut end = index + 1;
+            while end < children.len() && head.is_some() && sequence_head(children[end].as_ref()) == head {
+                end += 1;
+            }
+            if end - index < 2 {
+                factored.push(children[index].clone());
+                index += 1;
+                continue;
+            }
+            let mut first = None;
+            let rests: Vec<Rc<dyn Parser<S>>> = children[index..end]
+                .iter()
+                .map(|child| match child.kind() {
+                    OpKind::Sequence(sequence_children) => {
+                        first.get_or_insert_with(|| sequence_children[0].clone());
+                        self.sequence(sequence_children[1..].to_vec())
+                    },
+                    _ => unreachable!("Only sequences have a head"),
+                })
+                .collect();
+            factored.push(self.sequence(vec![first.expect("The run is not empty"), self.alternation(rests)]));
+            index = end;
+        }
+        factored
+    }
+}
+
+/// The first op of a sequence of more than one op, as PEG text.
+/// Ops which can not be written as PEG text, or whose closures it does not show, are never equal.
+fn sequence_head<S>(op: &dyn Parser<S>) -> Option<String> {
+    match op.kind() {
+        OpKind::Sequence(children) if children.len() > 1 && !has_closure(children[0].as_ref()) => {
+            let head = children[0].to_peg_string();
+            (!head.contains("@unknown")).then_some(head)
+        },
+        _ => None,
+    }
+}
+
+/// Whether the op contains a `Predicate` or `Action`; two of them written the same may still differ
+fn has_closure<S>(op: &dyn Parser<S>) -> bool {
+    match op.kind() {
+        OpKind::Predicate { .. } | OpKind::Action { .. } => true,
+        OpKind::Grammar(grammar) => grammar.get_rules().iter().any(|(_, rule)| has_closure(rule.as_ref())),
+        kind => kind.children().iter().any(|child| has_closure(child.as_ref())),
+    }
+}
+
+/// Whether the op makes a new match without a label; other ops label their match, or may pass on a labeled one
+fn has_unlabeled_match<S>(op: &dyn Parser<S>) -> bool {
+    matches!(op.kind(), OpKind::Literal(_) | OpKind::Regex { .. } | OpKind::Sequence(_) | OpKind::Alternation(_) | OpKind::Quantity { .. })
+}
+
+fn count_ops<S>(op: &dyn Parser<S>) -> usize {
+    1 + op.kind().children().iter().map(|child| count_ops(child.as_ref())).sum::<usize>()
+}
+
+/// Whether the op refers to no rules and does not depend on the parser state, so that it parses the same anywhere
+fn is_self_contained<S>(op: &dyn Parser<S>) -> bool {
+    matches!(
+        op.kind(),
+        OpKind::Literal(_) | OpKind::Regex { .. } | OpKind::Sequence(_) | OpKind::Alternation(_) | OpKind::Quantity { .. } | OpKind::Lookahead { .. } | OpKind::Label { .. }
+    ) && op.kind().children().iter().all(|child| is_self_contained(child.as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::*;
+
+    /// A label, start position and end position
+    type LabeledMatch = (String, usize, usize);
+
+    /// The labeled matches, in order
+    fn labeled_matches(parser_match: &ParserMatch, labeled: &mut Vec<LabeledMatch>) {
+        if let Some(label) = parser_match.get_label() {
+            labeled.push((label.to_owned(), parser_match.get_start_position(), parser_match.get_end_position()));
+        }
+        for child in parser_match.get_children() {
+            labeled_matches(&child, labeled);
+        }
+    }
+
+    fn parse(gram: &Rc<Grammar>, text: &str) -> Option<(usize, Vec<LabeledMatch>)> {
+        let mut context = Box::new(ParserContext::new(text));
+        gram.clone().parse(&mut context, 0).map(|result| {
+            let mut labeled = vec![];
+            labeled_matches(&result.get_root(), &mut labeled);
+            (result.len(), labeled)
+        })
+    }
+
+    fn assert_equivalent(gram: Grammar, texts: &[&str]) -> Rc<Grammar> {
+        let optimized = Rc::new(gram.optimize());
+        let gram = Rc::new(gram);
+        for text in texts {
+            assert_eq!(parse(&optimized, text), parse(&gram, text), "{:?}", text);
+        }
+        optimized
+    }
+
+    #[test]
+    fn literal_alternations_match_the_same_way() {
+        let keywords = alt!(lit!("in"), lit!("int"), lit!("if"), lit!("i"), lit!("in"));
+        let optimized = assert_equivalent(Grammar::new(None, vec![("Keyword", keywords)]), &["int", "in", "if", "i", "x", ""]);
+        let (_, keyword) = optimized.get_rule_by_name("Keyword").unwrap();
+        assert_eq!(keyword.to_peg_string(), "\"in\" / \"int\" / \"if\" / \"i\" / \"in\"");
+
+        let operators = alt!(lit!("+"), lit!("é"), lit!("*"));
+        let gram = Rc::new(Grammar::new(None, vec![("Op", operators.clone())]));
+        let optimized = Rc::new(gram.optimize());
+        for text in ["+", "é", "*", "-"] {
+            let mut context = Box::new(ParserContext::new(text));
+            let expected = format!("{:?}", gram.clone().parse(&mut context, 0));
+            let mut context = Box::new(ParserContext::new(text));
+            assert_eq!(format!("{:?}", optimized.clone().parse(&mut context, 0)), expected);
+        }
+    }
+
+    #[test]
+    fn nested_sequences_and_alternations_are_flattened() {
+        let gram = Grammar::new(
+            None,
+            vec![
+                ("Call", seq!(lbl!(reg!("[a-z]+"), "name"), seq!(lit!("("), seq!(qtt!(rul!("Arg"), 0, None))), lit!(")"))),
+                ("Arg",  alt!(alt!(reg!("[0-9]+"), lbl!(seq!(rul!("Call")), "call")), alt!(reg!("'[^']*'")))),
+            ]
+        );
+        let optimized = assert_equivalent(gram, &["f()", "f(1)", "f(g('a')2)", "f(", ""]);
+        assert_eq!(optimized.to_string(), "Call <- name:r\"[a-z]+\" \"(\" Arg* \")\"\nArg <- r\"[0-9]+\" / call:(Call) / r\"'[^']*'\"\n");
+    }
+
+    #[test]
+    fn small_rules_are_inlined() {
+        let base = Rc::new(Grammar::new(
+            None,
+            vec![
+                ("List",   seq!(rul!("Item"), qtt!(seq!(rul!("Comma"), rul!("Item")), 0, None))),
+                ("Item",   alt!(rul!("Number"), rul!("Nested"))),
+                ("Nested", seq!(lit!("("), rul!("List"), lit!(")"))),
+                ("Number", reg!("[0-9]+")),
+                ("Comma",  lit!(",")),
+            ]
+        ));
+        let derived = Grammar::new(None, vec![("Number", reg!("[0-9x]+"))]).with_base(base.clone());
+        let optimized = assert_equivalent(derived, &["1,2", "(1,x),3", "x", "(", ""]);
+        // Number is overridden, and Nested refers to other rules
+        let optimized_base = optimized.get_base().unwrap();
+        assert_eq!(optimized_base.get_rules()[0].1.to_peg_string(), "Item (Comma:\",\" Item)*");
+        assert_eq!(optimized_base.get_rules()[1].1.to_peg_string(), "Number / Nested");
+
+        let standalone = assert_equivalent(Grammar::new(None, base.get_rules().iter().map(|(rule_name, rule)| (rule_name.as_str(), rule.clone())).collect()), &["1,(2)"]);
+        assert_eq!(standalone.get_rules()[1].1.to_peg_string(), "Number:r\"[0-9]+\" / Nested");
+    }
+
+    #[test]
+    fn common_prefixes_are_factored_out() {
+        let gram = Grammar::new(
+            None,
+            vec![
+                ("Statement", alt!(
+                    seq!(rul!("Name"), lit!("="), rul!("Name")),
+                    seq!(rul!("Name"), lit!("+="), rul!("Name")),
+                    seq!(rul!("Name"), lit!("()")),
+                    seq!(lit!("return "), rul!("Name"))
+                )),
+                ("Name", lbl!(reg!("[a-z]+"), "identifier")),
+            ]
+        );
+        let optimized = assert_equivalent(gram, &["a=b", "a+=b", "a()", "return a", "a", "a-=b", ""]);
+        assert_eq!(
+            optimized.get_rules()[0].1.to_peg_string(),
+            "Name:(identifier:r\"[a-z]+\") (\"=\" Name:(identifier:r\"[a-z]+\") / \"+=\" Name:(identifier:r\"[a-z]+\") / \"()\") / \"return \" Name:(identifier:r\"[a-z]+\")"
+        );
+    }
+
+    #[test]
+    fn predicates_written_the_same_are_not_factored_out() {
+        fn identifier(reserved_words: &'static [&'static str]) -> Rc<Predicate> {
+            Rc::new(Predicate::new(reg!("[a-z]+"), "identifier", move |_, text, _| !reserved_words.contains(&text)))
+        }
+        let gram = Grammar::new(
+            None,
+            vec![
+                ("Statement", alt!(seq!(identifier(&["let", "if"]), lit!("=1")), seq!(identifier(&["let"]), lit!("!")))),
+            ]
+        );
+        let optimized = assert_equivalent(gram, &["if!", "a=1", "a!", "if=1", "let!", ""]);
+        assert_eq!(optimized.get_rules()[0].1.to_peg_string(), "@predicate(r\"[a-z]+\", \"identifier\") \"=1\" / @predicate(r\"[a-z]+\", \"identifier\") \"!\"");
+    }
+
+    #[test]
+    fn example_grammar_is_equivalent() {
+        let gram = Grammar::from_peg_string(include_str!("../../../codegen_example/src/grammar.peg")).unwrap();
+        assert_equivalent(gram, &["1+2*3", "(1.5+pi)^2/e", "max(1,2)*sin(x)", "1+", "pie", ""]);
+    }
+}
@@ -0,0 +1,408 @@
+//! Reads and writes grammars as PEG text; see `Grammar::from_peg_string()` and `Parser::to_peg_string()`
+//!
+//! ```text
+//! @start Json                      # only needed when the starting rule is not the first rule
+//! Name <- expression
+//! List(X, Sep) <- X (Sep X)*       # parameterized rule
+//! ```
+//!
+//! | Text                          | Op                                           |
+//! |-------------------------------|----------------------------------------------|
+//! | `a / b`                       | `Alternation`                                |
+//! | `a b`                         | `Sequence`                                   |
+//! | `a &b`, `a !b`                | `Lookahead` of `b` after `a`                 |
+//! | `label:a`, `"any label":a`    | `Label`                                      |
+//! | `a*`, `a+`, `a?`, `a{2,5}`, `a{2,}` | `Quantity`                             |
+//! | `"text"`                      | `Literal`; escapes are `\" \\ \n \r \t \u{..}` |
+//! | `r"[a-z]+"mis`                | `Regex` with optional flags; only `\"` is an escape |
+//! | `b"\x89PNG"`                  | `Literal` of bytes which are not UTF-8; escapes are `\" \\ \x..` |
+//! | `Name`, `ns::Name`, `` `any name` `` | `RuleReference`; names which are not identifiers are in backquotes |
+//! | `Name(a, b)`                  | `RuleCall`; no space before `(`              |
+//! | `{ A <- a  B <- b }`          | nested `Grammar`                             |
+//! | `@capture(name, a)`           | `Capture`                                    |
+//! | `@backref(name)`              | `BackReference`                              |
+//! | `@counted(name, a)`, `@counted(name, a, big)` | `Counted`, optionally `with_binary_count` |
+//! | `@sepby(a, b)`, `@sepby(a, b, 1..=3, trailing)`, `@sepby(a, b, 1..)` | `SepBy`, optionally `with_occurrences` and `with_trailing_separator` |
+//! | `@until(a, b)`                | `Until`                                      |
+//! | `@indent`, `@samedent`, `@dedent` | `Indent`, `Samedent`, `Dedent`           |
+//! | `@grapheme`                   | `AnyGrapheme`; requires the `unicode-segmentation` feature |
+//! | `@byteclass(0x41..=0x5a, 0x5f)` | `ByteClass`                                |
+//! | `@int(4, big)`, `@int(2, little, 1..=3)` | `Integer`, optionally `with_range` |
+//! | `@take(name, big)`            | `Take`                                       |
+//! | `@operators(atom, prefix(a, label, 3), infix(b, label, 1, left), postfix(c, label, 2))` | `OperatorTable` |
+//! | `@predicate(a, "description")` | `Predicate`; written, but not read, as its check is a closure |
+//! | `@action(a)`                  | `Action`; written, but not read, as its update is a closure |
+//!
+//! `#` starts a comment which runs to the end of the line.
+//! Imports and base grammars refer to other `Grammar` values, so they are written as comments and are not read back.
+
+use std::error::Error;
+use std::fmt;
+
+use crate::core::Parser;
+use crate::ops::{
+    Action,
+    Alternation,
+    BackReference,
+    ByteClass,
+    Capture,
+    Counted,
+    Dedent,
+    Grammar,
+    Indent,
+    Integer,
+    Label,
+    Literal,
+    Lookahead,
+    OperatorTable,
+    Predicate,
+    Quantity,
+    Regex,
+    RuleCall,
+    RuleReference,
+    Samedent,
+    SepBy,
+    Sequence,
+    Take,
+    Until,
+};
+
+mod printer;
+mod reader;
+
+pub(crate) use self::printer::write_expression;
+
+/// Returned by `Grammar::from_peg_string` when the text is not a valid grammar
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PegSyntaxError {
+    position: usize,
+    message: String,
+}
+
+impl PegSyntaxError {
+    pub(crate) fn new(position: usize, message: String) -> Self {
+        Self { position, message }
+    }
+    /// The byte offset in the grammar text where the error was found
+    pub fn get_position(&self) -> usize {
+        self.position
+    }
+    pub fn get_message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for PegSyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl Error for PegSyntaxError {}
+
+impl<S: 'static> Grammar<S> {
+    /// Reads a grammar from PEG text, in the format written by `to_peg_string()`
+    ///
+    /// ```
+    /// # use npeg_rs::*;
+    /// # use std::rc::Rc;
+    /// let gram = Rc::new(Grammar::from_peg_string(r#"
+    ///     Sum    <- Number ("+" Number)*
+    ///     Number <- r"[0-9]+"
+    /// "#).unwrap());
+    /// let mut context = Box::new(ParserContext::new("1+22+3"));
+    /// let result = gram.clone().parse(&mut context, 0).unwrap();
+    /// assert_eq!(result.len(), 6);
+    /// assert_eq!(gram.to_peg_string(), "Sum <- Number (\"+\" Number)*\nNumber <- r\"[0-9]+\"\n");
+    /// ```
+    pub fn from_peg_string(text: &str) -> Result<Grammar<S>, PegSyntaxError> {
+        let mut reader = reader::Reader::new(text);
+        let grammar = reader.rules()?;
+        reader.end()?;
+        Ok(grammar)
+    }
+}
+
+/// Writes the whole grammar, one rule per line.
+/// Where a `Grammar` is nested within another it is written as `{ A <- a  B <- b }` instead.
+impl<S> fmt::Display for Grammar<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        printer::write_grammar(f, self)
+    }
+}
+
+/// Ops which contain other ops are generic over the user state; the others are written as if there were none
+macro_rules! impl_display {
+    ($($op:ty),*; $($leaf_op:ty),*) => {
+        $(
+            impl<S> fmt::Display for $op {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    write_expression(f, self.kind())
+                }
+            }
+        )*
+        $(
+            impl fmt::Display for $leaf_op {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    write_expression(f, Parser::<()>::kind(self))
+                }
+            }
+        )*
+    };
+}
+
+impl_display!(
+    dyn Parser<S>,
+    Action<S>,
+    Alternation<S>,
+    Capture<S>,
+    Counted<S>,
+    Label<S>,
+    Lookahead<S>,
+    OperatorTable<S>,
+    Predicate<S>,
+    Quantity<S>,
+    RuleCall<S>,
+    SepBy<S>,
+    Sequence<S>,
+    Until<S>;
+    BackReference,
+    ByteClass,
+    Dedent,
+    Indent,
+    Integer,
+    Literal,
+    Regex,
+    RuleReference,
+    Samedent,
+    Take
+);
+
+#[cfg(feature = "unicode-segmentation")]
+impl_display!(; crate::ops::AnyGrapheme);
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::*;
+
+    /// Prints, reads back and prints again; both printings must be the same
+    fn round_trip(gram: &Grammar) -> Grammar {
+        let text = gram.to_peg_string();
+        let read = Grammar::from_peg_string(&text).unwrap_or_else(|error| panic!("{}\n{}", error, text));
+        assert_eq!(read.to_peg_string(), text);
+        read
+    }
+
+    #[test]
+    fn precedence_and_parentheses() {
+        let gram = Grammar::new(None, vec![
+            ("A", alt!(seq!(lit!("a"), rul!("B")), alt!(lit!("b"), lit!("c")))),
+            ("B", seq!(qtt!(seq!(lit!("x"), lit!("y")), 0, None), qtt!(lit!("z"), 1, None), qtt!(rul!("A"), 0, Some(1)))),
+            ("C", seq!(lit!("a"), seq!(lit!("b"), lit!("c")), qtt!(qtt!(lit!("d"), 2, Some(5)), 3, None))),
+        ]);
+        assert_eq!(
+            gram.to_peg_string(),
+            concat!(
+                "A <- \"a\" B / (\"b\" / \"c\")\n",
+                "B <- (\"x\" \"y\")* \"z\"+ A?\n",
+                "C <- \"a\" (\"b\" \"c\") (\"d\"{2,5}){3,}\n",
+            )
+        );
+        round_trip(&gram);
+    }
+
+    #[test]
+    fn lookahead_and_labels() {
+        let lookahead: Rc<dyn Parser> = Rc::new(Lookahead::new(lbl!(reg!("[a-z]+"), "word"), lit!("("), false));
+        let gram = Grammar::new(None, vec![
+            ("A", seq!(lit!("a"), lookahead.clone(), lbl!(seq!(lit!("b"), lit!("c")), "ATOM B"))),
+            ("B", Rc::new(Lookahead::new(seq!(lit!("a"), lit!("b")), qtt!(lit!("c"), 1, None), true))),
+            ("C", Rc::new(Lookahead::new(lookahead, lit!("d"), true))),
+        ]);
+        assert_eq!(
+            gram.to_peg_string(),
+            concat!(
+                "A <- \"a\" word:r\"[a-z]+\" !\"(\" \"ATOM B\":(\"b\" \"c\")\n",
+                "B <- (\"a\" \"b\") &\"c\"+\n",
+                "C <- word:r\"[a-z]+\" !\"(\" &\"d\"\n",
+            )
+        );
+        round_trip(&gram);
+    }
+
+    #[test]
+    fn literals_and_regexes_are_escaped() {
+        let gram = Grammar::new(None, vec![
+            ("A", seq!(lit!("say \"hi\"\\\n\t\u{7}"), reg!("\"[^\"\\\\]*\"" mi), reg!("(?x) a # comment"))),
+        ]);
+        assert_eq!(
+            gram.to_peg_string(),
+            "A <- \"say \\\"hi\\\"\\\\\\n\\t\\u{7}\" r\"\\\"[^\\\"\\\\]*\\\"\"mi r\"(?x) a # comment\"\n"
+        );
+        let read = round_trip(&gram);
+        let (_, rule) = read.get_rule_by_name("A").unwrap();
+        match rule.kind() {
+            OpKind::Sequence(children) => {
+                assert!(matches!(children[0].kind(), OpKind::Literal("say \"hi\"\\\n\t\u{7}")));
+                assert!(matches!(children[1].kind(), OpKind::Regex { pattern: "\"[^\"\\\\]*\"", multi_line: true, case_insensitive: true, dot_matches_new_line: false }));
+            },
+            _ => panic!("Expected a Sequence"),
+        }
+    }
+
+    #[test]
+    fn parameterized_rules_and_builtins() {
+        let gram = Grammar::new(Some("Block"), vec![
+            ("Raw",   seq!(cap!(reg!("#*"), "hashes"), lit!("\""), brf!("hashes"))),
+            ("Block", seq!(idt!(), rul!("json::Value"), qtt!(seq!(sdt!(), call!("List", rul!("Raw"), lit!(","))), 0, None), ddt!())),
+            ("Inner", Rc::new(Grammar::new(Some("Y"), vec![("X", lit!("x")), ("Y", rul!("X"))]))),
+        ]).with_parameterized_rule("List", vec!["X", "Sep"], seq!(rul!("X"), qtt!(seq!(rul!("Sep"), rul!("X")), 0, None)));
+        assert_eq!(
+            gram.to_peg_string(),
+            concat!(
+                "@start Block\n",
+                "Raw <- @capture(hashes, r\"#*\") \"\\\"\" @backref(hashes)\n",
+                "Block <- @indent json::Value (@samedent List(Raw, \",\"))* @dedent\n",
+                "Inner <- { @start Y X <- \"x\" Y <- X }\n",
+                "List(X, Sep) <- X (Sep X)*\n",
+            )
+        );
+        let read = round_trip(&gram);
+        assert_eq!(read.get_starting_rule_name().unwrap().as_str(), "Block");
+        assert_eq!(read.get_rule_parameters("List").len(), 2);
+    }
+
+    #[test]
+    fn byte_ops() {
+        let gram = Grammar::new(None, vec![
+            ("Header", seq!(byt!(b"\x89PNG\"\\\r"), rul!("Chunk"), bcl!(0x00..=0x1f, 0x7f..=0x7f))),
+            ("Chunk",  seq!(cap!(int!(4, Big), "length"), Rc::new(Integer::new(2, Endianness::Little).with_range(1, 3)), take!("length", Little))),
+        ]);
+        assert_eq!(
+            gram.to_peg_string(),
+            concat!(
+                "Header <- b\"\\x89PNG\\\"\\\\\\x0d\" Chunk @byteclass(0x00..=0x1f, 0x7f)\n",
+                "Chunk <- @capture(length, @int(4, big)) @int(2, little, 1..=3) @take(length, little)\n",
+            )
+        );
+        let read = Rc::new(round_trip(&gram));
+        let input = b"\x89PNG\"\\\r\x02\x00\x00\x00\x03\x00ab\x7f";
+        assert_eq!(parse_bytes(read.clone(), input).unwrap().unwrap().get_end_position(), input.len());
+        assert!(parse_bytes(read, &input[..input.len() - 1]).unwrap().is_none());
+        // Bytes which are UTF-8 are written as text
+        assert_eq!(byt!(b"ab").to_string(), "\"ab\"");
+    }
+
+    #[test]
+    fn list_ops() {
+        let gram = Grammar::new(None, vec![
+            ("Lists",   seq!(sep!(rul!("Item"), lit!(",")), Rc::new(SepBy::new(rul!("Item"), alt!(lit!(";"), lit!("|"))).with_occurrences(1, Some(3)).with_trailing_separator(true)))),
+            ("Item",    alt!(Rc::new(SepBy::new(lit!("a"), lit!("+")).with_occurrences(2, None)), unt!(reg!("."), lit!("*/")))),
+            ("Counts",  seq!(cap!(reg!("[0-9]"), "n"), cnt!(rul!("Item"), "n"), Rc::new(Counted::new(lit!("b"), "n").with_binary_count(Endianness::Big)))),
+        ]);
+        assert_eq!(
+            gram.to_peg_string(),
+            concat!(
+                "Lists <- @sepby(Item, \",\") @sepby(Item, \";\" / \"|\", 1..=3, trailing)\n",
+                "Item <- @sepby(\"a\", \"+\", 2..) / @until(r\".\", \"*/\")\n",
+                "Counts <- @capture(n, r\"[0-9]\") @counted(n, Item) @counted(n, \"b\", big)\n",
+            )
+        );
+        let read = Rc::new(round_trip(&gram));
+        let result = parse(read.clone(), "a+a,x*/a+a;a+a;").unwrap().unwrap();
+        assert_eq!(result.get_end_position(), 15);
+        assert!(parse(read, "a,a").unwrap().is_none());
+    }
+
+    /// Every rule, parameter and capture is named `name`
+    fn grammar_named(name: &str) -> Grammar {
+        Grammar::new(Some(name), vec![
+            ("A", seq!(Rc::new(Capture::new(lit!("a"), name)), Rc::new(BackReference::new(name)), Rc::new(RuleCall::new(name, vec![rul!("A")])))),
+        ]).with_parameterized_rule(name, vec![name, "B"], seq!(Rc::new(RuleReference::new(name)), rul!("B")))
+    }
+
+    #[test]
+    fn names_which_are_not_identifiers_are_quoted() {
+        let gram = grammar_named("my rule");
+        assert_eq!(
+            gram.to_peg_string(),
+            concat!(
+                "@start `my rule`\n",
+                "A <- @capture(`my rule`, \"a\") @backref(`my rule`) `my rule`(A)\n",
+                "`my rule`(`my rule`, B) <- `my rule` B\n",
+            )
+        );
+        round_trip(&gram);
+        for name in ["", "1st", "a-b", "a::b", "::", "`\\\"", "\n\u{0}", "é", "a b <- c"] {
+            let read = round_trip(&grammar_named(name));
+            assert_eq!(read.get_rule_parameters(name).len(), 2, "{:?}", name);
+        }
+        // References into imported grammars keep their `::`
+        assert_eq!(rul!("json::Value").to_string(), "json::Value");
+    }
+
+    #[cfg(feature = "proptest")]
+    proptest::proptest! {
+        #[test]
+        fn any_name_round_trips(name in "\\PC*|[a-z:`\"\\\\ ]*") {
+            proptest::prop_assume!(name != "A" && name != "B");
+            let read = round_trip(&grammar_named(&name));
+            proptest::prop_assert_eq!(read.get_rule_parameters(&name).len(), 2);
+        }
+    }
+
+    #[test]
+    fn read_grammar_parses_like_the_original() {
+        let text = "
+            # a comment
+            Prog  <- \"(\" Quant
+            Quant <- Wurd{0,5}
+            Wurd  <- WORD:r\"[a-z]+\" \" \"?
+        ";
+        let gram = Rc::new(Grammar::from_peg_string(text).unwrap());
+        let mut context = Box::new(ParserContext::new("(one two three"));
+        let result = gram.parse(&mut context, 0).unwrap();
+        assert_eq!(result.len(), 14);
+    }
+
+    #[test]
+    fn ops_display_as_expressions() {
+        let seq: Rc<dyn Parser> = seq!(lit!("a"), alt!(lit!("b"), lit!("c")));
+        assert_eq!(seq.to_string(), "\"a\" (\"b\" / \"c\")");
+        assert_eq!(Quantity::<()>::new(lit!("a"), 2, Some(3)).to_string(), "\"a\"{2,3}");
+        assert_eq!(Regex::new("x", false, false, true).to_string(), "r\"x\"s");
+    }
+
+    #[test]
+    fn syntax_errors() {
+        for (text, position) in [
+            ("", 0),
+            ("A <- ", 5),
+            ("A <- \"a", 5),
+            ("A <- \"\"", 7),
+            ("A <- &\"a\"", 5),
+            ("A <- (\"a\"", 9),
+            ("A <- r\"(\"", 5),
+            ("A <- \"a\"{3,2}", 12),
+            ("A <- @what", 5),
+            ("A <- \"a\" ]", 9),
+            ("A <- \"a\" A <- \"b\"", 9),
+            ("A <- b\"\\x8\"", 7),
+            ("A <- b\"é\"", 7),
+            ("A <- @int(9, big)", 10),
+            ("A <- @take(n, middle)", 14),
+            ("A <- @byteclass(0x02..=0x01)", 27),
+            ("A <- @predicate(\"a\", \"b\")", 5),
+            ("A <- \"a\" @action(\"b\")", 9),
+            ("A <- @sepby(\"a\", \"b\", 3..=2)", 27),
+            ("A <- @sepby(\"a\", \"b\", trailer)", 22),
+            ("A <- @counted(n, \"a\", middle)", 22),
+            ("A <- @operators(a, sideways(\"b\", b, 1))", 19),
+            ("A <- @operators(a, infix(\"b\", b, 1, up))", 36),
+        ] {
+            let error = Grammar::<()>::from_peg_string(text).unwrap_err();
+            assert_eq!(error.get_position(), position, "{:?}: {}", text, error);
+        }
+    }
+}
@@ -0,0 +1,345 @@
+use std::fmt::{self, Write};
+use std::rc::Rc;
+
+use crate::core::{OpKind, Parser};
+use crate::ops::{Associativity, Endianness, Grammar};
+
+/// How tightly an expression binds; an op is parenthesized where something binding more tightly is expected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Precedence {
+    Choice,
+    Sequence,
+    /// Lookahead and labels; `a &b`, `label:a`
+    Item,
+    /// Repetition; `a*`, `a{2,3}`
+    Suffix,
+    Primary,
+}
+
+fn precedence_of<S>(kind: &OpKind<S>) -> Precedence {
+    match kind {
+        OpKind::Alternation(_) => Precedence::Choice,
+        OpKind::Sequence(_) => Precedence::Sequence,
+        OpKind::Lookahead { .. } | OpKind::Label { .. } => Precedence::Item,
+        OpKind::Quantity { .. } => Precedence::Suffix,
+        _ => Precedence::Primary,
+    }
+}
+
+pub(crate) fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    matches!(chars.next(), Some(first) if first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|each| each.is_ascii_alphanumeric() || each == '_')
+}
+
+/// Rule and capture names are written as they are if they are identifiers, and in backquotes otherwise
+fn write_name(out: &mut dyn Write, name: &str) -> fmt::Result {
+    if is_identifier(name) {
+        out.write_str(name)
+    } else {
+        write_delimited(out, name, '`')
+    }
+}
+
+/// Labels are written as they are if they are identifiers, and in double quotes otherwise
+fn write_label(out: &mut dyn Write, label: &str) -> fmt::Result {
+    if is_identifier(label) {
+        out.write_str(label)
+    } else {
+        write_quoted(out, label)
+    }
+}
+
+/// Like `write_name`, but `namespace::Name` is written as it is
+fn write_rule_name(out: &mut dyn Write, rule_name: &str) -> fmt::Result {
+    if rule_name.split("::").all(is_identifier) {
+        out.write_str(rule_name)
+    } else {
+        write_delimited(out, rule_name, '`')
+    }
+}
+
+pub(crate) fn write_quoted(out: &mut dyn Write, text: &str) -> fmt::Result {
+    write_delimited(out, text, '"')
+}
+
+fn write_delimited(out: &mut dyn Write, text: &str, delimiter: char) -> fmt::Result {
+    out.write_char(delimiter)?;
+    for each in text.chars() {
+        match each {
+            each if each == delimiter => write!(out, "\\{}", each)?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            '\r' => out.write_str("\\r")?,
+            '\t' => out.write_str("\\t")?,
+            each if each.is_control() => write!(out, "\\u{{{:x}}}", each as u32)?,
+            each => out.write_char(each)?,
+        }
+    }
+    out.write_char(delimiter)
+}
+
+fn endianness_name(endianness: Endianness) -> &'static str {
+    match endianness {
+        Endianness::Big => "big",
+        Endianness::Little => "little",
+    }
+}
+
+fn write_children<S>(out: &mut dyn Write, children: &[Rc<dyn Parser<S>>], separator: &str, precedence: Precedence) -> fmt::Result {
+    for (index, child) in children.iter().enumerate() {
+        if index > 0 {
+            out.write_str(separator)?;
+        }
+        write_op(out, child.kind(), precedence)?;
+    }
+    Ok(())
+}
+
+/// Writes the op in PEG text, in parentheses if it binds less tightly than `precedence`
+fn write_op<S>(out: &mut dyn Write, kind: OpKind<S>, precedence: Precedence) -> fmt::Result {
+    if precedence_of(&kind) < precedence {
+        out.write_char('(')?;
+        write_op(out, kind, Precedence::Choice)?;
+        return out.write_char(')');
+    }
+    match kind {
+        OpKind::Alternation(children) => write_children(out, children, " / ", Precedence::Sequence),
+        OpKind::Sequence(children) => write_children(out, children, " ", Precedence::Item),
+        OpKind::Lookahead { child, scout, accept_match } => {
+            write_op(out, child.kind(), Precedence::Item)?;
+            out.write_str(if accept_match { " &" } else { " !" })?;
+            write_op(out, scout.kind(), Precedence::Suffix)
+        },
+        OpKind::Label { child, label } => {
+            write_label(out, label)?;
+            out.write_char(':')?;
+            write_op(out, child.kind(), Precedence::Suffix)
+        },
+        OpKind::Quantity { child, minimum_occurrences, maximum_occurrences } => {
+            write_op(out, child.kind(), Precedence::Primary)?;
+            match (minimum_occurrences, maximum_occurrences) {
+                (0, None) => out.write_char('*'),
+                (1, None) => out.write_char('+'),
+                (0, Some(1)) => out.write_char('?'),
+                (minimum_occurrences, None) => write!(out, "{{{},}}", minimum_occurrences),
+                (minimum_occurrences, Some(maximum_occurrences)) => write!(out, "{{{},{}}}", minimum_occurrences, maximum_occurrences),
+            }
+        },
+        OpKind::Literal(literal_text) => write_quoted(out, literal_text),
+        OpKind::ByteLiteral(literal_bytes) => {
+            out.write_str("b\"")?;
+            for byte in literal_bytes {
+                match byte {
+                    b'"' | b'\\' => write!(out, "\\{}", *byte as char)?,
+                    0x20..=0x7e => out.write_char(*byte as char)?,
+                    _ => write!(out, "\\x{:02x}", byte)?,
+                }
+            }
+            out.write_char('"')
+        },
+        OpKind::ByteClass(ranges) => {
+            out.write_str("@byteclass(")?;
+            for (index, range) in ranges.iter().enumerate() {
+                if index > 0 {
+                    out.write_str(", ")?;
+                }
+                write!(out, "{:#04x}", range.start())?;
+                if range.end() != range.start() {
+                    write!(out, "..={:#04x}", range.end())?;
+                }
+            }
+            out.write_char(')')
+        },
+        OpKind::Integer { width, endianness, minimum, maximum } => {
+            write!(out, "@int({}, {}", width, endianness_name(endianness))?;
+            if minimum != 0 || maximum != u64::MAX {
+                write!(out, ", {}..={}", minimum, maximum)?;
+            }
+            out.write_char(')')
+        },
+        OpKind::Take { name, endianness } => {
+            out.write_str("@take(")?;
+            write_name(out, name)?;
+            write!(out, ", {})", endianness_name(endianness))
+        },
+        OpKind::Regex { pattern, multi_line, case_insensitive, dot_matches_new_line } => {
+            // The pattern is written verbatim except that unescaped quotes are escaped
+            out.write_str("r\"")?;
+            let mut escaped = false;
+            for each in pattern.chars() {
+                if each == '"' && !escaped {
+                    out.write_char('\\')?;
+                }
+                escaped = each == '\\' && !escaped;
+                out.write_char(each)?;
+            }
+            out.write_char('"')?;
+            if multi_line {
+                out.write_char('m')?;
+            }
+            if case_insensitive {
+                out.write_char('i')?;
+            }
+            if dot_matches_new_line {
+                out.write_char('s')?;
+            }
+            Ok(())
+        },
+        OpKind::RuleReference(rule_name) => write_rule_name(out, rule_name),
+        OpKind::RuleCall { rule_name, arguments } => {
+            write_rule_name(out, rule_name)?;
+            out.write_char('(')?;
+            write_children(out, arguments, ", ", Precedence::Choice)?;
+            out.write_char(')')
+        },
+        OpKind::Capture { child, name } => {
+            out.write_str("@capture(")?;
+            write_name(out, name)?;
+            out.write_str(", ")?;
+            write_op(out, child.kind(), Precedence::Choice)?;
+            out.write_char(')')
+        },
+        OpKind::Counted { child, name, endianness } => {
+            out.write_str("@counted(")?;
+            write_name(out, name)?;
+            out.write_str(", ")?;
+            write_op(out, child.kind(), Precedence::Choice)?;
+            if let Some(endianness) = endianness {
+                write!(out, ", {}", endianness_name(endianness))?;
+            }
+            out.write_char(')')
+        },
+        OpKind::SepBy { element, separator, minimum_occurrences, maximum_occurrences, trailing_separator } => {
+            out.write_str("@sepby(")?;
+            write_op(out, element.kind(), Precedence::Choice)?;
+            out.write_str(", ")?;
+            write_op(out, separator.kind(), Precedence::Choice)?;
+            match (minimum_occurrences, maximum_occurrences) {
+                (0, None) => {},
+                (minimum_occurrences, None) => write!(out, ", {}..", minimum_occurrences)?,
+                (minimum_occurrences, Some(maximum_occurrences)) => write!(out, ", {}..={}", minimum_occurrences, maximum_occurrences)?,
+            }
+            if trailing_separator {
+                out.write_str(", trailing")?;
+            }
+            out.write_char(')')
+        },
+        OpKind::Until { child, terminator } => {
+            out.write_str("@until(")?;
+            write_op(out, child.kind(), Precedence::Choice)?;
+            out.write_str(", ")?;
+            write_op(out, terminator.kind(), Precedence::Choice)?;
+            out.write_char(')')
+        },
+        OpKind::BackReference(name) => {
+            out.write_str("@backref(")?;
+            write_name(out, name)?;
+            out.write_char(')')
+        },
+        OpKind::OperatorTable(table) => {
+            out.write_str("@operators(")?;
+            write_op(out, table.get_atom().kind(), Precedence::Choice)?;
+            let fixities = [
+                ("prefix", table.get_prefix_operators()),
+                ("infix", table.get_infix_operators()),
+                ("postfix", table.get_postfix_operators()),
+            ];
+            for (fixity, operators) in fixities {
+                for operator in operators {
+                    write!(out, ", {}(", fixity)?;
+                    write_op(out, operator.get_parser().kind(), Precedence::Choice)?;
+                    out.write_str(", ")?;
+                    write_label(out, operator.get_label())?;
+                    write!(out, ", {}", operator.get_precedence())?;
+                    if fixity == "infix" {
+                        out.write_str(match operator.get_associativity() {
+                            Associativity::Left => ", left",
+                            Associativity::Right => ", right",
+                        })?;
+                    }
+                    out.write_char(')')?;
+                }
+            }
+            out.write_char(')')
+        },
+        OpKind::Action { child, .. } => {
+            out.write_str("@action(")?;
+            write_op(out, child.kind(), Precedence::Choice)?;
+            out.write_char(')')
+        },
+        OpKind::Predicate { child, description } => {
+            out.write_str("@predicate(")?;
+            write_op(out, child.kind(), Precedence::Choice)?;
+            out.write_str(", ")?;
+            write_quoted(out, description)?;
+            out.write_char(')')
+        },
+        OpKind::Indent => out.write_str("@indent"),
+        OpKind::Samedent => out.write_str("@samedent"),
+        OpKind::Dedent => out.write_str("@dedent"),
+        OpKind::AnyGrapheme => out.write_str("@grapheme"),
+        OpKind::Grammar(grammar) => {
+            out.write_str("{ ")?;
+            write_starting_rule(out, grammar, " ")?;
+            write_rules(out, grammar, " ")?;
+            out.write_str(" }")
+        },
+        OpKind::Other => out.write_str("@unknown"),
+    }
+}
+
+/// Writes `@start Name` unless the starting rule is the first rule, which is the default
+fn write_starting_rule<S>(out: &mut dyn Write, grammar: &Grammar<S>, separator: &str) -> fmt::Result {
+    let first_rule_name = grammar.get_rules().first().map(|(rule_name, _parser_operator)| rule_name.as_str());
+    match grammar.get_declared_starting_rule_name() {
+        Some(starting_rule_name) if Some(starting_rule_name) != first_rule_name => {
+            out.write_str("@start ")?;
+            write_name(out, starting_rule_name)?;
+            out.write_str(separator)
+        },
+        _ => Ok(()),
+    }
+}
+
+/// Writes each rule as `Name <- expression`, or `Name(Parameter, ...) <- expression`
+fn write_rules<S>(out: &mut dyn Write, grammar: &Grammar<S>, separator: &str) -> fmt::Result {
+    for (index, (rule_name, parser_operator)) in grammar.get_rules().iter().enumerate() {
+        if index > 0 {
+            out.write_str(separator)?;
+        }
+        write_name(out, rule_name)?;
+        let parameters = grammar.get_rule_parameters(rule_name);
+        if !parameters.is_empty() {
+            out.write_char('(')?;
+            for (index, parameter) in parameters.iter().enumerate() {
+                if index > 0 {
+                    out.write_str(", ")?;
+                }
+                write_name(out, parameter)?;
+            }
+            out.write_char(')')?;
+        }
+        out.write_str(" <- ")?;
+        write_op(out, parser_operator.kind(), Precedence::Choice)?;
+    }
+    Ok(())
+}
+
+/// Writes a single op as a PEG expression
+pub(crate) fn write_expression<S>(out: &mut dyn Write, kind: OpKind<S>) -> fmt::Result {
+    write_op(out, kind, Precedence::Choice)
+}
+
+/// Writes a grammar as PEG text with one rule per line
+pub(crate) fn write_grammar<S>(out: &mut dyn Write, grammar: &Grammar<S>) -> fmt::Result {
+    write_starting_rule(out, grammar, "\n")?;
+    for (namespace, _import) in grammar.get_all_imports() {
+        writeln!(out, "# imports {} (not included)", namespace)?;
+    }
+    if grammar.get_base().is_some() {
+        writeln!(out, "# extends a base grammar (not included)")?;
+    }
+    write_rules(out, grammar, "\n")?;
+    out.write_char('\n')
+}
@@ -0,0 +1,726 @@
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use super::PegSyntaxError;
+use crate::core::Parser;
+use crate::ops::{
+    Alternation,
+    Associativity,
+    BackReference,
+    ByteClass,
+    Capture,
+    Counted,
+    Dedent,
+    Endianness,
+    Grammar,
+    Indent,
+    Integer,
+    Label,
+    Literal,
+    Lookahead,
+    OperatorTable,
+    Quantity,
+    Regex,
+    RuleCall,
+    RuleReference,
+    Samedent,
+    SepBy,
+    Sequence,
+    Take,
+    Until,
+};
+
+type ReadResult<T> = Result<T, PegSyntaxError>;
+
+/// A rule read: its name, its parameters and its op
+type ReadRule<S> = (String, Vec<String>, Rc<dyn Parser<S>>);
+
+/// A recursive descent reader for the text produced by `Grammar::to_peg_string`, which makes ops for user state `S`
+pub(crate) struct Reader<'a, S> {
+    text: &'a str,
+    position: usize,
+    user_state: PhantomData<S>,
+}
+
+impl<'a, S: 'static> Reader<'a, S> {
+    pub(crate) fn new(text: &'a str) -> Self {
+        Self { text, position: 0, user_state: PhantomData }
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> ReadResult<T> {
+        Err(PegSyntaxError::new(self.position, message.into()))
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    /// Skips whitespace and `#` comments
+    fn skip_spacing(&mut self) {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.position += rest.len() - trimmed.len();
+            if trimmed.starts_with('#') {
+                self.position += trimmed.find('\n').unwrap_or(trimmed.len());
+            } else {
+                return;
+            }
+        }
+    }
+
+    /// Consumes `token` if it is next, after skipping spacing
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_spacing();
+        if self.rest().starts_with(token) {
+            self.position += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> ReadResult<()> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            self.error(format!("Expected '{}'", token))
+        }
+    }
+
+    /// Reads an identifier which must start exactly at the current position
+    fn identifier_here(&mut self) -> Option<&'a str> {
+        let rest = self.rest();
+        let mut chars = rest.char_indices();
+        match chars.next() {
+            Some((_, first)) if first.is_ascii_alphabetic() || first == '_' => (),
+            _ => return None,
+        }
+        let length = chars
+            .find(|(_, each)| !(each.is_ascii_alphanumeric() || *each == '_'))
+            .map_or(rest.len(), |(index, _)| index);
+        self.position += length;
+        Some(&rest[..length])
+    }
+
+    fn identifier(&mut self) -> ReadResult<&'a str> {
+        self.skip_spacing();
+        match self.identifier_here() {
+            Some(identifier) => Ok(identifier),
+            None => self.error("Expected an identifier"),
+        }
+    }
+
+    /// Reads a rule, parameter or capture name; an identifier, or any text in backquotes
+    fn name(&mut self) -> ReadResult<String> {
+        self.skip_spacing();
+        if self.peek() == Some('`') {
+            self.quoted()
+        } else {
+            self.identifier().map(|identifier| identifier.to_owned())
+        }
+    }
+
+    /// Reads a label; an identifier, or any text in double quotes
+    fn label(&mut self) -> ReadResult<String> {
+        self.skip_spacing();
+        if self.peek() == Some('"') {
+            self.quoted()
+        } else {
+            self.identifier().map(|identifier| identifier.to_owned())
+        }
+    }
+
+    fn number<T: std::str::FromStr>(&mut self) -> ReadResult<T> {
+        self.skip_spacing();
+        let rest = self.rest();
+        let length = rest.find(|each: char| !each.is_ascii_digit()).unwrap_or(rest.len());
+        match rest[..length].parse() {
+            Ok(number) => {
+                self.position += length;
+                Ok(number)
+            },
+            Err(_) => self.error("Expected a number"),
+        }
+    }
+
+    /// Reads a byte written as `0x` and two hex digits
+    fn byte(&mut self) -> ReadResult<u8> {
+        self.skip_spacing();
+        match self.rest().strip_prefix("0x").and_then(hex_byte) {
+            Some(byte) => {
+                self.position += 4;
+                Ok(byte)
+            },
+            None => self.error("Expected a byte, e.g. 0x7f"),
+        }
+    }
+
+    /// Reads `big` or `little`
+    fn endianness(&mut self) -> ReadResult<Endianness> {
+        self.skip_spacing();
+        let position = self.position;
+        match self.identifier_here() {
+            Some("big") => Ok(Endianness::Big),
+            Some("little") => Ok(Endianness::Little),
+            _ => {
+                self.position = position;
+                self.error("Expected big or little")
+            },
+        }
+    }
+
+    /// Reads a string in double quotes or backquotes starting at the current position, interpreting escapes
+    fn quoted(&mut self) -> ReadResult<String> {
+        let start_position = self.position;
+        let delimiter = self.peek();
+        self.position += 1;
+        let mut result = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((index, each)) = chars.next() {
+            match each {
+                each if Some(each) == delimiter => {
+                    self.position += index + 1;
+                    return Ok(result);
+                },
+                '\\' => match chars.next() {
+                    Some((_, '"')) => result.push('"'),
+                    Some((_, '`')) => result.push('`'),
+                    Some((_, '\\')) => result.push('\\'),
+                    Some((_, 'n')) => result.push('\n'),
+                    Some((_, 'r')) => result.push('\r'),
+                    Some((_, 't')) => result.push('\t'),
+                    Some((escape_index, 'u')) => {
+                        let escape = &self.rest()[escape_index + 1..];
+                        let code_point = escape
+                            .strip_prefix('{')
+                            .and_then(|escape| escape.split_once('}'))
+                            .and_then(|(hex, _)| u32::from_str_radix(hex, 16).ok().map(|code_point| (hex.len(), code_point)))
+                            .and_then(|(hex_length, code_point)| char::from_u32(code_point).map(|code_point| (hex_length, code_point)));
+                        match code_point {
+                            Some((hex_length, code_point)) => {
+                                result.push(code_point);
+                                // skip the braces and the hex digits
+                                for _ in 0..hex_length + 2 {
+                                    chars.next();
+                                }
+                            },
+                            None => {
+                                self.position += escape_index;
+                                return self.error("Invalid unicode escape");
+                            },
+                        }
+                    },
+                    _ => {
+                        self.position += index;
+                        return self.error("Invalid escape");
+                    },
+                },
+                each => result.push(each),
+            }
+        }
+        self.position = start_position;
+        self.error("Unterminated string")
+    }
+
+    /// Reads `r"pattern"flags`; the pattern is verbatim except for escaped quotes
+    fn regex(&mut self) -> ReadResult<Rc<dyn Parser<S>>> {
+        let start_position = self.position;
+        self.position += 2;
+        let mut pattern = String::new();
+        let mut chars = self.rest().char_indices();
+        let mut closed = false;
+        while let Some((index, each)) = chars.next() {
+            match each {
+                '"' => {
+                    self.position += index + 1;
+                    closed = true;
+                    break;
+                },
+                '\\' => match chars.next() {
+                    Some((_, '"')) => pattern.push('"'),
+                    Some((_, escaped)) => {
+                        pattern.push('\\');
+                        pattern.push(escaped);
+                    },
+                    None => pattern.push('\\'),
+                },
+                each => pattern.push(each),
+            }
+        }
+        if !closed {
+            self.position = start_position;
+            return self.error("Unterminated regular expression");
+        }
+        let (mut multi_line, mut case_insensitive, mut dot_matches_new_line) = (false, false, false);
+        while let Some(flag) = self.peek() {
+            match flag {
+                'm' => multi_line = true,
+                'i' => case_insensitive = true,
+                's' => dot_matches_new_line = true,
+                _ => break,
+            }
+            self.position += 1;
+        }
+        if let Err(error) = regex::Regex::new(&pattern) {
+            self.position = start_position;
+            return self.error(format!("Invalid regular expression: {}", error));
+        }
+        Ok(Rc::new(Regex::new(&pattern, multi_line, case_insensitive, dot_matches_new_line)))
+    }
+
+    /// Reads `b"bytes"`; bytes other than printable ASCII are escaped as `\xNN`, and `\"` and `\\` are also escapes
+    fn byte_literal(&mut self) -> ReadResult<Rc<dyn Parser<S>>> {
+        let start_position = self.position;
+        self.position += 2;
+        let mut literal_bytes = vec![];
+        loop {
+            let rest = self.rest();
+            match rest.as_bytes().first() {
+                Some(b'"') => {
+                    self.position += 1;
+                    break;
+                },
+                Some(b'\\') => {
+                    let (byte, length) = match rest.as_bytes().get(1) {
+                        Some(escaped @ (b'"' | b'\\')) => (*escaped, 2),
+                        Some(b'x') => match rest.get(2..).and_then(hex_byte) {
+                            Some(byte) => (byte, 4),
+                            None => return self.error("Invalid byte escape"),
+                        },
+                        _ => return self.error("Invalid escape"),
+                    };
+                    literal_bytes.push(byte);
+                    self.position += length;
+                },
+                Some(byte @ 0x20..=0x7e) => {
+                    literal_bytes.push(*byte);
+                    self.position += 1;
+                },
+                Some(_) => return self.error("Bytes other than printable ASCII must be escaped"),
+                None => {
+                    self.position = start_position;
+                    return self.error("Unterminated byte string");
+                },
+            }
+        }
+        if literal_bytes.is_empty() {
+            self.position = start_position;
+            return self.error("Empty literals are not permitted");
+        }
+        Ok(Rc::new(Literal::from_bytes(&literal_bytes)))
+    }
+
+    /// Reads `Name`, `namespace::Name` or `` `any name` ``
+    fn rule_name(&mut self) -> ReadResult<String> {
+        self.skip_spacing();
+        if self.peek() == Some('`') {
+            return self.quoted();
+        }
+        let mut rule_name = self.identifier()?.to_owned();
+        while self.rest().starts_with("::") {
+            self.position += 2;
+            match self.identifier_here() {
+                Some(identifier) => {
+                    rule_name.push_str("::");
+                    rule_name.push_str(identifier);
+                },
+                None => return self.error("Expected an identifier after '::'"),
+            }
+        }
+        Ok(rule_name)
+    }
+
+    /// Reads `Name <-` or `Name(Parameter, ...) <-`
+    fn rule_header(&mut self) -> ReadResult<(String, Vec<String>)> {
+        let rule_name = self.name()?;
+        let mut parameters = vec![];
+        if self.rest().starts_with('(') {
+            self.position += 1;
+            loop {
+                parameters.push(self.name()?);
+                if !self.eat(",") {
+                    break;
+                }
+            }
+            self.expect(")")?;
+        }
+        self.expect("<-")?;
+        Ok((rule_name, parameters))
+    }
+
+    fn at_rule_header(&mut self) -> bool {
+        let position = self.position;
+        let result = self.rule_header().is_ok();
+        self.position = position;
+        result
+    }
+
+    /// Reads rules until something other than a rule header is found
+    pub(crate) fn rules(&mut self) -> ReadResult<Grammar<S>> {
+        let starting_rule_name = if self.eat("@start") {
+            Some(self.name()?)
+        } else {
+            None
+        };
+        let mut rules: Vec<ReadRule<S>> = vec![];
+        while self.at_rule_header() {
+            let header_position = self.position;
+            let (rule_name, parameters) = self.rule_header()?;
+            if rules.iter().any(|(each_rule_name, _, _)| *each_rule_name == rule_name) {
+                self.position = header_position;
+                self.skip_spacing();
+                return self.error(format!("Rule {} is defined more than once", rule_name));
+            }
+            rules.push((rule_name, parameters, self.choice()?));
+        }
+        if rules.is_empty() {
+            return self.error("Expected a rule");
+        }
+        let mut grammar = Grammar::new(starting_rule_name.as_deref(), vec![]);
+        for (rule_name, parameters, parser_operator) in rules {
+            grammar = if parameters.is_empty() {
+                grammar.with_rule(&rule_name, parser_operator)
+            } else {
+                grammar.with_parameterized_rule(&rule_name, parameters.iter().map(|parameter| parameter.as_str()).collect(), parser_operator)
+            };
+        }
+        Ok(grammar)
+    }
+
+    pub(crate) fn end(&mut self) -> ReadResult<()> {
+        self.skip_spacing();
+        if self.position == self.text.len() {
+            Ok(())
+        } else {
+            self.error("Unexpected text")
+        }
+    }
+
+    pub(crate) fn choice(&mut self) -> ReadResult<Rc<dyn Parser<S>>> {
+        let mut children = vec![self.sequence()?];
+        while self.eat("/") {
+            children.push(self.sequence()?);
+        }
+        Ok(if children.len() == 1 {
+            children.pop().unwrap()
+        } else {
+            Rc::new(Alternation::new(children))
+        })
+    }
+
+    fn sequence(&mut self) -> ReadResult<Rc<dyn Parser<S>>> {
+        let mut children: Vec<Rc<dyn Parser<S>>> = vec![];
+        loop {
+            self.skip_spacing();
+            let accept_match = match self.peek() {
+                Some('&') => true,
+                Some('!') => false,
+                Some('/' | ')' | '}' | ',') | None => break,
+                Some(_) if self.at_rule_header() => break,
+                Some(_) => {
+                    children.push(self.item()?);
+                    continue;
+                },
+            };
+            // `child &scout` applies to the item before it
+            let Some(child) = children.pop() else {
+                return self.error("Expected an expression before the lookahead");
+            };
+            self.position += 1;
+            let scout = self.suffix()?;
+            children.push(Rc::new(Lookahead::new(child, scout, accept_match)));
+        }
+        match children.len() {
+            0 => self.error("Expected an expression"),
+            1 => Ok(children.pop().unwrap()),
+            _ => Ok(Rc::new(Sequence::new(children))),
+        }
+    }
+
+    /// Reads `label:suffix` or `suffix`
+    fn item(&mut self) -> ReadResult<Rc<dyn Parser<S>>> {
+        let position = self.position;
+        let label = if self.peek() == Some('"') {
+            Some(self.quoted()?)
+        } else {
+            self.identifier_here().map(|identifier| identifier.to_owned())
+        };
+        match label {
+            Some(label) if self.rest().starts_with(':') && !self.rest().starts_with("::") => {
+                self.position += 1;
+                Ok(Rc::new(Label::new(self.suffix()?, &label)))
+            },
+            _ => {
+                self.position = position;
+                self.suffix()
+            },
+        }
+    }
+
+    /// Reads a primary followed by any number of `*`, `+`, `?`, `{min,max}` or `{min,}`
+    fn suffix(&mut self) -> ReadResult<Rc<dyn Parser<S>>> {
+        let mut result = self.primary()?;
+        loop {
+            self.skip_spacing();
+            let (minimum_occurrences, maximum_occurrences) = match self.peek() {
+                Some('*') => (0, None),
+                Some('+') => (1, None),
+                Some('?') => (0, Some(1)),
+                Some('{') if self.rest()[1..].trim_start().starts_with(|each: char| each.is_ascii_digit()) => {
+                    self.position += 1;
+                    let minimum_occurrences = self.number()?;
+                    self.expect(",")?;
+                    self.skip_spacing();
+                    let maximum_occurrences = if self.peek() == Some('}') {
+                        None
+                    } else {
+                        Some(self.number()?)
+                    };
+                    if !self.rest().starts_with('}') {
+                        return self.error("Expected '}'");
+                    }
+                    if maximum_occurrences.is_some_and(|maximum_occurrences| maximum_occurrences <= minimum_occurrences) {
+                        return self.error("The maximum must be greater than the minimum");
+                    }
+                    (minimum_occurrences, maximum_occurrences)
+                },
+                _ => return Ok(result),
+            };
+            self.position += 1;
+            result = Rc::new(Quantity::new(result, minimum_occurrences, maximum_occurrences));
+        }
+    }
+
+    fn primary(&mut self) -> ReadResult<Rc<dyn Parser<S>>> {
+        self.skip_spacing();
+        let rest = self.rest();
+        if rest.starts_with('"') {
+            let literal_text = self.quoted()?;
+            if literal_text.is_empty() {
+                return self.error("Empty literals are not permitted");
+            }
+            Ok(Rc::new(Literal::new(&literal_text)))
+        } else if rest.starts_with("r\"") {
+            self.regex()
+        } else if rest.starts_with("b\"") {
+            self.byte_literal()
+        } else if self.eat("(") {
+            let result = self.choice()?;
+            self.expect(")")?;
+            Ok(result)
+        } else if self.eat("{") {
+            let grammar = self.rules()?;
+            self.expect("}")?;
+            Ok(Rc::new(grammar))
+        } else if rest.starts_with('@') {
+            self.builtin()
+        } else {
+            let rule_name = self.rule_name()?;
+            if self.rest().starts_with('(') {
+                self.position += 1;
+                let mut arguments = vec![self.choice()?];
+                while self.eat(",") {
+                    arguments.push(self.choice()?);
+                }
+                self.expect(")")?;
+                Ok(Rc::new(RuleCall::new(&rule_name, arguments)))
+            } else {
+                Ok(Rc::new(RuleReference::new(&rule_name)))
+            }
+        }
+    }
+
+    /// Reads the ops which have no symbol of their own; `@capture(name, expression)`, `@backref(name)`,
+    /// `@indent`, `@samedent`, `@dedent`, `@grapheme`, `@byteclass(0x00..=0x1f, 0x7f)`, `@int(width, big)`,
+    /// `@int(width, little, minimum..=maximum)`, `@take(name, big)` and
+    /// `@operators(atom, prefix(operator, label, precedence), infix(operator, label, precedence, left), ...)`
+    fn builtin(&mut self) -> ReadResult<Rc<dyn Parser<S>>> {
+        let position = self.position;
+        self.position += 1;
+        match self.identifier_here() {
+            Some("capture") => {
+                self.expect("(")?;
+                let name = self.name()?;
+                self.expect(",")?;
+                let child = self.choice()?;
+                self.expect(")")?;
+                Ok(Rc::new(Capture::new(child, &name)))
+            },
+            Some("counted") => {
+                self.expect("(")?;
+                let name = self.name()?;
+                self.expect(",")?;
+                let mut counted = Counted::new(self.choice()?, &name);
+                if self.eat(",") {
+                    counted = counted.with_binary_count(self.endianness()?);
+                }
+                self.expect(")")?;
+                Ok(Rc::new(counted))
+            },
+            Some("sepby") => {
+                self.expect("(")?;
+                let element = self.choice()?;
+                self.expect(",")?;
+                let mut sep_by = SepBy::new(element, self.choice()?);
+                let mut more = self.eat(",");
+                self.skip_spacing();
+                if more && self.peek().is_some_and(|each| each.is_ascii_digit()) {
+                    let minimum_occurrences = self.number()?;
+                    self.expect("..")?;
+                    let maximum_occurrences = if self.eat("=") { Some(self.number()?) } else { None };
+                    if maximum_occurrences.is_some_and(|maximum_occurrences| maximum_occurrences < minimum_occurrences) {
+                        return self.error("The maximum must not be less than the minimum");
+                    }
+                    sep_by = sep_by.with_occurrences(minimum_occurrences, maximum_occurrences);
+                    more = self.eat(",");
+                }
+                if more {
+                    self.skip_spacing();
+                    let trailing_position = self.position;
+                    if self.identifier_here() != Some("trailing") {
+                        self.position = trailing_position;
+                        return self.error("Expected a range or trailing");
+                    }
+                    sep_by = sep_by.with_trailing_separator(true);
+                }
+                self.expect(")")?;
+                Ok(Rc::new(sep_by))
+            },
+            Some("until") => {
+                self.expect("(")?;
+                let child = self.choice()?;
+                self.expect(",")?;
+                let terminator = self.choice()?;
+                self.expect(")")?;
+                Ok(Rc::new(Until::new(child, terminator)))
+            },
+            Some("backref") => {
+                self.expect("(")?;
+                let name = self.name()?;
+                self.expect(")")?;
+                Ok(Rc::new(BackReference::new(&name)))
+            },
+            Some("indent") => Ok(Rc::new(Indent::new())),
+            Some("samedent") => Ok(Rc::new(Samedent::new())),
+            Some("dedent") => Ok(Rc::new(Dedent::new())),
+            #[cfg(feature = "unicode-segmentation")]
+            Some("grapheme") => Ok(Rc::new(crate::ops::AnyGrapheme::new())),
+            #[cfg(not(feature = "unicode-segmentation"))]
+            Some("grapheme") => {
+                self.position = position;
+                self.error("@grapheme requires the unicode-segmentation feature")
+            },
+            Some("byteclass") => {
+                self.expect("(")?;
+                let mut ranges = vec![];
+                loop {
+                    let start = self.byte()?;
+                    let end = if self.eat("..=") { self.byte()? } else { start };
+                    ranges.push(start..=end);
+                    if !self.eat(",") {
+                        break;
+                    }
+                }
+                if ranges.iter().all(|range| range.is_empty()) {
+                    return self.error("Empty byte classes are not permitted");
+                }
+                self.expect(")")?;
+                Ok(Rc::new(ByteClass::new(&ranges)))
+            },
+            Some("int") => {
+                self.expect("(")?;
+                self.skip_spacing();
+                let width_position = self.position;
+                let width = self.number()?;
+                if !(1..=8).contains(&width) {
+                    self.position = width_position;
+                    return self.error("Integer width must be from 1 to 8 bytes");
+                }
+                self.expect(",")?;
+                let mut integer = Integer::new(width, self.endianness()?);
+                if self.eat(",") {
+                    let minimum = self.number()?;
+                    self.expect("..=")?;
+                    integer = integer.with_range(minimum, self.number()?);
+                }
+                self.expect(")")?;
+                Ok(Rc::new(integer))
+            },
+            Some("operators") => {
+                self.expect("(")?;
+                let mut table = OperatorTable::new(self.choice()?);
+                while self.eat(",") {
+                    self.skip_spacing();
+                    let fixity_position = self.position;
+                    let fixity = self.identifier_here();
+                    if !matches!(fixity, Some("prefix" | "infix" | "postfix")) {
+                        self.position = fixity_position;
+                        return self.error("Expected prefix, infix or postfix");
+                    }
+                    self.expect("(")?;
+                    let operator = self.choice()?;
+                    self.expect(",")?;
+                    let label = self.label()?;
+                    self.expect(",")?;
+                    let precedence = self.number()?;
+                    table = match fixity {
+                        Some("prefix") => table.with_prefix(operator, &label, precedence),
+                        Some("postfix") => table.with_postfix(operator, &label, precedence),
+                        _ => {
+                            self.expect(",")?;
+                            self.skip_spacing();
+                            let associativity_position = self.position;
+                            let associativity = match self.identifier_here() {
+                                Some("left") => Associativity::Left,
+                                Some("right") => Associativity::Right,
+                                _ => {
+                                    self.position = associativity_position;
+                                    return self.error("Expected left or right");
+                                },
+                            };
+                            table.with_infix(operator, &label, precedence, associativity)
+                        },
+                    };
+                    self.expect(")")?;
+                }
+                self.expect(")")?;
+                Ok(Rc::new(table))
+            },
+            Some("action") => {
+                self.position = position;
+                self.error("An Action can not be read, as its update is a closure")
+            },
+            Some("predicate") => {
+                self.position = position;
+                self.error("A Predicate can not be read, as its check is a closure")
+            },
+            Some("take") => {
+                self.expect("(")?;
+                let name = self.name()?;
+                self.expect(",")?;
+                let endianness = self.endianness()?;
+                self.expect(")")?;
+                Ok(Rc::new(Take::new(&name, endianness)))
+            },
+            _ => {
+                self.position = position;
+                self.error("Unknown builtin")
+            },
+        }
+    }
+}
+
+/// Reads two hex digits at the start of `text`
+fn hex_byte(text: &str) -> Option<u8> {
+    let hex = text.get(..2)?;
+    if hex.bytes().all(|each| each.is_ascii_hexdigit()) {
+        u8::from_str_radix(hex, 16).ok()
+    } else {
+        None
+    }
+}
@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use regex::{Regex, RegexBuilder};
+
+use super::{ByteSet, CaptureKind, CompileError, Instruction, Program, MAX_REPETITION_INSTRUCTIONS};
+use crate::core::{OpKind, Parser, Symbol};
+use crate::ops::Grammar;
+
+/// A grammar on the stack used to resolve rule references, like the stack of rule sets in `ParserContext`.
+/// Nested grammars are borrowed from the op tree, while imported grammars are shared.
+#[derive(Clone)]
+enum RuleSet<'a> {
+    Borrowed(&'a Grammar),
+    Shared(Rc<Grammar>),
+}
+
+impl RuleSet<'_> {
+    fn grammar(&self) -> &Grammar {
+        match self {
+            RuleSet::Borrowed(grammar) => grammar,
+            RuleSet::Shared(grammar) => grammar,
+        }
+    }
+}
+
+/// Rules are compiled once for each stack of rule sets they are parsed with
+type RuleKey = (Vec<usize>, usize);
+
+/// Compiles each rule into a chunk of instructions, which are linked into one `Program` at the end.
+/// Within a chunk, jump targets are offsets from the start of the chunk and `Call` holds the index of a chunk.
+#[derive(Default)]
+pub(crate) struct Compiler {
+    chunks: Vec<Vec<Instruction>>,
+    chunk_indices: HashMap<RuleKey, usize>,
+    labels: Vec<Symbol>,
+    regexes: Vec<Regex>,
+    regex_keys: Vec<(String, bool, bool, bool)>,
+    /// The rule being compiled, for error messages
+    rule_name: String,
+}
+
+impl Compiler {
+    pub(crate) fn compile(mut self, grammar: &Grammar) -> Result<Program, CompileError> {
+        self.chunks.push(vec![]);
+        let mut code = vec![];
+        self.compile_op(&[], grammar, &mut code)?;
+        code.push(Instruction::End);
+        self.chunks[0] = code;
+        Ok(self.link())
+    }
+
+    fn link(self) -> Program {
+        let mut chunk_addresses = vec![];
+        let mut address = 0;
+        for chunk in &self.chunks {
+            chunk_addresses.push(address);
+            address += chunk.len();
+        }
+        let instructions = self
+            .chunks
+            .into_iter()
+            .zip(chunk_addresses.iter())
+            .flat_map(|(chunk, chunk_address)| {
+                let chunk_addresses = &chunk_addresses;
+                chunk.into_iter().map(move |instruction| match instruction {
+                    Instruction::Choice(offset) => Instruction::Choice(chunk_address + offset),
+                    Instruction::Commit(offset) => Instruction::Commit(chunk_address + offset),
+                    Instruction::BackCommit(offset) => Instruction::BackCommit(chunk_address + offset),
+                    Instruction::ProgressCommit(offset) => Instruction::ProgressCommit(chunk_address + offset),
+                    Instruction::Jump(offset) => Instruction::Jump(chunk_address + offset),
+                    Instruction::Call(chunk_index) => Instruction::Call(chunk_addresses[chunk_index]),
+                    instruction => instruction,
+                })
+            })
+            .collect();
+        Program {
+            instructions,
+            regexes: self.regexes,
+            labels: self.labels,
+        }
+    }
+
+    fn label_index(&mut self, label: &str) -> usize {
+        match self.labels.iter().position(|each| each.as_str() == label) {
+            Some(index) => index,
+            None => {
+                self.labels.push(Symbol::intern(label));
+                self.labels.len() - 1
+            },
+        }
+    }
+
+    fn regex_index(&mut self, pattern: &str, multi_line: bool, case_insensitive: bool, dot_matches_new_line: bool) -> Result<usize, CompileError> {
+        let key = (pattern.to_owned(), multi_line, case_insensitive, dot_matches_new_line);
+        if let Some(index) = self.regex_keys.iter().position(|each| *each == key) {
+            return Ok(index);
+        }
+        // Anchored like `Regex::new` does
+        let regex = RegexBuilder::new(&format!("^{}", pattern))
+            .multi_line(multi_line)
+            .case_insensitive(case_insensitive)
+            .dot_matches_new_line(dot_matches_new_line)
+            .build()
+            .map_err(|error| CompileError::InvalidRegex {
+                rule_name: self.rule_name.clone(),
+                pattern: pattern.to_owned(),
+                message: error.to_string(),
+            })?;
+        self.regexes.push(regex);
+        self.regex_keys.push(key);
+        Ok(self.regexes.len() - 1)
+    }
+
+    /// Looks up a rule the way `ParserContext::resolve_rule` does, and compiles it if needed.
+    /// Returns the index of the rule's chunk.
+    fn compile_rule_reference(&mut self, stack: &[RuleSet<'_>], rule_name: &str) -> Result<usize, CompileError> {
+        let (resolved, rule_sets) = stack
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(index, rule_set)| {
+                rule_set.grammar().resolve_rule(rule_name).map(|mut resolved| {
+                    let rule_sets = match resolved.rule_sets.take() {
+                        Some(rule_sets) => rule_sets.into_iter().map(RuleSet::Shared).collect(),
+                        None => stack[..=index].to_vec(),
+                    };
+                    (resolved, rule_sets)
+                })
+            })
+            .ok_or_else(|| CompileError::RuleNotFound {
+                rule_name: self.rule_name.clone(),
+                reference: rule_name.to_owned(),
+            })?;
+        if !resolved.parameters.is_empty() {
+            return Err(CompileError::ParameterizedRule { rule_name: rule_name.to_owned() });
+        }
+        self.compile_rule(&rule_sets, rule_name, resolved.parser_operator.as_ref())
+    }
+
+    fn compile_rule(&mut self, stack: &[RuleSet<'_>], rule_name: &str, parser_operator: &dyn Parser) -> Result<usize, CompileError> {
+        let key = (stack.iter().map(|rule_set| rule_set.grammar().get_id()).collect(), parser_operator.get_id());
+        if let Some(chunk_index) = self.chunk_indices.get(&key) {
+            return Ok(*chunk_index);
+        }
+        // Reserve the chunk first; rules may refer to themselves
+        let chunk_index = self.chunks.len();
+        self.chunks.push(vec![]);
+        self.chunk_indices.insert(key, chunk_index);
+        let previous_rule_name = std::mem::replace(&mut self.rule_name, rule_name.to_owned());
+        let mut code = vec![];
+        self.compile_op(stack, parser_operator, &mut code)?;
+        code.push(Instruction::Return);
+        self.chunks[chunk_index] = code;
+        self.rule_name = previous_rule_name;
+        Ok(chunk_index)
+    }
+
+    /// Appends the code for `op`, which leaves exactly one closed match behind when it succeeds,
+    /// just as `parse_internal` returns one `ParserMatch`
+    fn compile_op(&mut self, stack: &[RuleSet<'_>], op: &dyn Parser, code: &mut Vec<Instruction>) -> Result<(), CompileError> {
+        match op.kind() {
+            OpKind::Literal(literal_text) => {
+                code.push(Instruction::Capture(CaptureKind::Open));
+                code.extend(literal_text.bytes().map(Instruction::Char));
+                code.push(Instruction::Capture(CaptureKind::Close));
+            },
+            OpKind::Regex { pattern, multi_line, case_insensitive, dot_matches_new_line } => {
+                let regex_index = self.regex_index(pattern, multi_line, case_insensitive, dot_matches_new_line)?;
+                code.push(Instruction::Capture(CaptureKind::Open));
+                code.push(Instruction::Regex(regex_index));
+                code.push(Instruction::Capture(CaptureKind::Close));
+            },
+            OpKind::Sequence(children) => {
+                code.push(Instruction::Capture(CaptureKind::Open));
+                for child in children {
+                    self.compile_op(stack, child.as_ref(), code)?;
+                }
+                code.push(Instruction::Capture(CaptureKind::Close));
+            },
+            OpKind::Alternation(children) => {
+                code.push(Instruction::Capture(CaptureKind::Open));
+                if let Some(byte_set) = single_byte_literals(children) {
+                    // The match of the chosen literal is still needed as the child
+                    code.push(Instruction::Capture(CaptureKind::Open));
+                    code.push(Instruction::Set(Box::new(byte_set)));
+                    code.push(Instruction::Capture(CaptureKind::Close));
+                } else {
+                    let mut commits = vec![];
+                    for (index, child) in children.iter().enumerate() {
+                        if index + 1 == children.len() {
+                            self.compile_op(stack, child.as_ref(), code)?;
+                        } else {
+                            let choice = code.len();
+                            code.push(Instruction::Choice(0));
+                            self.compile_op(stack, child.as_ref(), code)?;
+                            commits.push(code.len());
+                            code.push(Instruction::Commit(0));
+                            code[choice] = Instruction::Choice(code.len());
+                        }
+                    }
+                    for commit in commits {
+                        code[commit] = Instruction::Commit(code.len());
+                    }
+                }
+                code.push(Instruction::Capture(CaptureKind::Close));
+            },
+            OpKind::Quantity { child, minimum_occurrences, maximum_occurrences } => {
+                // The child is copied for each occurrence, so the size of the copies is checked first
+                let mut child_code = vec![];
+                self.compile_op(stack, child.as_ref(), &mut child_code)?;
+                let copies = maximum_occurrences.unwrap_or(minimum_occurrences + 1);
+                if copies.saturating_mul(child_code.len() + 3) > MAX_REPETITION_INSTRUCTIONS {
+                    return Err(CompileError::RepetitionTooLarge {
+                        rule_name: self.rule_name.clone(),
+                        op: op.to_peg_string(),
+                    });
+                }
+                code.push(Instruction::Capture(CaptureKind::Open));
+                // Like `Quantity`, stops once the child matches nothing and there are enough occurrences
+                let mut exits = vec![];
+                for occurrence in 1..=minimum_occurrences {
+                    if occurrence < minimum_occurrences {
+                        self.compile_op(stack, child.as_ref(), code)?;
+                    } else {
+                        // The last required occurrence; Choice L1; child; ProgressCommit L2; Jump L3; L1: Fail; L2:
+                        let choice = code.len();
+                        code.push(Instruction::Choice(0));
+                        self.compile_op(stack, child.as_ref(), code)?;
+                        code.push(Instruction::ProgressCommit(code.len() + 3));
+                        exits.push(code.len());
+                        code.push(Instruction::Jump(0));
+                        code[choice] = Instruction::Choice(code.len());
+                        code.push(Instruction::Fail);
+                    }
+                }
+                match maximum_occurrences {
+                    None => {
+                        // L1: Choice L2; child; ProgressCommit L1; L2:
+                        let choice = code.len();
+                        code.push(Instruction::Choice(0));
+                        self.compile_op(stack, child.as_ref(), code)?;
+                        code.push(Instruction::ProgressCommit(choice));
+                        code[choice] = Instruction::Choice(code.len());
+                    },
+                    Some(maximum_occurrences) => {
+                        // Each optional occurrence gives up on the rest when it fails or matches nothing;
+                        // Choice L2; child; ProgressCommit L1; Jump L2; L1: the next occurrence ... L2:
+                        for _ in minimum_occurrences..maximum_occurrences {
+                            exits.push(code.len());
+                            code.push(Instruction::Choice(0));
+                            self.compile_op(stack, child.as_ref(), code)?;
+                            code.push(Instruction::ProgressCommit(code.len() + 2));
+                            exits.push(code.len());
+                            code.push(Instruction::Jump(0));
+                        }
+                    },
+                }
+                let exit = code.len();
+                for each in exits {
+                    code[each] = match code[each] {
+                        Instruction::Choice(_) => Instruction::Choice(exit),
+                        _ => Instruction::Jump(exit),
+                    };
+                }
+                code.push(Instruction::Capture(CaptureKind::Close));
+            },
+            OpKind::Lookahead { child, scout, accept_match } => {
+                self.compile_op(stack, child.as_ref(), code)?;
+                let choice = code.len();
+                code.push(Instruction::Choice(0));
+                self.compile_op(stack, scout.as_ref(), code)?;
+                if accept_match {
+                    // Choice L1; scout; BackCommit L2; L1: Fail; L2:
+                    code.push(Instruction::BackCommit(code.len() + 2));
+                    code[choice] = Instruction::Choice(code.len());
+                    code.push(Instruction::Fail);
+                } else {
+                    // Choice L1; scout; Commit L2; L2: Fail; L1:
+                    code.push(Instruction::Commit(code.len() + 1));
+                    code.push(Instruction::Fail);
+                    code[choice] = Instruction::Choice(code.len());
+                }
+            },
+            OpKind::Label { child, label } => {
+                self.compile_op(stack, child.as_ref(), code)?;
+                let label_index = self.label_index(label);
+                code.push(Instruction::Capture(CaptureKind::Relabel(label_index)));
+            },
+            OpKind::RuleReference(rule_name) => {
+                let chunk_index = self.compile_rule_reference(stack, rule_name)?;
+                code.push(Instruction::Call(chunk_index));
+                let label_index = self.label_index(rule_name);
+                code.push(Instruction::Capture(CaptureKind::Relabel(label_index)));
+            },
+            OpKind::Grammar(grammar) => {
+                let mut rule_sets = stack.to_vec();
+                rule_sets.push(RuleSet::Borrowed(grammar));
+                let (rule_name, parser_operator) = grammar.get_starting_rule().ok_or(CompileError::EmptyGrammar)?;
+                if !grammar.get_rule_parameters(&rule_name).is_empty() {
+                    return Err(CompileError::ParameterizedRule { rule_name: rule_name.to_string() });
+                }
+                let chunk_index = self.compile_rule(&rule_sets, &rule_name, parser_operator.as_ref())?;
+                code.push(Instruction::Call(chunk_index));
+                let label_index = self.label_index(&rule_name);
+                code.push(Instruction::Capture(CaptureKind::Relabel(label_index)));
+            },
+            OpKind::Action { .. }
+            | OpKind::AnyGrapheme
+            | OpKind::BackReference(_)
+            | OpKind::ByteClass(_)
+            | OpKind::ByteLiteral(_)
+            | OpKind::Capture { .. }
+            | OpKind::Counted { .. }
+            | OpKind::Dedent
+            | OpKind::Indent
+            | OpKind::Integer { .. }
+            | OpKind::OperatorTable(_)
+            | OpKind::Predicate { .. }
+            | OpKind::RuleCall { .. }
+            | OpKind::Samedent
+            | OpKind::SepBy { .. }
+            | OpKind::Take { .. }
+            | OpKind::Until { .. }
+            | OpKind::Other => {
+                return Err(CompileError::Unsupported {
+                    rule_name: self.rule_name.clone(),
+                    op: op.to_peg_string(),
+                })
+            },
+        }
+        Ok(())
+    }
+}
+
+/// If every alternative is a literal of one byte, the alternation can be matched with a single `Set`
+fn single_byte_literals(children: &[Rc<dyn Parser>]) -> Option<ByteSet> {
+    let mut byte_set = ByteSet::default();
+    for child in children {
+        match child.kind() {
+            OpKind::Literal(literal_text) if literal_text.len() == 1 => byte_set.insert(literal_text.as_bytes()[0]),
+            _ => return None,
+        }
+    }
+    Some(byte_set)
+}
@@ -0,0 +1,63 @@
+/// A set of bytes, as a 256 bit mask
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ByteSet([u64; 4]);
+
+impl ByteSet {
+    pub fn insert(&mut self, byte: u8) {
+        self.0[(byte >> 6) as usize] |= 1 << (byte & 63);
+    }
+    pub fn contains(&self, byte: u8) -> bool {
+        self.0[(byte >> 6) as usize] & (1 << (byte & 63)) != 0
+    }
+    pub fn union(&mut self, other: &ByteSet) {
+        for (word, other_word) in self.0.iter_mut().zip(other.0.iter()) {
+            *word |= other_word;
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|word| *word == 0)
+    }
+}
+
+/// Events recorded by `Instruction::Capture`, from which the `ParserMatch` tree is built once the parse succeeds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureKind {
+    /// Starts a match at the current position
+    Open,
+    /// Ends the innermost open match at the current position
+    Close,
+    /// Labels the most recently closed match with the label at this index of the `Program`
+    Relabel(usize),
+}
+
+/// `Instruction`
+///
+/// One step of a `Program`. Instructions which jump hold the address of their target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Instruction {
+    /// Matches one byte
+    Char(u8),
+    /// Matches one byte which is in the set
+    Set(Box<ByteSet>),
+    /// Matches the regular expression at this index of the `Program` at the current position
+    Regex(usize),
+    /// Pushes a backtrack entry which resumes at the target, at the current position
+    Choice(usize),
+    /// Pops the backtrack entry pushed by the matching `Choice`, and jumps to the target
+    Commit(usize),
+    /// Like `Commit`, but returns to the position of the backtrack entry and discards captures made since;
+    /// this is how a positive lookahead consumes nothing
+    BackCommit(usize),
+    /// Like `Commit`, but only jumps to the target if the position has moved since the matching `Choice`,
+    /// and otherwise goes on to the next instruction; this is how a repetition stops once its child matches nothing
+    ProgressCommit(usize),
+    Jump(usize),
+    /// Pushes the return address and jumps to the start of a rule
+    Call(usize),
+    Return,
+    /// Backtracks to the most recent `Choice`, or fails the parse if there is none
+    Fail,
+    Capture(CaptureKind),
+    /// The parse succeeded
+    End,
+}
@@ -0,0 +1,144 @@
+use super::{CaptureKind, Instruction, Program};
+use crate::core::{MatchArena, MatchId, ParseTree, Symbol};
+
+/// An entry of the machine's stack; backtrack entries and return addresses share one stack, as in LPeg
+enum StackEntry {
+    Backtrack {
+        address: usize,
+        position: usize,
+        capture_count: usize,
+    },
+    Return(usize),
+}
+
+impl Program {
+    /// Runs the program on `full_text`, starting at `start_position`.
+    ///
+    /// The machine keeps its own stack instead of recursing, so deeply nested input cannot overflow the Rust stack.
+    pub fn parse_at(&self, full_text: &str, start_position: usize) -> Option<ParseTree> {
+        // Text is never split within a character, as with `Parser::parse`
+        if !full_text.is_char_boundary(start_position) {
+            return None;
+        }
+        let bytes = full_text.as_bytes();
+        let mut address = 0;
+        let mut position = start_position;
+        let mut stack: Vec<StackEntry> = vec![];
+        let mut captures: Vec<(CaptureKind, usize)> = vec![];
+        loop {
+            let matched = match &self.instructions[address] {
+                Instruction::Char(byte) => {
+                    let matched = bytes.get(position) == Some(byte);
+                    position += matched as usize;
+                    matched
+                },
+                Instruction::Set(byte_set) => {
+                    let matched = bytes.get(position).is_some_and(|byte| byte_set.contains(*byte));
+                    position += matched as usize;
+                    matched
+                },
+                // The anchor only applies to the first alternative of the pattern, so a match further on is no match
+                Instruction::Regex(index) => match self.regexes[*index].find(&full_text[position..]).filter(|re_match| re_match.start() == 0) {
+                    Some(re_match) => {
+                        position += re_match.end();
+                        true
+                    },
+                    None => false,
+                },
+                Instruction::Choice(target) => {
+                    stack.push(StackEntry::Backtrack {
+                        address: *target,
+                        position,
+                        capture_count: captures.len(),
+                    });
+                    true
+                },
+                Instruction::Commit(target) => {
+                    stack.pop();
+                    address = *target;
+                    continue;
+                },
+                Instruction::ProgressCommit(target) => {
+                    if let Some(StackEntry::Backtrack { position: entry_position, .. }) = stack.pop() {
+                        address = if position > entry_position { *target } else { address + 1 };
+                    }
+                    continue;
+                },
+                Instruction::Jump(target) => {
+                    address = *target;
+                    continue;
+                },
+                Instruction::BackCommit(target) => {
+                    if let Some(StackEntry::Backtrack { position: entry_position, capture_count, .. }) = stack.pop() {
+                        position = entry_position;
+                        captures.truncate(capture_count);
+                    }
+                    address = *target;
+                    continue;
+                },
+                Instruction::Call(target) => {
+                    stack.push(StackEntry::Return(address + 1));
+                    address = *target;
+                    continue;
+                },
+                Instruction::Return => {
+                    if let Some(StackEntry::Return(return_address)) = stack.pop() {
+                        address = return_address;
+                    }
+                    continue;
+                },
+                Instruction::Fail => false,
+                Instruction::Capture(kind) => {
+                    captures.push((*kind, position));
+                    true
+                },
+                Instruction::End => return Some(build_tree(&captures, &self.labels)),
+            };
+            if matched {
+                address += 1;
+                continue;
+            }
+            // Backtrack to the most recent choice, discarding the return addresses above it
+            loop {
+                match stack.pop() {
+                    Some(StackEntry::Backtrack { address: entry_address, position: entry_position, capture_count }) => {
+                        address = entry_address;
+                        position = entry_position;
+                        captures.truncate(capture_count);
+                        break;
+                    },
+                    Some(StackEntry::Return(_)) => (),
+                    None => return None,
+                }
+            }
+        }
+    }
+
+    /// Runs the program on `full_text` from the start, like parsing the compiled `Grammar` at position zero
+    pub fn parse(&self, full_text: &str) -> Option<ParseTree> {
+        self.parse_at(full_text, 0)
+    }
+}
+
+/// Builds the match tree from the capture events left by a successful parse
+fn build_tree(captures: &[(CaptureKind, usize)], labels: &[Symbol]) -> ParseTree {
+    let mut arena = MatchArena::new();
+    // The start position of each open match, with the children closed within it so far
+    let mut open: Vec<(usize, Vec<MatchId>)> = vec![(0, vec![])];
+    for (kind, position) in captures {
+        match kind {
+            CaptureKind::Open => open.push((*position, vec![])),
+            CaptureKind::Close => {
+                let (start_position, children) = open.pop().expect("Capture events are balanced");
+                let parser_match = arena.push(start_position, *position, None, &children);
+                open.last_mut().expect("Capture events are balanced").1.push(parser_match);
+            },
+            CaptureKind::Relabel(index) => {
+                let last = open.last_mut().and_then(|(_, children)| children.last_mut()).expect("Relabel follows a match");
+                *last = arena.relabel(*last, labels[*index]);
+            },
+        }
+    }
+    let root = open.pop().and_then(|(_, mut children)| children.pop()).expect("A successful parse has one match");
+    ParseTree::new(arena, root)
+}
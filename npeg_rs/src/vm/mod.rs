@@ -0,0 +1,202 @@
+//! A parsing machine in the style of LPeg; see `Program::compile()`
+//!
+//! The op tree of a `Grammar` is compiled into a flat list of `Instruction`s, which are run by a loop with an explicit
+//! backtrack stack instead of recursing through `Parser::parse`. The `ParserMatch` tree is rebuilt from capture events
+//! at the end, and is the same as the tree produced by the interpreter.
+//!
+//! Unlike the interpreter, the machine does not memoize, so grammars which backtrack heavily may be slower.
+
+use std::error::Error;
+use std::fmt;
+
+use regex::Regex;
+
+use crate::core::Symbol;
+use crate::ops::Grammar;
+
+mod compiler;
+mod instruction;
+mod machine;
+
+pub use self::instruction::{ByteSet, CaptureKind, Instruction};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompileError {
+    /// The op has no instructions; `op` is the op written as PEG text
+    Unsupported { rule_name: String, op: String },
+    /// A `RuleReference` names a rule which does not exist where it is used
+    RuleNotFound { rule_name: String, reference: String },
+    /// The starting rule, or a rule used by a `RuleReference`, takes parameters
+    ParameterizedRule { rule_name: String },
+    InvalidRegex { rule_name: String, pattern: String, message: String },
+    /// A grammar has no rules
+    EmptyGrammar,
+    /// A repetition is compiled to a copy of its child for each occurrence up to its maximum, or one more than its minimum,
+    /// and those copies would take more than `MAX_REPETITION_INSTRUCTIONS`
+    RepetitionTooLarge { rule_name: String, op: String },
+}
+
+/// The most instructions the copies of the child of one repetition may take
+pub const MAX_REPETITION_INSTRUCTIONS: usize = 1 << 16;
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::Unsupported { rule_name, op } => write!(f, "Rule {} uses {}, which can not be compiled", rule_name, op),
+            CompileError::RuleNotFound { rule_name, reference } => write!(f, "Rule {} references {}, which was not found", rule_name, reference),
+            CompileError::ParameterizedRule { rule_name } => write!(f, "Rule {} takes parameters, which can not be compiled", rule_name),
+            CompileError::InvalidRegex { rule_name, pattern, message } => write!(f, "Rule {} has an invalid regular expression {}: {}", rule_name, pattern, message),
+            CompileError::EmptyGrammar => write!(f, "Grammar has no rules"),
+            CompileError::RepetitionTooLarge { rule_name, op } => write!(f, "Rule {} uses {}, which repeats too many times to be compiled", rule_name, op),
+        }
+    }
+}
+
+impl Error for CompileError {}
+
+/// `Program`
+///
+/// A `Grammar` compiled for the parsing machine. `Program::parse` gives the same result as parsing the grammar
+/// with a new `ParserContext`.
+///
+/// Ops which depend on the parser state or on parameters (`Capture`, `BackReference`, `Indent`, `Samedent`, `Dedent`,
+/// parameterized rules and `RuleCall`) can not be compiled yet.
+///
+/// ```
+/// # use npeg_rs::*;
+/// let gram = Grammar::new(None, vec![
+///     ("Nested", alt!(seq!(lit!("("), rul!("Nested"), lit!(")")), lit!("x"))),
+/// ]);
+/// let program = Program::compile(&gram).unwrap();
+/// assert_eq!(program.parse("((x))").unwrap().get_root().len(), 5);
+/// ```
+#[derive(Debug)]
+pub struct Program {
+    instructions: Vec<Instruction>,
+    regexes: Vec<Regex>,
+    labels: Vec<Symbol>,
+}
+
+impl Program {
+    pub fn compile(grammar: &Grammar) -> Result<Program, CompileError> {
+        compiler::Compiler::default().compile(grammar)
+    }
+    pub fn get_instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::*;
+
+    /// The machine must produce exactly the same tree as the interpreter
+    fn assert_same_as_interpreter(gram: Grammar, texts: &[&str]) {
+        let program = Program::compile(&gram).unwrap();
+        let gram = Rc::new(gram);
+        for text in texts {
+            let mut context = Box::new(ParserContext::new(text));
+            let interpreted = gram.clone().parse(&mut context, 0);
+            assert_eq!(format!("{:?}", program.parse(text)), format!("{:?}", interpreted), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn matches_interpreter() {
+        let gram = Grammar::from_peg_string(r#"
+            Expression <- Sum !r"."s
+            Sum        <- Product (operator:("+" / "-") Product)*
+            Product    <- Atom (operator:r"[*/]" Atom)*
+            Atom       <- Number / "(" Sum ")" / Call
+            Call       <- function:r"[a-z]+"i &"(" "(" Sum{1,3} ")"
+            Number     <- r"[0-9]"{1,3} ("." r"[0-9]"+)?
+        "#).unwrap();
+        assert_same_as_interpreter(gram, &["1", "1+2*3", "(1+2)*3", "max(1(2)3)-4", "1234", "12.", "2*(3", "", "f(1(2)3(4))"]);
+    }
+
+    #[test]
+    fn matches_interpreter_with_imports_and_nested_grammars() {
+        let json = Rc::new(Grammar::new(None, vec![
+            ("Value",  alt!(rul!("Number"), lit!("null"))),
+            ("Number", reg!("[0-9]+")),
+        ]));
+        let gram = Grammar::new(None, vec![
+            ("Pair",   seq!(rul!("json::Value"), lit!(","), rul!("Inner"))),
+            ("Inner",  Rc::new(Grammar::new(None, vec![("A", seq!(rul!("Number"), qtt!(lit!("!"), 0, Some(2))))]))),
+            ("Number", lit!("x")),
+        ]).with_import("json", json);
+        assert_same_as_interpreter(gram, &["1,x!!!", "null,x", "x,1", "12,x!"]);
+    }
+
+    #[test]
+    fn lookahead_discards_scout_captures() {
+        let gram = Grammar::new(None, vec![
+            ("A", seq!(Rc::new(Lookahead::new(lit!("a"), lbl!(lit!("b"), "scout"), true)), lit!("b"))),
+        ]);
+        assert_same_as_interpreter(gram, &["ab", "ac"]);
+    }
+
+    #[test]
+    fn regex_matches_further_on_are_no_match() {
+        let gram = Grammar::new(None, vec![("A", reg!("[0-9]+|x"))]);
+        assert_same_as_interpreter(gram, &["ax", "12", "x"]);
+    }
+
+    #[test]
+    fn alternation_of_bytes_compiles_to_set() {
+        let gram = Grammar::new(None, vec![("A", qtt!(alt!(lit!("a"), lit!("b"), lit!("c")), 1, None))]);
+        let program = Program::compile(&gram).unwrap();
+        assert!(program.get_instructions().iter().any(|instruction| matches!(instruction, Instruction::Set(_))));
+        assert_same_as_interpreter(gram, &["abcabd", "d"]);
+    }
+
+    #[test]
+    fn deep_nesting_does_not_recurse() {
+        let gram = Grammar::new(None, vec![
+            ("Nested", alt!(seq!(lit!("("), rul!("Nested"), lit!(")")), lit!("x"))),
+        ]);
+        let program = Program::compile(&gram).unwrap();
+        let text = "(".repeat(100_000) + "x" + &")".repeat(100_000);
+        let result = program.parse(&text).unwrap();
+        assert_eq!(result.get_root().len(), text.len());
+    }
+
+    #[test]
+    fn repetition_stops_once_child_matches_nothing() {
+        let gram = Grammar::new(None, vec![
+            ("A", seq!(qtt!(qtt!(lit!("a"), 0, None), 0, None), qtt!(qtt!(lit!("b"), 0, Some(2)), 2, Some(5)), lit!(";"))),
+        ]);
+        assert_same_as_interpreter(gram, &[";", "aaa;", "b;", "bbbbb;", "bbbbbbbbbbbbb;", "c"]);
+    }
+
+    #[test]
+    fn large_repetitions_are_reported() {
+        let gram = Grammar::new(None, vec![("A", qtt!(seq!(lit!("ab"), lit!("c")), 0, Some(1_000_000)))]);
+        assert_eq!(
+            Program::compile(&gram).unwrap_err(),
+            CompileError::RepetitionTooLarge { rule_name: "A".into(), op: "(\"ab\" \"c\"){0,1000000}".into() }
+        );
+        let gram = Grammar::new(None, vec![("A", qtt!(qtt!(lit!("a"), 0, Some(300)), 0, Some(300)))]);
+        assert!(matches!(Program::compile(&gram), Err(CompileError::RepetitionTooLarge { .. })));
+        assert!(Program::compile(&Grammar::new(None, vec![("A", qtt!(lit!("a"), 1_000, None))])).is_ok());
+    }
+
+    #[test]
+    fn positions_within_a_character_do_not_match() {
+        let program = Program::compile(&Grammar::new(None, vec![("A", reg!(".*"))])).unwrap();
+        assert!(program.parse_at("é", 1).is_none());
+        assert!(program.parse_at("é", 3).is_none());
+        assert_eq!(program.parse_at("é", 2).unwrap().get_root().len(), 0);
+    }
+
+    #[test]
+    fn unsupported_ops_are_reported() {
+        let gram = Grammar::new(None, vec![("A", seq!(idt!(), lit!("a")))]);
+        assert_eq!(
+            Program::compile(&gram).unwrap_err(),
+            CompileError::Unsupported { rule_name: "A".into(), op: "@indent".into() }
+        );
+    }
+}
@@ -0,0 +1,554 @@
+//! Compiles a `npeg_rs::Grammar` into Rust source code.
+//!
+//! The generated code has one function per op, called directly rather than through `Rc<dyn Parser>`,
+//! and produces the same `ParserMatch` tree as parsing the `Grammar` with the interpreter.
+//! Rules are memoized by position, as they are by the interpreter.
+//! Like a `ParserContext`, the generated parser limits how deeply ops are nested, by `DEFAULT_MAX_DEPTH` unless
+//! other `Limits` are given, so deeply nested input fails with `ParseError::TooDeep` rather than overflowing the stack.
+//!
+//! It is intended to be used from a `build.rs`;
+//!
+//! ```no_run
+//! // build.rs
+//! let grammar = npeg_rs::Grammar::from_peg_string(&std::fs::read_to_string("src/grammar.peg").unwrap()).unwrap();
+//! let source = npeg_rs_codegen::generate(&grammar).unwrap();
+//! std::fs::write(std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("grammar.rs"), source).unwrap();
+//! ```
+//!
+//! The generated code depends on the `npeg_rs` and `regex` crates, and brings names into scope with `use`,
+//! so it should be included in a module of its own;
+//!
+//! ```ignore
+//! mod grammar {
+//!     include!(concat!(env!("OUT_DIR"), "/grammar.rs"));
+//! }
+//! let result = grammar::parse("1+2");
+//! ```
+//!
+//! Ops which depend on the parser state or on parameters (`Capture`, `BackReference`, `Indent`, `Samedent`, `Dedent`,
+//! parameterized rules and `RuleCall`) are not supported yet; `generate` returns `CodegenError::Unsupported` for them.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{self, Write};
+use std::rc::Rc;
+
+use npeg_rs::{Grammar, OpKind, Parser, ResolvedRule};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CodegenError {
+    /// The op has no generated equivalent; `op` is the op written as PEG text
+    Unsupported { rule_name: String, op: String },
+    /// A `RuleReference` names a rule which does not exist where it is used
+    RuleNotFound { rule_name: String, reference: String },
+    /// The starting rule, or a rule used by a `RuleReference`, takes parameters
+    ParameterizedRule { rule_name: String },
+    InvalidRegex { rule_name: String, pattern: String, message: String },
+    /// A grammar has no rules
+    EmptyGrammar,
+}
+
+impl fmt::Display for CodegenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodegenError::Unsupported { rule_name, op } => write!(f, "Rule {} uses {}, which is not supported by code generation", rule_name, op),
+            CodegenError::RuleNotFound { rule_name, reference } => write!(f, "Rule {} references {}, which was not found", rule_name, reference),
+            CodegenError::ParameterizedRule { rule_name } => write!(f, "Rule {} takes parameters, which is not supported by code generation", rule_name),
+            CodegenError::InvalidRegex { rule_name, pattern, message } => write!(f, "Rule {} has an invalid regular expression {}: {}", rule_name, pattern, message),
+            CodegenError::EmptyGrammar => write!(f, "Grammar has no rules"),
+        }
+    }
+}
+
+impl Error for CodegenError {}
+
+/// Generates Rust source for `grammar`, exposing `pub fn parse(text: &str) -> Option<ParseTree>`
+/// which behaves like `Rc::new(grammar).parse(&mut Box::new(ParserContext::new(text)), 0)`,
+/// and `try_parse(text)` and `try_parse_with_limits(text, &Limits { .. })` which return the `ParseError`
+/// if the parse was abandoned, like `Parser::try_parse`.
+pub fn generate(grammar: &Grammar) -> Result<String, CodegenError> {
+    let mut generator = Generator::default();
+    let start = generator.function_for(&[], grammar)?;
+    Ok(generator.finish(start, grammar))
+}
+
+/// A grammar on the stack used to resolve rule references, like `ParserContext`'s stack of rule sets.
+/// Nested grammars are borrowed from the op tree, while those of imports are shared.
+#[derive(Clone)]
+enum RuleSet<'a> {
+    Borrowed(&'a Grammar),
+    Shared(Rc<Grammar>),
+}
+
+impl RuleSet<'_> {
+    fn grammar(&self) -> &Grammar {
+        match self {
+            RuleSet::Borrowed(grammar) => grammar,
+            RuleSet::Shared(grammar) => grammar,
+        }
+    }
+}
+
+/// Functions are generated once for each op and stack of rule sets it is parsed with
+type FunctionKey = (Vec<usize>, usize);
+
+#[derive(Default)]
+struct Generator {
+    functions: Vec<String>,
+    function_indices: HashMap<FunctionKey, usize>,
+    /// Functions which are the body of a rule get a memoizing wrapper
+    memoized: Vec<bool>,
+    labels: Vec<String>,
+    regexes: Vec<(String, bool, bool, bool)>,
+    /// The rule being generated, for error messages
+    rule_name: String,
+}
+
+impl Generator {
+    fn label_index(&mut self, label: &str) -> usize {
+        match self.labels.iter().position(|each| each == label) {
+            Some(index) => index,
+            None => {
+                self.labels.push(label.to_owned());
+                self.labels.len() - 1
+            },
+        }
+    }
+
+    fn regex_index(&mut self, regex: (String, bool, bool, bool)) -> usize {
+        match self.regexes.iter().position(|each| *each == regex) {
+            Some(index) => index,
+            None => {
+                self.regexes.push(regex);
+                self.regexes.len() - 1
+            },
+        }
+    }
+
+    /// Looks up a rule the way `ParserContext::resolve_rule` does, returning it with the stack of rule sets to parse it with
+    fn resolve_rule<'a>(&self, stack: &[RuleSet<'a>], rule_name: &str) -> Result<(ResolvedRule, Vec<RuleSet<'a>>), CodegenError> {
+        stack
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(index, rule_set)| {
+                rule_set.grammar().resolve_rule(rule_name).map(|mut resolved| {
+                    let rule_sets = match resolved.rule_sets.take() {
+                        Some(rule_sets) => rule_sets.into_iter().map(RuleSet::Shared).collect(),
+                        None => stack[..=index].to_vec(),
+                    };
+                    (resolved, rule_sets)
+                })
+            })
+            .ok_or_else(|| CodegenError::RuleNotFound {
+                rule_name: self.rule_name.clone(),
+                reference: rule_name.to_owned(),
+            })
+    }
+
+    /// Generates the body of a rule and returns the index of its memoizing wrapper `rule_{index}`
+    fn rule_function_for(&mut self, stack: &[RuleSet<'_>], rule_name: &str, parser_operator: &dyn Parser) -> Result<usize, CodegenError> {
+        let previous_rule_name = std::mem::replace(&mut self.rule_name, rule_name.to_owned());
+        let index = self.function_for(stack, parser_operator)?;
+        self.rule_name = previous_rule_name;
+        self.memoized[index] = true;
+        Ok(index)
+    }
+
+    /// Generates `op_{index}` for `op` parsed with `stack`, unless it was already generated, and returns its index
+    fn function_for(&mut self, stack: &[RuleSet<'_>], op: &dyn Parser) -> Result<usize, CodegenError> {
+        let key = (stack.iter().map(|rule_set| rule_set.grammar().get_id()).collect(), op.get_id());
+        if let Some(index) = self.function_indices.get(&key) {
+            return Ok(*index);
+        }
+        // Reserve the index first; rules may refer to themselves
+        let index = self.functions.len();
+        self.functions.push(String::new());
+        self.memoized.push(false);
+        self.function_indices.insert(key, index);
+        let body = self.body_for(stack, op)?;
+        self.functions[index] = body;
+        Ok(index)
+    }
+
+    fn unsupported(&self, op: &dyn Parser) -> CodegenError {
+        CodegenError::Unsupported {
+            rule_name: self.rule_name.clone(),
+            op: op.to_peg_string(),
+        }
+    }
+
+    /// Writes the statements of `op_{index}`, which mirror the `parse_internal` of each op
+    fn body_for(&mut self, stack: &[RuleSet<'_>], op: &dyn Parser) -> Result<String, CodegenError> {
+        let mut body = String::new();
+        let out = &mut body;
+        match op.kind() {
+            OpKind::Literal(literal_text) => {
+                w(out, format_args!("if self.text[position..].starts_with({:?}) {{", literal_text));
+                w(out, format_args!("    Some(self.arena.push(position, position + {}, None, &[]))", literal_text.len()));
+                w(out, format_args!("}} else {{"));
+                w(out, format_args!("    None"));
+                w(out, format_args!("}}"));
+            },
+            OpKind::Regex { pattern, multi_line, case_insensitive, dot_matches_new_line } => {
+                let pattern = format!("^{}", pattern);
+                if let Err(error) = regex::RegexBuilder::new(&pattern).build() {
+                    return Err(CodegenError::InvalidRegex {
+                        rule_name: self.rule_name.clone(),
+                        pattern,
+                        message: error.to_string(),
+                    });
+                }
+                let regex_index = self.regex_index((pattern, multi_line, case_insensitive, dot_matches_new_line));
+                // The anchor only applies to the first alternative of the pattern, so a match further on is no match
+                w(out, format_args!("regex_{}().find(&self.text[position..]).filter(|re_match| re_match.start() == 0).map(|re_match| {{", regex_index));
+                w(out, format_args!("    self.arena.push(position, position + re_match.end(), None, &[])"));
+                w(out, format_args!("}})"));
+            },
+            OpKind::Sequence(children) => {
+                w(out, format_args!("let end_position = position;"));
+                let mut child_names = vec![];
+                for (number, child) in children.iter().enumerate() {
+                    let child_index = self.function_for(stack, child.as_ref())?;
+                    w(out, format_args!("let child_{} = self.nested(Self::op_{}, end_position)?;", number, child_index));
+                    w(out, format_args!("let end_position = self.end_of(child_{});", number));
+                    child_names.push(format!("child_{}", number));
+                }
+                w(out, format_args!("Some(self.arena.push(position, end_position, None, &[{}]))", child_names.join(", ")));
+            },
+            OpKind::Alternation(children) => {
+                for child in children {
+                    let child_index = self.function_for(stack, child.as_ref())?;
+                    w(out, format_args!("if let Some(child) = self.nested(Self::op_{}, position) {{", child_index));
+                    w(out, format_args!("    let end_position = self.end_of(child);"));
+                    w(out, format_args!("    return Some(self.arena.push(position, end_position, None, &[child]));"));
+                    w(out, format_args!("}}"));
+                }
+                w(out, format_args!("None"));
+            },
+            OpKind::Quantity { child, minimum_occurrences, maximum_occurrences } => {
+                let child_index = self.function_for(stack, child.as_ref())?;
+                // Like `Quantity`, stops once the child matches nothing and there are enough children;
+                // there is always at least one, as the child was just pushed
+                let enough_children = match minimum_occurrences {
+                    0 | 1 => "".to_owned(),
+                    minimum_occurrences => format!(" && children.len() >= {}", minimum_occurrences),
+                };
+                w(out, format_args!("let mut end_position = position;"));
+                w(out, format_args!("let mut children = Vec::new();"));
+                match maximum_occurrences {
+                    Some(maximum_occurrences) => {
+                        w(out, format_args!("while {} {{", fewer_children_than(maximum_occurrences)));
+                        w(out, format_args!("    match self.nested(Self::op_{}, end_position) {{", child_index));
+                        w(out, format_args!("        Some(child) => {{"));
+                        w(out, format_args!("            let child_end = self.end_of(child);"));
+                        w(out, format_args!("            children.push(child);"));
+                        w(out, format_args!("            if child_end == end_position{} {{", enough_children));
+                        w(out, format_args!("                break;"));
+                        w(out, format_args!("            }}"));
+                        w(out, format_args!("            end_position = child_end;"));
+                        w(out, format_args!("        }}"));
+                        w(out, format_args!("        None => break,"));
+                        w(out, format_args!("    }}"));
+                        w(out, format_args!("}}"));
+                    },
+                    None => {
+                        w(out, format_args!("while let Some(child) = self.nested(Self::op_{}, end_position) {{", child_index));
+                        w(out, format_args!("    let child_end = self.end_of(child);"));
+                        w(out, format_args!("    children.push(child);"));
+                        w(out, format_args!("    if child_end == end_position{} {{", enough_children));
+                        w(out, format_args!("        break;"));
+                        w(out, format_args!("    }}"));
+                        w(out, format_args!("    end_position = child_end;"));
+                        w(out, format_args!("}}"));
+                    },
+                }
+                if minimum_occurrences > 0 {
+                    w(out, format_args!("if {} {{", fewer_children_than(minimum_occurrences)));
+                    w(out, format_args!("    return None;"));
+                    w(out, format_args!("}}"));
+                }
+                w(out, format_args!("Some(self.arena.push(position, end_position, None, &children))"));
+            },
+            OpKind::Lookahead { child, scout, accept_match } => {
+                let child_index = self.function_for(stack, child.as_ref())?;
+                let scout_index = self.function_for(stack, scout.as_ref())?;
+                w(out, format_args!("let child = self.nested(Self::op_{}, position);", child_index));
+                w(out, format_args!("let end_position = child.map_or(position, |child| self.end_of(child));"));
+                w(out, format_args!("if self.nested(Self::op_{}, end_position).{}() {{", scout_index, if accept_match { "is_some" } else { "is_none" }));
+                w(out, format_args!("    child"));
+                w(out, format_args!("}} else {{"));
+                w(out, format_args!("    None"));
+                w(out, format_args!("}}"));
+            },
+            OpKind::Label { child, label } => {
+                let child_index = self.function_for(stack, child.as_ref())?;
+                let label_index = self.label_index(label);
+                w(out, format_args!("self.nested(Self::op_{}, position).map(|child| self.arena.relabel(child, self.labels[{}]))", child_index, label_index));
+            },
+            OpKind::RuleReference(rule_name) => {
+                let (resolved, rule_sets) = self.resolve_rule(stack, rule_name)?;
+                if !resolved.parameters.is_empty() {
+                    return Err(CodegenError::ParameterizedRule { rule_name: rule_name.to_owned() });
+                }
+                let rule_index = self.rule_function_for(&rule_sets, rule_name, resolved.parser_operator.as_ref())?;
+                let label_index = self.label_index(rule_name);
+                w(out, format_args!("self.rule_{}(position).map(|child| self.arena.relabel(child, self.labels[{}]))", rule_index, label_index));
+            },
+            OpKind::Grammar(grammar) => {
+                let mut rule_sets = stack.to_vec();
+                rule_sets.push(RuleSet::Borrowed(grammar));
+                let (rule_name, parser_operator) = grammar.get_starting_rule().ok_or(CodegenError::EmptyGrammar)?;
+                if !grammar.get_rule_parameters(&rule_name).is_empty() {
+                    return Err(CodegenError::ParameterizedRule { rule_name: rule_name.to_string() });
+                }
+                let rule_index = self.rule_function_for(&rule_sets, &rule_name, parser_operator.as_ref())?;
+                let label_index = self.label_index(&rule_name);
+                w(out, format_args!("self.rule_{}(position).map(|child| self.arena.relabel(child, self.labels[{}]))", rule_index, label_index));
+            },
+            OpKind::Action { .. }
+            | OpKind::AnyGrapheme
+            | OpKind::BackReference(_)
+            | OpKind::ByteClass(_)
+            | OpKind::ByteLiteral(_)
+            | OpKind::Capture { .. }
+            | OpKind::Counted { .. }
+            | OpKind::Dedent
+            | OpKind::Indent
+            | OpKind::Integer { .. }
+            | OpKind::OperatorTable(_)
+            | OpKind::Predicate { .. }
+            | OpKind::RuleCall { .. }
+            | OpKind::Samedent
+            | OpKind::SepBy { .. }
+            | OpKind::Take { .. }
+            | OpKind::Until { .. }
+            | OpKind::Other => return Err(self.unsupported(op)),
+        }
+        Ok(body)
+    }
+
+    fn finish(self, start: usize, grammar: &Grammar) -> String {
+        let mut source = String::new();
+        let out = &mut source;
+        w(out, format_args!("// Generated by npeg_rs_codegen; do not edit. The grammar is:"));
+        w(out, format_args!("//"));
+        for line in grammar.to_string().lines() {
+            w(out, format_args!("// {}", line));
+        }
+        w(out, format_args!(""));
+        w(out, format_args!("use std::collections::HashMap;"));
+        w(out, format_args!("use std::sync::OnceLock;"));
+        w(out, format_args!("use std::time::Instant;"));
+        w(out, format_args!(""));
+        w(out, format_args!("use npeg_rs::{{MatchArena, MatchId, ParseError, ParseTree, Symbol, DEFAULT_MAX_DEPTH}};"));
+        w(out, format_args!("use regex::{{Regex, RegexBuilder}};"));
+        w(out, format_args!(""));
+        w(out, format_args!("/// Parses `text` from the start with the starting rule of the grammar; `None` if it does not match"));
+        w(out, format_args!("/// or the parse was abandoned, see `try_parse`"));
+        w(out, format_args!("pub fn parse(text: &str) -> Option<ParseTree> {{"));
+        w(out, format_args!("    try_parse(text).ok().flatten()"));
+        w(out, format_args!("}}"));
+        w(out, format_args!(""));
+        w(out, format_args!("/// Like `parse`, but returns the `ParseError` if the parse was abandoned; the default `Limits` apply"));
+        w(out, format_args!("pub fn try_parse(text: &str) -> Result<Option<ParseTree>, ParseError> {{"));
+        w(out, format_args!("    try_parse_with_limits(text, &Limits::default())"));
+        w(out, format_args!("}}"));
+        w(out, format_args!(""));
+        w(out, format_args!("pub fn try_parse_with_limits(text: &str, limits: &Limits) -> Result<Option<ParseTree>, ParseError> {{"));
+        w(out, format_args!("    let mut parser = GeneratedParser::new(text, limits);"));
+        w(out, format_args!("    let root = parser.nested(GeneratedParser::op_{}, 0);", start));
+        w(out, format_args!("    match parser.error {{"));
+        w(out, format_args!("        Some(error) => Err(error),"));
+        w(out, format_args!("        None => Ok(root.map(|root| ParseTree::new(parser.arena, root))),"));
+        w(out, format_args!("    }}"));
+        w(out, format_args!("}}"));
+        w(out, format_args!(""));
+        w(out, format_args!("/// Limits the parse as the `ParserContext` builder methods of the same names do"));
+        w(out, format_args!("#[derive(Debug, Clone)]"));
+        w(out, format_args!("pub struct Limits {{"));
+        w(out, format_args!("    pub max_depth: Option<usize>,"));
+        w(out, format_args!("    pub max_steps: Option<usize>,"));
+        w(out, format_args!("    pub max_memo_entries: Option<usize>,"));
+        w(out, format_args!("    pub deadline: Option<Instant>,"));
+        w(out, format_args!("}}"));
+        w(out, format_args!(""));
+        w(out, format_args!("impl Default for Limits {{"));
+        w(out, format_args!("    fn default() -> Self {{"));
+        w(out, format_args!("        Self {{"));
+        w(out, format_args!("            max_depth: Some(DEFAULT_MAX_DEPTH),"));
+        w(out, format_args!("            max_steps: None,"));
+        w(out, format_args!("            max_memo_entries: None,"));
+        w(out, format_args!("            deadline: None,"));
+        w(out, format_args!("        }}"));
+        w(out, format_args!("    }}"));
+        w(out, format_args!("}}"));
+        for (index, (pattern, multi_line, case_insensitive, dot_matches_new_line)) in self.regexes.iter().enumerate() {
+            w(out, format_args!(""));
+            w(out, format_args!("fn regex_{}() -> &'static Regex {{", index));
+            w(out, format_args!("    static REGEX: OnceLock<Regex> = OnceLock::new();"));
+            w(out, format_args!("    REGEX.get_or_init(|| {{"));
+            w(out, format_args!("        RegexBuilder::new({:?})", pattern));
+            w(out, format_args!("            .multi_line({})", multi_line));
+            w(out, format_args!("            .case_insensitive({})", case_insensitive));
+            w(out, format_args!("            .dot_matches_new_line({})", dot_matches_new_line));
+            w(out, format_args!("            .build()"));
+            w(out, format_args!("            .expect(\"Regular expressions are checked when the code is generated\")"));
+            w(out, format_args!("    }})"));
+            w(out, format_args!("}}"));
+        }
+        w(out, format_args!(""));
+        w(out, format_args!("struct GeneratedParser<'t> {{"));
+        w(out, format_args!("    text: &'t str,"));
+        w(out, format_args!("    /// Results of rules, by rule function and start position"));
+        w(out, format_args!("    memory: HashMap<(usize, usize), Option<MatchId>>,"));
+        w(out, format_args!("    arena: MatchArena,"));
+        w(out, format_args!("    labels: Vec<Symbol>,"));
+        w(out, format_args!("    limits: Limits,"));
+        w(out, format_args!("    /// The number of ops currently being parsed, one inside the other"));
+        w(out, format_args!("    depth: usize,"));
+        w(out, format_args!("    steps: usize,"));
+        w(out, format_args!("    /// Once set, every op fails so that the parse unwinds quickly"));
+        w(out, format_args!("    error: Option<ParseError>,"));
+        w(out, format_args!("}}"));
+        w(out, format_args!(""));
+        w(out, format_args!("impl<'t> GeneratedParser<'t> {{"));
+        w(out, format_args!("    fn new(text: &'t str, limits: &Limits) -> Self {{"));
+        w(out, format_args!("        Self {{"));
+        w(out, format_args!("            text,"));
+        w(out, format_args!("            memory: HashMap::new(),"));
+        w(out, format_args!("            arena: MatchArena::new(),"));
+        w(out, format_args!("            limits: limits.clone(),"));
+        w(out, format_args!("            depth: 0,"));
+        w(out, format_args!("            steps: 0,"));
+        w(out, format_args!("            error: None,"));
+        w(out, format_args!("            labels: vec!["));
+        for label in &self.labels {
+            w(out, format_args!("                Symbol::intern({:?}),", label));
+        }
+        w(out, format_args!("            ],"));
+        w(out, format_args!("        }}"));
+        w(out, format_args!("    }}"));
+        w(out, format_args!(""));
+        w(out, format_args!("    fn end_of(&self, child: MatchId) -> usize {{"));
+        w(out, format_args!("        self.arena.get(child).get_end_position()"));
+        w(out, format_args!("    }}"));
+        w(out, format_args!(""));
+        w(out, format_args!("    /// Parses with `op` one level deeper, unless a limit has been reached; like `ParserContext::enter()`"));
+        w(out, format_args!("    fn nested(&mut self, op: fn(&mut Self, usize) -> Option<MatchId>, position: usize) -> Option<MatchId> {{"));
+        w(out, format_args!("        if self.error.is_some() {{"));
+        w(out, format_args!("            return None;"));
+        w(out, format_args!("        }}"));
+        w(out, format_args!("        let error = if self.limits.max_depth.is_some_and(|max_depth| self.depth >= max_depth) {{"));
+        w(out, format_args!("            Some(ParseError::TooDeep {{ position }})"));
+        w(out, format_args!("        }} else if self.limits.max_steps.is_some_and(|max_steps| self.steps >= max_steps) {{"));
+        w(out, format_args!("            Some(ParseError::TooManySteps {{ position }})"));
+        w(out, format_args!("        }} else if self.steps.is_multiple_of({}) && self.limits.deadline.is_some_and(|deadline| Instant::now() >= deadline) {{", DEADLINE_CHECK_INTERVAL));
+        w(out, format_args!("            Some(ParseError::DeadlineExceeded {{ position }})"));
+        w(out, format_args!("        }} else {{"));
+        w(out, format_args!("            None"));
+        w(out, format_args!("        }};"));
+        w(out, format_args!("        if error.is_some() {{"));
+        w(out, format_args!("            self.error = error;"));
+        w(out, format_args!("            return None;"));
+        w(out, format_args!("        }}"));
+        w(out, format_args!("        self.steps += 1;"));
+        w(out, format_args!("        self.depth += 1;"));
+        w(out, format_args!("        let result = op(self, position);"));
+        w(out, format_args!("        self.depth -= 1;"));
+        w(out, format_args!("        result"));
+        w(out, format_args!("    }}"));
+        for (index, body) in self.functions.iter().enumerate() {
+            if self.memoized[index] {
+                w(out, format_args!(""));
+                w(out, format_args!("    fn rule_{}(&mut self, position: usize) -> Option<MatchId> {{", index));
+                w(out, format_args!("        if let Some(result) = self.memory.get(&({}, position)) {{", index));
+                w(out, format_args!("            return *result;"));
+                w(out, format_args!("        }}"));
+                w(out, format_args!("        let result = self.nested(Self::op_{}, position);", index));
+                w(out, format_args!("        if self.error.is_some() {{"));
+                w(out, format_args!("            // The result was cut short by the error, so it must not be memoized"));
+                w(out, format_args!("            return None;"));
+                w(out, format_args!("        }}"));
+                w(out, format_args!("        if self.limits.max_memo_entries.is_some_and(|max_memo_entries| self.memory.len() >= max_memo_entries) {{"));
+                w(out, format_args!("            self.error = Some(ParseError::TooManyMemoEntries {{ position }});"));
+                w(out, format_args!("            return None;"));
+                w(out, format_args!("        }}"));
+                w(out, format_args!("        self.memory.insert(({}, position), result);", index));
+                w(out, format_args!("        result"));
+                w(out, format_args!("    }}"));
+            }
+            w(out, format_args!(""));
+            w(out, format_args!("    fn op_{}(&mut self, position: usize) -> Option<MatchId> {{", index));
+            for line in body.lines() {
+                w(out, format_args!("        {}", line));
+            }
+            w(out, format_args!("    }}"));
+        }
+        w(out, format_args!("}}"));
+        source
+    }
+}
+
+/// Like `ParserContext`, the generated parser only reads the clock once per this many steps
+const DEADLINE_CHECK_INTERVAL: usize = 256;
+
+/// The condition `children.len() < count`, written the way clippy prefers
+fn fewer_children_than(count: usize) -> String {
+    if count == 1 {
+        "children.is_empty()".into()
+    } else {
+        format!("children.len() < {}", count)
+    }
+}
+
+/// Writes one line of generated code
+fn w(out: &mut String, line: fmt::Arguments<'_>) {
+    out.write_fmt(line).expect("Writing to a String does not fail");
+    out.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use npeg_rs::*;
+
+    use super::{generate, CodegenError};
+
+    #[test]
+    fn unsupported_ops_are_reported() {
+        let gram = Grammar::new(None, vec![
+            ("Raw",    seq!(cap!(reg!("#*"), "hashes"), lit!("\""), brf!("hashes"))),
+        ]);
+        assert_eq!(
+            generate(&gram),
+            Err(CodegenError::Unsupported { rule_name: "Raw".into(), op: "@capture(hashes, r\"#*\")".into() })
+        );
+    }
+
+    #[test]
+    fn missing_and_parameterized_rules_are_reported() {
+        let gram = Grammar::new(None, vec![("A", rul!("B"))]);
+        assert_eq!(generate(&gram), Err(CodegenError::RuleNotFound { rule_name: "A".into(), reference: "B".into() }));
+        let gram = Grammar::new(None, vec![("A", rul!("List"))]).with_parameterized_rule("List", vec!["X"], rul!("X"));
+        assert_eq!(generate(&gram), Err(CodegenError::ParameterizedRule { rule_name: "List".into() }));
+    }
+
+    #[test]
+    fn rules_are_generated_once_per_grammar_stack() {
+        let json = Rc::new(Grammar::new(None, vec![
+            ("Value",  alt!(rul!("Number"), lit!("null"))),
+            ("Number", reg!("[0-9]+")),
+        ]));
+        let gram = Grammar::new(None, vec![
+            ("Pair",   seq!(rul!("json::Value"), lit!(","), rul!("json::Value"), lit!(","), rul!("Number"))),
+            ("Number", lit!("x")),
+        ]).with_import("json", json);
+        let source = generate(&gram).unwrap();
+        // Pair, json::Value, json::Number and Number
+        assert_eq!(source.matches("fn rule_").count(), 4);
+        assert!(source.contains("RegexBuilder::new(\"^[0-9]+\")"));
+        assert!(source.contains("// Pair <- json::Value \",\" json::Value \",\" Number"));
+    }
+}